@@ -1,6 +1,7 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
 
 use super::opts::Wordlist;
+use crate::runner::{filters::utils::decode_hex, ntlm, sigv4};
 use clap::{
     builder::TypedValueParser,
     error::{ContextKind, ContextValue, ErrorKind},
@@ -169,6 +170,56 @@ pub fn parse_cookie(s: &str) -> Result<String, String> {
     Ok(s.to_string())
 }
 
+pub fn parse_ntlm(s: &str) -> Result<String, String> {
+    match ntlm::Credentials::parse(s) {
+        Some(_) => Ok(s.to_string()),
+        None => Err("Invalid credentials, expected [DOMAIN\\]USER:PASS".to_string()),
+    }
+}
+
+pub fn parse_depth_wordlist(s: &str) -> Result<String, String> {
+    match s.split_once(':') {
+        Some((depth, file)) if depth.parse::<usize>().is_ok_and(|depth| depth > 0) => {
+            parse_wordlist(file)?;
+            Ok(s.to_string())
+        }
+        _ => Err("Invalid wordlist, expected DEPTH:FILE with a depth of at least 1".to_string()),
+    }
+}
+
+pub fn parse_backoff(s: &str) -> Result<String, String> {
+    match s.split_once(':') {
+        Some((status, delay)) if status.parse::<u16>().is_ok() && delay.parse::<u64>().is_ok() => {
+            Ok(s.to_string())
+        }
+        _ => Err("Invalid backoff, expected STATUS:MILLISECONDS".to_string()),
+    }
+}
+
+pub fn parse_hex(s: &str) -> Result<String, String> {
+    match decode_hex(s) {
+        Some(bytes) if !bytes.is_empty() => Ok(s.to_string()),
+        _ => Err("Invalid bytes, expected an even number of hex digits".to_string()),
+    }
+}
+
+pub fn parse_regex(s: &str) -> Result<String, String> {
+    match regex::Regex::new(s) {
+        Ok(_) => Ok(s.to_string()),
+        Err(e) => Err(format!("Invalid regex: {}", e)),
+    }
+}
+
+pub fn parse_sigv4(s: &str) -> Result<String, String> {
+    match sigv4::Credentials::parse(s) {
+        Some(_) => Ok(s.to_string()),
+        None => Err(
+            "Invalid credentials, expected ACCESS_KEY:SECRET_KEY:REGION:SERVICE[:SESSION_TOKEN]"
+                .to_string(),
+        ),
+    }
+}
+
 pub fn parse_method(s: &str) -> Result<String, String> {
     let methods = [
         "GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "TRACE", "CONNECT",
@@ -181,7 +232,24 @@ pub fn parse_method(s: &str) -> Result<String, String> {
     }
 }
 
+/// Check if a wordlist has to be downloaded
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
 pub fn parse_wordlist(s: &str) -> Result<Wordlist, String> {
+    // Remote wordlists contain colons, the key is only what follows the last one in the path (the authority may hold a port)
+    if is_remote(s) {
+        let authority = s.find("://").map_or(0, |i| i + 3);
+        let path = s[authority..].find('/').map_or(s.len(), |i| authority + i);
+        return Ok(match s[path..].rsplit_once(':') {
+            Some((rest, keys)) if !keys.contains('/') => Wordlist(
+                format!("{}{}", &s[..path], rest),
+                keys.split(',').map(|x| x.to_string()).collect(),
+            ),
+            _ => Wordlist(s.to_string(), vec![]),
+        });
+    }
     let parts = s.split(':').collect::<Vec<_>>();
     if parts.len() == 1 {
         // Wordlist without a key
@@ -197,6 +265,33 @@ pub fn parse_wordlist(s: &str) -> Result<Wordlist, String> {
     }
 }
 
+/// Spans of the `--filter-or` groups, from the positions of the `--filter-or` and `--end-or` flags on the command line
+pub fn or_groups(starts: &[usize], ends: &[usize]) -> Result<Vec<Range<usize>>, String> {
+    let mut flags = starts
+        .iter()
+        .map(|index| (*index, true))
+        .chain(ends.iter().map(|index| (*index, false)))
+        .collect::<Vec<_>>();
+    flags.sort();
+    let mut groups = Vec::new();
+    let mut start = None;
+    for (index, opens) in flags {
+        match (opens, start) {
+            (true, None) => start = Some(index),
+            (true, Some(_)) => return Err("--filter-or groups can't be nested".into()),
+            (false, Some(from)) => {
+                groups.push(from..index);
+                start = None;
+            }
+            (false, None) => return Err("--end-or without a matching --filter-or".into()),
+        }
+    }
+    if start.is_some() {
+        return Err("--filter-or without a matching --end-or".into());
+    }
+    Ok(groups)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -298,5 +393,29 @@ mod tests {
             Wordlist("key".to_string(), vec!["".to_string()])
         );
         assert!(parse_wordlist("key:wordlist1,wordlist2:").is_err());
+        assert_eq!(
+            parse_wordlist("https://example.com/list.txt").unwrap(),
+            Wordlist("https://example.com/list.txt".to_string(), vec![])
+        );
+        assert_eq!(
+            parse_wordlist("http://example.com:8080").unwrap(),
+            Wordlist("http://example.com:8080".to_string(), vec![])
+        );
+        assert_eq!(
+            parse_wordlist("https://example.com:8080/list.txt:W1,W2").unwrap(),
+            Wordlist(
+                "https://example.com:8080/list.txt".to_string(),
+                vec!["W1".to_string(), "W2".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_or_groups() {
+        assert_eq!(or_groups(&[3, 9], &[7, 11]).unwrap(), vec![3..7, 9..11]);
+        assert!(or_groups(&[], &[]).unwrap().is_empty());
+        assert!(or_groups(&[3], &[]).is_err());
+        assert!(or_groups(&[], &[3]).is_err());
+        assert!(or_groups(&[3, 4], &[5]).is_err());
     }
 }
@@ -0,0 +1,59 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::cli::interactive::{Command, State};
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use colored::Colorize;
+use rhai::{Engine, Scope};
+use rustyline::DefaultEditor;
+use tokio::sync::Mutex;
+#[derive(Debug)]
+pub struct ExportCommand;
+
+#[async_trait]
+impl Command for ExportCommand {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn description(&self) -> &'static str {
+        "Export the tree of the current scan to a JSON file, without stopping it"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+        vec!["x", "snapshot"]
+    }
+
+    async fn run(
+        &self,
+        _rl: Arc<Mutex<DefaultEditor>>,
+        args: Vec<&str>,
+        state: Arc<Mutex<State>>,
+        _engine: Arc<Mutex<Engine>>,
+        _scope: Arc<Mutex<Scope<'_>>>,
+    ) -> Result<()> {
+        if args.len() != 1 {
+            println!("Usage: export <file>");
+            return Ok(());
+        }
+        let output = PathBuf::from(args[0]);
+        let root = state.lock().await.tree.lock().root.clone();
+        let Some(root) = root else {
+            println!("No scan to export, start one with {}", "run".bold());
+            return Ok(());
+        };
+        // Snapshot of the tree so far, the scan keeps adding to it
+        let root = root.lock().clone();
+        let content = serde_json::to_string(&root)?;
+        if let Some(parent) = output.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&output, content).await?;
+        println!(
+            "Exported {} results to {}",
+            (root.flatten().len() - 1).to_string().bold(),
+            output.to_string_lossy().bold()
+        );
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 pub mod append;
 pub mod eval;
+pub mod export;
 pub mod get;
 pub mod list;
 pub mod load;
@@ -8,3 +9,4 @@ pub mod remove;
 pub mod run;
 pub mod save;
 pub mod set;
+pub mod start;
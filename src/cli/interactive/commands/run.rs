@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use crate::{
-    _main,
+    _main_with,
     cli::interactive::{Command, State},
+    Interactive,
 };
 use async_trait::async_trait;
 use color_eyre::eyre::Result;
@@ -36,7 +37,15 @@ impl Command for RunCommand {
         _scope: Arc<Mutex<Scope<'_>>>,
     ) -> Result<()> {
         let mut state = state.lock().await;
-        let res = _main(state.opts.clone()).await;
+        if state.scan.as_ref().is_some_and(|scan| !scan.is_finished()) {
+            println!("A scan is already running in the background");
+            return Ok(());
+        }
+        let interactive = Interactive {
+            tree: Some(state.tree.clone()),
+            background: false,
+        };
+        let res = _main_with(state.opts.clone(), &interactive).await;
         match res {
             Ok(r) => {
                 if let Some(root) = r.root {
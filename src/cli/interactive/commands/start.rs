@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use crate::{
+    _main_with,
+    cli::{
+        interactive::{Command, State},
+        opts::Opts,
+    },
+    Interactive,
+};
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use log::error;
+use rhai::{Engine, Scope};
+use rustyline::DefaultEditor;
+use tokio::sync::Mutex;
+#[derive(Debug)]
+pub struct StartCommand;
+
+#[async_trait]
+impl Command for StartCommand {
+    fn name(&self) -> &'static str {
+        "start"
+    }
+
+    fn description(&self) -> &'static str {
+        "Runs the current tree in the background, to export it meanwhile"
+    }
+
+    fn aliases(&self) -> Vec<&'static str> {
+        vec!["bg", "background"]
+    }
+
+    async fn run(
+        &self,
+        _rl: Arc<Mutex<DefaultEditor>>,
+        _args: Vec<&str>,
+        state: Arc<Mutex<State>>,
+        _engine: Arc<Mutex<Engine>>,
+        _scope: Arc<Mutex<Scope<'_>>>,
+    ) -> Result<()> {
+        let mut guard = state.lock().await;
+        if guard.scan.as_ref().is_some_and(|scan| !scan.is_finished()) {
+            println!("A scan is already running in the background");
+            return Ok(());
+        }
+        // The results would be printed over the prompt, they are exported instead
+        let opts = Opts {
+            quiet: true,
+            ..guard.opts.clone()
+        };
+        let interactive = Interactive {
+            tree: Some(guard.tree.clone()),
+            background: true,
+        };
+        let state = state.clone();
+        guard.scan = Some(tokio::spawn(async move {
+            match _main_with(opts, &interactive).await {
+                Ok(r) => {
+                    if let Some(root) = r.root {
+                        let root = root.lock().clone();
+                        state.lock().await.last_result = Some(root);
+                    }
+                    println!("The background scan is done");
+                }
+                Err(e) => error!("{}", e),
+            }
+        }));
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ use colored::Colorize;
 use commands::{
     append::AppendCommand,
     eval::EvalCommand,
+    export::ExportCommand,
     get::GetCommand,
     list::ListCommand,
     load::LoadCommand,
@@ -13,17 +14,18 @@ use commands::{
     run::RunCommand,
     save::SaveCommand,
     set::SetCommand,
+    start::StartCommand,
 };
 
 use rhai::{exported_module, Engine, Scope};
 use rustyline::DefaultEditor;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
-use tokio::sync::Mutex;
+use tokio::{sync::Mutex, task::JoinHandle};
 
 use crate::utils::{
     scripting::{tree_data, tree_node, ScriptingResponse},
-    tree::{TreeData, TreeNode},
+    tree::{Tree, TreeData, TreeNode},
 };
 
 use super::opts::Opts;
@@ -35,6 +37,10 @@ mod commands;
 pub struct State {
     pub opts: Opts,
     pub last_result: Option<TreeNode<TreeData>>,
+    /// Scan started in the background with `start`, so the tree can be exported meanwhile
+    pub scan: Option<JoinHandle<()>>,
+    /// Tree of the last scan, filled as it runs for `export`
+    pub tree: Arc<parking_lot::Mutex<Tree<TreeData>>>,
 }
 
 unsafe impl Send for State {}
@@ -80,13 +86,17 @@ pub async fn main_interactive(opts: Opts) -> Result<()> {
         Box::new(RemoveCommand),
         Box::new(EvalCommand),
         Box::new(RunCommand),
+        Box::new(StartCommand),
         Box::new(ListCommand),
         Box::new(SaveCommand),
         Box::new(LoadCommand),
+        Box::new(ExportCommand),
     ];
     let state = State {
         opts,
         last_result: None,
+        scan: None,
+        tree: Arc::default(),
     };
     let state = Arc::new(Mutex::new(state));
 
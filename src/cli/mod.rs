@@ -1,3 +1,6 @@
 pub mod helpers;
 pub mod interactive;
 pub mod opts;
+pub mod profile;
+pub mod recipe;
+pub mod tui;
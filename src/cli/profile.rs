@@ -0,0 +1,134 @@
+use color_eyre::eyre::{eyre, Result};
+use merge::Merge;
+use serde::Deserialize;
+
+use super::{helpers::KeyVal, opts::Opts};
+
+/// Profiles usable by name with `--profile`
+const BUILTIN: [(&str, &str); 2] = [
+    (
+        "quick",
+        // Only the most interesting hits, skipping the empty pages
+        r#"
+filter = ["status:200-299,401,403"]
+min_content_length = 1
+"#,
+    ),
+    (
+        "thorough",
+        // Everything but the missing pages, with what's needed to triage them
+        r#"
+filter = ["!status:404"]
+show = ["type", "length", "hash"]
+filter_root_similar = true
+"#,
+    ),
+];
+
+/// Set of filter, match and show options loaded with `--profile`
+///
+/// The lists of several profiles are concatenated, for single values the first profile wins.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Merge)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[merge(strategy = merge::vec::append)]
+    #[serde(default)]
+    pub filter: Vec<KeyVal<String, String>>,
+    #[merge(strategy = merge::bool::overwrite_false)]
+    #[serde(default)]
+    pub or: bool,
+    pub min_content_length: Option<usize>,
+    pub filter_bytes: Option<String>,
+    pub filter_set_cookie: Option<String>,
+    pub match_set_cookie: Option<String>,
+    #[merge(strategy = merge::bool::overwrite_false)]
+    #[serde(default)]
+    pub filter_root_similar: bool,
+    pub filter_word: Option<String>,
+    pub match_word: Option<String>,
+    #[merge(strategy = merge::vec::append)]
+    #[serde(default)]
+    pub show: Vec<String>,
+    #[merge(strategy = merge::vec::append)]
+    #[serde(default)]
+    pub show_json: Vec<String>,
+}
+
+impl Profile {
+    /// Load a built-in profile by its name, or a TOML file
+    pub async fn from_name_or_path(name: &str) -> Result<Self> {
+        let contents = match BUILTIN.iter().find(|(builtin, _)| *builtin == name) {
+            Some((_, contents)) => contents.to_string(),
+            None => tokio::fs::read_to_string(name)
+                .await
+                .map_err(|e| eyre!("Failed to read profile {}: {}", name, e))?,
+        };
+        toml::from_str(&contents).map_err(|e| eyre!("Invalid profile {}: {}", name, e))
+    }
+
+    /// Load and combine the profiles in the given order
+    pub async fn load(names: &[String]) -> Result<Self> {
+        let mut profile = Self::default();
+        for name in names {
+            profile.merge(Self::from_name_or_path(name).await?);
+        }
+        Ok(profile)
+    }
+}
+
+impl From<Profile> for Opts {
+    fn from(profile: Profile) -> Self {
+        Self {
+            filter: profile.filter,
+            or: profile.or,
+            min_content_length: profile.min_content_length,
+            filter_bytes: profile.filter_bytes,
+            filter_set_cookie: profile.filter_set_cookie,
+            match_set_cookie: profile.match_set_cookie,
+            filter_root_similar: profile.filter_root_similar,
+            filter_word: profile.filter_word,
+            match_word: profile.match_word,
+            show: profile.show,
+            show_json: profile.show_json,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_builtin_profiles() {
+        for (name, _) in BUILTIN {
+            assert!(Profile::from_name_or_path(name).await.is_ok());
+        }
+        assert!(Profile::from_name_or_path("missing.toml").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let profile = Profile::load(&["quick".to_string(), "thorough".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            profile.filter,
+            vec![
+                KeyVal("status".to_string(), "200-299,401,403".to_string()),
+                KeyVal("!status".to_string(), "404".to_string())
+            ]
+        );
+        assert_eq!(profile.min_content_length, Some(1));
+        assert!(profile.filter_root_similar);
+
+        // The options given on the command line come first
+        let mut opts = Opts {
+            show: vec!["body".to_string()],
+            ..Default::default()
+        };
+        opts.merge(Opts::from(profile));
+        assert_eq!(opts.show, vec!["body"]);
+        assert_eq!(opts.filter.len(), 2);
+    }
+}
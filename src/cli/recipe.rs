@@ -0,0 +1,68 @@
+//! Scan setups shared as TOML files in `~/.config/rwalk/recipes` (`--recipe`)
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Result};
+use colored::Colorize;
+
+use super::opts::Opts;
+use crate::utils::constants::DEFAULT_RECIPES_PATH;
+
+/// Directory holding the recipes
+pub fn dir() -> Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(DEFAULT_RECIPES_PATH))
+        .ok_or_else(|| eyre!("No home directory found"))
+}
+
+/// Load the options of a recipe by its name
+pub async fn load(dir: &Path, name: &str) -> Result<Opts> {
+    let path = dir.join(format!("{}.toml", name));
+    if !path.exists() {
+        return Err(eyre!(
+            "Unknown recipe {}, use {} to see the available ones",
+            name.bold(),
+            "--list-recipes".bold()
+        ));
+    }
+    Opts::from_path(&path)
+        .await
+        .map_err(|e| eyre!("Invalid recipe {}: {}", name.bold(), e))
+}
+
+/// Names of the recipes, sorted
+pub fn list(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recipes() {
+        let dir = std::env::temp_dir().join(format!("rwalk-recipes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("wordpress.toml"), "filter = [\"!status:404\"]\n").unwrap();
+        std::fs::write(dir.join("api.toml"), "threads = 5\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        assert_eq!(list(&dir).unwrap(), vec!["api", "wordpress"]);
+        assert_eq!(load(&dir, "api").await.unwrap().threads, Some(5));
+        assert!(load(&dir, "missing").await.is_err());
+        assert!(list(&dir.join("missing")).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
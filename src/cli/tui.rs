@@ -0,0 +1,413 @@
+use std::{
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use color_eyre::eyre::Result;
+use log::warn;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+
+use super::opts::Opts;
+
+/// How often the table is redrawn when nothing happens
+const TICK: Duration = Duration::from_millis(100);
+/// Number of rows skipped with PageUp/PageDown
+const PAGE: usize = 10;
+
+/// A response that passed the filters, as shown in the live table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub status_code: u16,
+    pub size: usize,
+    pub time: u128,
+    pub url: String,
+}
+
+/// Channel the runners push their findings into
+pub type Findings = UnboundedSender<Finding>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SortBy {
+    /// Order in which the findings came in
+    #[default]
+    Arrival,
+    Status,
+    Size,
+    Time,
+    Url,
+}
+
+impl SortBy {
+    fn next(self) -> Self {
+        match self {
+            SortBy::Arrival => SortBy::Status,
+            SortBy::Status => SortBy::Size,
+            SortBy::Size => SortBy::Time,
+            SortBy::Time => SortBy::Url,
+            SortBy::Url => SortBy::Arrival,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortBy::Arrival => "arrival",
+            SortBy::Status => "status",
+            SortBy::Size => "size",
+            SortBy::Time => "time",
+            SortBy::Url => "url",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    findings: Vec<Finding>,
+    sort: SortBy,
+    reverse: bool,
+    filter: String,
+    /// Whether the keys are currently typed into the filter
+    filtering: bool,
+    table: TableState,
+}
+
+impl State {
+    /// Findings matching the filter, in the selected order
+    fn visible(&self) -> Vec<&Finding> {
+        let mut visible = self
+            .findings
+            .iter()
+            .filter(|finding| {
+                finding.url.contains(&self.filter) || finding.status_code.to_string() == self.filter
+            })
+            .collect::<Vec<_>>();
+        match self.sort {
+            SortBy::Arrival => {}
+            SortBy::Status => visible.sort_by_key(|finding| finding.status_code),
+            SortBy::Size => visible.sort_by_key(|finding| finding.size),
+            SortBy::Time => visible.sort_by_key(|finding| finding.time),
+            SortBy::Url => visible.sort_by(|a, b| a.url.cmp(&b.url)),
+        }
+        if self.reverse {
+            visible.reverse();
+        }
+        visible
+    }
+
+    /// Move the selection by `offset` rows, staying within the visible findings
+    fn scroll(&mut self, offset: isize) {
+        let len = self.visible().len();
+        if len == 0 {
+            self.table.select(None);
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        self.table
+            .select(Some((current + offset).clamp(0, len as isize - 1) as usize));
+    }
+
+    fn render(&mut self, frame: &mut Frame, finished: bool) {
+        let [table_area, footer_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.size());
+
+        let visible = self.visible();
+        let title = format!(
+            " {} findings{} ",
+            visible.len(),
+            if finished { ", scan finished" } else { "" }
+        );
+        let rows = visible
+            .iter()
+            .map(|finding| {
+                Row::new(vec![
+                    finding.status_code.to_string(),
+                    finding.size.to_string(),
+                    format!("{}ms", finding.time),
+                    finding.url.clone(),
+                ])
+                .style(Style::default().fg(color_for_status_code(finding.status_code)))
+            })
+            .collect::<Vec<_>>();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(6),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Min(0),
+            ],
+        )
+        .header(
+            Row::new(vec!["Status", "Size", "Time", "URL"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let footer = if self.filtering {
+            format!("Filter: {}_ (Enter to apply, Esc to clear)", self.filter)
+        } else {
+            format!(
+                "q: quit | s: sort ({}{}) | r: reverse | /: filter{} | ↑↓ PgUp PgDn Home End: scroll",
+                self.sort.name(),
+                if self.reverse { ", reversed" } else { "" },
+                if self.filter.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", self.filter)
+                }
+            )
+        };
+        frame.render_widget(
+            Paragraph::new(Line::from(footer)).style(Style::default().fg(Color::DarkGray)),
+            footer_area,
+        );
+    }
+
+    /// Handle a key press, returns `true` when the user wants to quit
+    fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+            return true;
+        }
+        if self.filtering {
+            match code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.filtering = false;
+                }
+                _ => {}
+            }
+            self.scroll(0);
+            return false;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('r') => self.reverse = !self.reverse,
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll(-1),
+            KeyCode::PageDown => self.scroll(PAGE as isize),
+            KeyCode::PageUp => self.scroll(-(PAGE as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => self.scroll(isize::MAX / 2),
+            _ => {}
+        }
+        false
+    }
+}
+
+fn color_for_status_code(status_code: u16) -> Color {
+    match status_code {
+        100..=199 => Color::Blue,
+        200..=299 => Color::Green,
+        300..=399 => Color::Yellow,
+        400..=499 => Color::Rgb(255, 165, 0),
+        500..=599 => Color::Red,
+        _ => Color::Reset,
+    }
+}
+
+/// Live table of the findings, replacing the scrolling output (`--tui`)
+pub struct Tui {
+    findings: Findings,
+    finished: Arc<AtomicBool>,
+    /// Set when the scan fails, to close the table without waiting for the user
+    stop: Arc<AtomicBool>,
+    /// Taken by [`Tui::finish`], otherwise joined on drop so the terminal is always restored
+    handle: Option<std::thread::JoinHandle<Result<()>>>,
+}
+
+impl Tui {
+    /// Start the table in the alternate screen
+    ///
+    /// Returns `None` when `--tui` is not set or when stdout is not a terminal, in which case the classic output is used.
+    /// The receiver fires if the user quits before the end of the scan.
+    pub fn start(opts: &Opts) -> Result<Option<(Self, oneshot::Receiver<()>)>> {
+        if !opts.tui {
+            return Ok(None);
+        }
+        if !std::io::stdout().is_terminal() {
+            warn!("Not a terminal, falling back to the classic output");
+            return Ok(None);
+        }
+        let (findings, rx) = tokio::sync::mpsc::unbounded_channel();
+        let (quit_tx, quit_rx) = oneshot::channel();
+        let finished = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        let terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+        // Logs would be drawn over the table
+        let max_level = log::max_level();
+        log::set_max_level(log::LevelFilter::Off);
+
+        let thread_finished = finished.clone();
+        let thread_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let res = run(terminal, rx, &thread_finished, &thread_stop);
+            let restored =
+                disable_raw_mode().and_then(|_| execute!(std::io::stdout(), LeaveAlternateScreen));
+            log::set_max_level(max_level);
+            // Quitting before the end of the scan aborts it, just like Ctrl+C
+            if !thread_finished.load(Ordering::Relaxed) {
+                let _ = quit_tx.send(());
+            }
+            res?;
+            Ok(restored?)
+        });
+        Ok(Some((
+            Self {
+                findings,
+                finished,
+                stop,
+                handle: Some(handle),
+            },
+            quit_rx,
+        )))
+    }
+
+    pub fn findings(&self) -> Findings {
+        self.findings.clone()
+    }
+
+    /// Mark the scan as finished and wait for the user to close the table
+    pub async fn finish(mut self) -> Result<()> {
+        self.finished.store(true, Ordering::Relaxed);
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || {
+            handle
+                .join()
+                .map_err(|_| color_eyre::eyre::eyre!("The TUI thread panicked"))?
+        })
+        .await?
+    }
+}
+
+impl Drop for Tui {
+    /// The scan failed before [`Tui::finish`], leave the alternate screen before the error is printed
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run(
+    mut terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
+    mut rx: UnboundedReceiver<Finding>,
+    finished: &AtomicBool,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let mut state = State::default();
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        while let Ok(finding) = rx.try_recv() {
+            state.findings.push(finding);
+        }
+        if state.table.selected().is_none() {
+            state.scroll(0);
+        }
+        terminal.draw(|frame| state.render(frame, finished.load(Ordering::Relaxed)))?;
+
+        if event::poll(TICK)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && state.handle_key(key.code, key.modifiers) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(status_code: u16, size: usize, url: &str) -> Finding {
+        Finding {
+            status_code,
+            size,
+            time: 0,
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_visible() {
+        let mut state = State {
+            findings: vec![
+                finding(404, 10, "http://localhost/b"),
+                finding(200, 30, "http://localhost/a"),
+                finding(301, 20, "http://localhost/admin"),
+            ],
+            ..Default::default()
+        };
+        let urls = |state: &State| {
+            state
+                .visible()
+                .iter()
+                .map(|finding| finding.url.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(urls(&state)[0], "http://localhost/b");
+
+        state.sort = SortBy::Status;
+        assert_eq!(urls(&state)[0], "http://localhost/a");
+        state.reverse = true;
+        assert_eq!(urls(&state)[0], "http://localhost/b");
+
+        state.sort = SortBy::Size;
+        state.reverse = false;
+        state.filter = "/a".to_string();
+        assert_eq!(
+            urls(&state),
+            vec!["http://localhost/admin", "http://localhost/a"]
+        );
+
+        state.filter = "404".to_string();
+        assert_eq!(urls(&state), vec!["http://localhost/b"]);
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let mut state = State::default();
+        assert!(!state.handle_key(KeyCode::Char('/'), KeyModifiers::NONE));
+        assert!(!state.handle_key(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert_eq!(state.filter, "q");
+        assert!(!state.handle_key(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(state.handle_key(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert!(state.handle_key(KeyCode::Char('c'), KeyModifiers::CONTROL));
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -9,13 +9,13 @@ use std::{
 };
 
 use crate::{
-    cli::{helpers::KeyVal, opts::Opts},
-    runner::{
-        wordlists::{compute_checksum, ParsedWordlist},
-        Runner,
-    },
+    cli::{helpers::KeyVal, opts::Opts, tui::Tui},
+    runner::{breaker::Tripped, session::Session, wordlists::compute_checksum, Runner},
     utils::{
-        constants::{DEFAULT_FUZZ_KEY, DEFAULT_MODE, DEFAULT_STATUS_CODES},
+        constants::{
+            DEFAULT_CIRCUIT_WINDOW, DEFAULT_FUZZ_KEY, DEFAULT_HISTOGRAM_BUCKETS, DEFAULT_MODE,
+            DEFAULT_STATUS_CODES,
+        },
         table::build_opts_table,
     },
 };
@@ -33,8 +33,8 @@ use url::Url;
 use utils::{structs::FuzzMatch, tree::UrlType};
 
 use crate::utils::{
-    constants::SUCCESS,
     structs::{Mode, Save},
+    theme,
     tree::{Tree, TreeData},
 };
 
@@ -42,11 +42,262 @@ pub mod cli;
 pub mod runner;
 pub mod utils;
 
+/// How a scan is run from the interactive mode
+#[derive(Debug, Clone, Default)]
+pub struct Interactive {
+    /// Filled with the tree of the scan, so that it can be exported while it runs
+    pub tree: Option<Arc<Mutex<Tree<TreeData>>>>,
+    /// Started with `start`, nothing is drawn over the prompt and Ctrl-C is left to it
+    pub background: bool,
+}
+
 pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
-    if opts.url.is_none() && !opts.resume {
+    _main_with(opts, &Interactive::default()).await
+}
+
+pub async fn _main_with(opts: Opts, interactive: &Interactive) -> Result<Tree<TreeData>> {
+    utils::check_format(&opts)?;
+    utils::check_append(&opts)?;
+    if let Some(path) = &opts.rerun_errors {
+        return rerun_errors(opts.clone(), path).await;
+    }
+    if let Some(path) = &opts.passive {
+        return passive(&opts, path);
+    }
+    let Some(path) = opts.urls_file.clone() else {
+        return scan(opts, None, None, interactive).await?.into_tree();
+    };
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| eyre!("Failed to read {}: {}", path.bold(), e))?;
+    let targets = runner::classic::parse_urls_file(&content)?;
+    if targets.is_empty() {
+        bail!("No URLs found in {}", path.bold());
+    }
+    // Resolve all the hosts at once, so dead ones are known before scanning
+    let hosts = targets
+        .iter()
+        .filter_map(|target| Some(Url::parse(&target.url).ok()?.domain()?.to_string()))
+        .unique()
+        .collect::<Vec<_>>();
+    // The scans of the URLs share the resolver, so these hosts are not resolved again
+    let resolver = runner::dns::Resolver::from_opts(&opts)?;
+    let unresolved = runner::dns::preresolve(&resolver, &hosts).await;
+    if !unresolved.is_empty() {
+        if !opts.skip_unresolvable {
+            bail!(
+                "Could not resolve {}, use --skip-unresolvable to skip them",
+                unresolved.join(", ").bold()
+            );
+        }
+        warn!(
+            "Skipping the URLs of {}, they could not be resolved",
+            unresolved.join(", ").bold()
+        );
+    }
+    let targets = targets
+        .into_iter()
+        .filter(|target| {
+            Url::parse(&target.url)
+                .ok()
+                .and_then(|url| {
+                    url.domain()
+                        .map(|domain| !unresolved.iter().any(|host| host == domain))
+                })
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        bail!("None of the URLs of {} could be resolved", path.bold());
+    }
+    // URLs with options of their own are scanned one after the other, the others are requested as-is together
+    let (fixed_urls, targets): (Vec<_>, Vec<_>) = targets
+        .into_iter()
+        .partition(|target| target.overrides.is_empty());
+    // Each scan writes its own results, the ones sharing the outputs would replace each other's
+    let shared = targets
+        .iter()
+        .filter(|target| !target.overrides.iter().any(|(key, _)| key == "output"))
+        .count()
+        + usize::from(!fixed_urls.is_empty());
+    if shared > 1 && !opts.output.is_empty() && !opts.append {
+        bail!(
+            "{} scans of {} would write to {}, give their lines an output= of their own or use --append",
+            shared,
+            path.bold(),
+            opts.output.join(", ").bold()
+        );
+    }
+    let mut trees = Vec::new();
+    let mut tripped = false;
+    for target in &targets {
+        info!(
+            "Scanning {} with {}",
+            target.url.bold(),
+            target
+                .overrides
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .join(" ")
+                .dimmed()
+        );
+        let scanned = scan(
+            target.opts(&opts)?,
+            None,
+            Some(resolver.clone()),
+            interactive,
+        )
+        .await?;
+        tripped |= scanned.tripped;
+        trees.push(scanned.tree);
+    }
+    if !fixed_urls.is_empty() {
+        let urls = fixed_urls.into_iter().map(|target| target.url).collect();
+        let scanned = scan(opts, Some(urls), Some(resolver), interactive).await?;
+        tripped |= scanned.tripped;
+        trees.push(scanned.tree);
+    }
+    if tripped {
+        return Err(Tripped.into());
+    }
+    Ok(utils::tree::merge(trees, &path))
+}
+
+/// Request again the URLs that failed in a previous run (`--rerun-errors`)
+///
+/// The options of the previous run are used for the ones not given.
+async fn rerun_errors(mut opts: Opts, path: &str) -> Result<Tree<TreeData>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| eyre!("Failed to read {}: {}", path.bold(), e))?;
+    let save = serde_json::from_str::<Save>(&content)
+        .map_err(|e| eyre!("Invalid save file {}: {}", path.bold(), e))?;
+    if save.errors.is_empty() {
+        bail!("No failed requests in {}", path.bold());
+    }
+    opts.merge(save.opts);
+    opts.rerun_errors = None;
+    opts.resume = false;
+    let total = save.errors.len();
+    info!("Retrying {} failed requests", total.to_string().bold());
+    let scanned = scan(opts, Some(save.errors), None, &Interactive::default()).await?;
+    info!(
+        "{} of the {} failed URLs now resolve",
+        total
+            .saturating_sub(scanned.errors.len())
+            .to_string()
+            .bold(),
+        total
+    );
+    scanned.into_tree()
+}
+
+/// Run the responses captured by a previous scan through the filters, without sending any request (`--passive`)
+fn passive(opts: &Opts, path: &str) -> Result<Tree<TreeData>> {
+    // Same filters as during a scan
+    let mut opts = opts.clone();
+    default_status_filter(&mut opts);
+    let opts = &opts;
+    let captures = runner::passive::load(path)?;
+    let Some(first) = captures.first() else {
+        bail!("No responses found in {}", path.bold());
+    };
+    // Without a URL, the origin of the first capture is used as the root
+    let root_url = match &opts.url {
+        Some(url) => url.clone(),
+        None => Url::parse(&first.url)?.origin().ascii_serialization() + "/",
+    };
+    let tree = Arc::new(Mutex::new(Tree::new()));
+    tree.lock().insert(
+        TreeData {
+            url: root_url.clone(),
+            depth: 0,
+            path: Url::parse(&root_url)?
+                .path()
+                .trim_end_matches('/')
+                .to_string(),
+            status_code: 0,
+            extra: serde_json::Value::Null,
+            url_type: UrlType::Directory,
+            response: None,
+        },
+        None,
+    );
+    let session = Session {
+        baseline: opts
+            .baseline_file
+            .as_deref()
+            .map(runner::filters::baseline::Baseline::load)
+            .transpose()?,
+        ..Session::filters(opts)
+    };
+    let kept = runner::passive::replay(opts, &session, &captures, &tree)?;
+    let root = tree.lock().root.clone().unwrap();
+    if !opts.quiet {
+        info!(
+            "{} of the {} captured responses passed the filters",
+            kept.to_string().bold(),
+            captures.len()
+        );
+        if session.printed.hidden() == 0 {
+            print_tree(&*root.lock())?;
+        }
+    }
+    if !opts.output.is_empty() {
+        let depth = Arc::new(Mutex::new(tree.lock().depth()));
+        match utils::save_to_file(opts, &session.har, root, depth, tree.clone()) {
+            Ok(outputs) => info!("Saved to {}", outputs.join(", ").bold()),
+            Err(e) => error!("{}", e),
+        }
+    }
+    let tree = tree.lock().clone();
+    Ok(tree)
+}
+
+/// Default status filters, unless one is given, even as part of a --filter-or group
+fn default_status_filter(opts: &mut Opts) {
+    if !opts
+        .filter
+        .iter()
+        .any(|e| runner::filters::or_group(&e.0).1 == "status")
+    {
+        opts.filter.push(KeyVal(
+            "status".to_string(),
+            DEFAULT_STATUS_CODES.to_string(),
+        ));
+        opts.default_status = true;
+    }
+}
+
+/// Results of a scan, with what the caller needs of its state
+struct Scanned {
+    tree: Tree<TreeData>,
+    /// URLs of the failed requests
+    errors: Vec<String>,
+    /// Aborted by `--circuit-breaker`, the results so far are kept
+    tripped: bool,
+}
+
+impl Scanned {
+    fn into_tree(self) -> Result<Tree<TreeData>> {
+        if self.tripped {
+            return Err(Tripped.into());
+        }
+        Ok(self.tree)
+    }
+}
+
+/// The resolver is created from the options when it isn't shared with other scans
+async fn scan(
+    opts: Opts,
+    fixed_urls: Option<Vec<String>>,
+    resolver: Option<runner::dns::Resolver>,
+    interactive: &Interactive,
+) -> Result<Scanned> {
+    if opts.url.is_none() && !opts.resume && opts.urls_file.is_none() {
         bail!("Missing URL");
     }
-    if opts.wordlists.is_empty() && !opts.resume {
+    if opts.wordlists.is_empty() && !opts.resume && opts.urls_file.is_none() {
         bail!("Missing wordlists");
     }
 
@@ -82,21 +333,53 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         opts.clone()
     };
 
-    // Default status filters
-    if !opts.filter.iter().any(|e| e.0 == "status") {
-        let mut filters = opts.filter.clone();
-        filters.push(KeyVal(
-            "status".to_string(),
-            DEFAULT_STATUS_CODES.to_string(),
-        ));
-        opts.filter = filters;
+    default_status_filter(&mut opts);
+
+    // Headers from the file come first so that the ones given with -H replace them
+    if let Some(path) = &opts.headers_file {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| eyre!("Failed to read {}: {}", path.bold(), e))?;
+        let mut headers = runner::client::parse_headers_file(&content)?;
+        headers.append(&mut opts.headers);
+        opts.headers = headers;
     }
 
     // Parse wordlists into a HashMap associating each wordlist key to its contents
-    let mut words = runner::wordlists::parse(&opts.wordlists).await?;
+    // Remote wordlists are downloaded first, then parsed from their cached copy
+    // With `--low-memory`, only the first batch is read, the runner reads the next ones
+    let mut batches = None;
+    let mut words = if fixed_urls.is_some() {
+        HashMap::new()
+    } else if opts.low_memory {
+        let mut stream =
+            runner::stream::Batches::open(&runner::wordlists::download(&opts).await?).await?;
+        let words = stream.next().await?.unwrap_or_default();
+        batches = Some(stream);
+        words
+    } else {
+        let wordlists = runner::wordlists::download(&opts).await?;
+        let mut words = runner::wordlists::parse(&wordlists).await?;
+        if opts.wordlist_weighted {
+            runner::wordlists::weigh(&mut words);
+        }
+        words
+    };
 
-    let mut url = opts.url.clone().unwrap();
+    // Without a target URL, the origin of the first fixed URL is used as the root
+    let mut url = match (&opts.url, &fixed_urls) {
+        (Some(url), _) => url.clone(),
+        (None, Some(urls)) => Url::parse(&urls[0])?.origin().ascii_serialization() + "/",
+        (None, None) => bail!("Missing URL"),
+    };
+
+    // Append the query to fuzz, its keys are replaced with URL-encoded words
+    if let Some(query) = &opts.query {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(query.trim_start_matches('?'));
+    }
 
+    runner::template::check_keys(&url, words.keys())?;
     // Check if the URL contains any of the replace keywords
     let mut fuzz_matches = words
         .keys()
@@ -108,18 +391,48 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
             })
         })
         .collect::<Vec<_>>();
+    // Keys in `--data` fuzz the body in classic mode
+    let data_keys = opts
+        .data
+        .as_ref()
+        .map(|data| {
+            words
+                .keys()
+                .filter(|key| data.contains(key.as_str()))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if data_keys.is_empty()
+        && opts.body_type.as_deref() == Some("json")
+        && serde_json::from_str::<serde_json::Value>(opts.data.as_deref().unwrap_or_default())
+            .is_err()
+    {
+        bail!("The body given with {} is not valid JSON", "--data".bold());
+    }
     // Set the mode based on the options and the URL
-    let mode: Mode = if opts.mode.is_some() {
+    let mode: Mode = if fixed_urls.is_some() {
+        Mode::Classic
+    } else if opts.mode.is_some() {
         opts.mode.as_deref().unwrap().into()
     } else if opts.depth.is_some() {
         Mode::Recursive
-    } else if !fuzz_matches.is_empty() {
+    } else if !fuzz_matches.is_empty() || !data_keys.is_empty() {
         Mode::Classic
     } else {
         DEFAULT_MODE.into()
     };
 
+    if batches.is_some() && mode != Mode::Classic {
+        bail!(
+            "{} is only supported in classic mode",
+            "--low-memory".bold()
+        );
+    }
+
     match mode {
+        // Fixed URLs are requested as-is, no fuzz key is needed
+        _ if fixed_urls.is_some() => {}
         Mode::Recursive => {
             if !fuzz_matches.is_empty() {
                 warn!(
@@ -136,7 +449,7 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
             }
         }
         Mode::Classic => {
-            if fuzz_matches.is_empty() {
+            if fuzz_matches.is_empty() && data_keys.is_empty() {
                 url = url.trim_end_matches('/').to_string() + "/" + DEFAULT_FUZZ_KEY;
                 fuzz_matches.push(FuzzMatch {
                     content: DEFAULT_FUZZ_KEY.to_string(),
@@ -151,9 +464,9 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
             }
             // Remove unused wordlists keys
             for k in words.keys().cloned().collect::<Vec<_>>() {
-                if !fuzz_matches.iter().any(|e| e.content == k) {
+                if !fuzz_matches.iter().any(|e| e.content == k) && !data_keys.contains(&k) {
                     warn!(
-                        "Wordlist {} is not used in the URL, removing it",
+                        "Wordlist {} is not used in the URL or the body, removing it",
                         k.bold().blue()
                     );
                     words.remove(&k);
@@ -179,18 +492,117 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
 
     let before = words.values().fold(0, |acc, x| acc + x.words.len());
 
+    // Exclude or restrict words with `--filter-word` and `--match-word`
+    if opts.filter_word.is_some() || opts.match_word.is_some() {
+        runner::wordlists::match_words(&opts, &mut words)?;
+        if !opts.quiet {
+            info!(
+                "{} words left after matching",
+                words
+                    .values()
+                    .fold(0, |acc, x| acc + x.words.len())
+                    .to_string()
+                    .bold()
+                    .blue()
+            );
+        }
+    }
+
     // Apply filters and transformations to the wordlists (if any)
     runner::wordlists::filters(&opts, &mut words)?;
     runner::wordlists::transformations(&opts, &mut words);
 
-    runner::wordlists::deduplicate(&mut words);
+    if opts.wordlist_weighted {
+        runner::wordlists::deduplicate_in_order(&mut words);
+    } else {
+        runner::wordlists::deduplicate(&mut words);
+    }
+
+    if opts.deny_by_default && !runner::filters::has_allow_filter(&opts) {
+        warn!(
+            "{} is set without any filter that isn't negated, nothing will be shown",
+            "--deny-by-default".bold()
+        );
+    }
+
+    // Nothing to fuzz, the base URL is requested once as a fixed URL (`--allow-empty`)
+    if fixed_urls.is_none() && runner::wordlists::check_empty(&opts, &words)? {
+        let base = fuzz_matches
+            .iter()
+            .fold(url.clone(), |url, e| url.replace(&e.content, ""));
+        warn!(
+            "No words found in wordlists, only requesting {}",
+            base.bold()
+        );
+        return Box::pin(scan(opts, Some(vec![base]), resolver, interactive)).await;
+    }
+
+    let resolver = match resolver {
+        Some(resolver) => resolver,
+        None => runner::dns::Resolver::from_opts(&opts)?,
+    };
+
+    // Probe the server to skip the words only differing by their case when it ignores it
+    if opts.detect_case_insensitive && fixed_urls.is_none() {
+        let base = match mode {
+            Mode::Classic => {
+                &url[..fuzz_matches
+                    .iter()
+                    .map(|m| m.start)
+                    .min()
+                    .unwrap_or(url.len())]
+            }
+            _ => url.as_str(),
+        };
+        // Its own session, the number of threads is not known yet
+        let session = Session::new(&opts, resolver.clone(), 1)?;
+        match runner::case::detect(&opts, &session, base, &words).await? {
+            Some(true) if opts.skip_case_variants => {
+                runner::case::deduplicate_case_insensitive(&mut words);
+                info!(
+                    "The server is {}, words only differing by their case are skipped",
+                    "case-insensitive".bold()
+                );
+            }
+            Some(true) => info!(
+                "The server is {}, use {} to skip the words only differing by their case",
+                "case-insensitive".bold(),
+                "--skip-case-variants".bold()
+            ),
+            Some(false) => info!("The server is {}", "case-sensitive".bold()),
+            None => warn!("Could not tell whether the server is case-sensitive, none of the sampled words exist"),
+        }
+    }
+
+    // Let an external command put the most promising words first
+    if let Some(cmd) = &opts.scorer_cmd {
+        runner::scorer::reorder(cmd, &mut words).await;
+    }
 
     // Get the number of threads to use, default to 10 times the number of cores
+    // With --target-rps, enough threads are started for the maximum concurrency
     let threads = opts
         .threads
+        .or(opts.target_rps.and(opts.max_concurrency))
         .unwrap_or(num_cpus::get() * 10)
         .max(1)
-        .min(words.iter().fold(0, |acc, (_, v)| acc + v.words.len()));
+        .min(match &fixed_urls {
+            Some(urls) => urls.len(),
+            None => words.iter().fold(0, |acc, (_, v)| acc + v.words.len()),
+        });
+    // NTLM authenticates a connection, so each client sends its requests one after the other
+    let threads = if opts.ntlm.is_some() {
+        if opts.threads.is_some_and(|threads| threads > 1) {
+            warn!(
+                "{} sends one request at a time, {} is ignored",
+                "--ntlm".bold(),
+                "--threads".bold()
+            );
+        }
+        1
+    } else {
+        threads
+    };
 
     if !opts.quiet {
         println!(
@@ -213,14 +625,13 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         );
     }
 
-    if words.values().all(|x| x.words.is_empty()) {
-        bail!("No words found in wordlists");
-    }
-
     // These will be used to keep track of the current state of the tree across threads
     let current_depth = Arc::new(Mutex::new(0));
     let current_indexes: Arc<Mutex<HashMap<String, Vec<usize>>>> =
         Arc::new(Mutex::new(HashMap::new()));
+    // Words already tested for each URL when resuming with bigger wordlists
+    let tested_words: Arc<Mutex<HashMap<String, HashSet<String>>>> =
+        Arc::new(Mutex::new(HashMap::new()));
 
     let saved_tree = if opts.resume {
         match saved_json {
@@ -229,6 +640,7 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
                 &json.unwrap(),
                 current_depth.clone(),
                 current_indexes.clone(),
+                tested_words.clone(),
                 words.clone(),
             )?),
             _ => None,
@@ -258,7 +670,15 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
                         }
                     }
                 }
-                url[..smallest_index].to_string()
+                match &opts.root_path {
+                    // Paths in the output are relative to the given root instead
+                    Some(root_path) => {
+                        let mut root = Url::parse(&url[..smallest_index])?;
+                        root.set_path(root_path);
+                        root.to_string()
+                    }
+                    None => url[..smallest_index].to_string(),
+                }
             }
         };
         t.lock().insert(
@@ -280,11 +700,36 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         t
     };
 
+    // The interactive mode keeps the same tree across its scans
+    let tree = match &interactive.tree {
+        Some(shared) => {
+            *shared.lock() = tree.lock().clone();
+            shared.clone()
+        }
+        None => tree,
+    };
+
     // Check if the root URL is up
     let root_url = tree.lock().root.clone().unwrap().lock().data.url.clone();
     let root_url = Url::parse(&root_url)?;
 
-    let tmp_client = runner::client::build(&opts)?;
+    let mut session = Session::new(&opts, resolver, threads)?;
+
+    // Make sure the configured resolver answers before starting the scan
+    if session.resolver.is_configured() {
+        if let Some(host) = root_url.host_str() {
+            session.resolver.lookup(host).await.map_err(|e| {
+                eyre!(
+                    "Failed to resolve {} with the configured resolver: {}",
+                    host,
+                    e
+                )
+            })?;
+        }
+    }
+
+    // Not counted with the connections of the scan
+    let tmp_client = runner::client::build(&opts, &session.resolver, &Default::default())?;
 
     let res = tmp_client.get(root_url.clone()).send().await;
     if let Err(e) = res {
@@ -294,10 +739,57 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
             bail!("Root URL is down, use --force to continue");
         }
     } else {
-        tree.lock().root.clone().unwrap().lock().data.status_code = res?.status().as_u16();
+        let res = res?;
+        tree.lock().root.clone().unwrap().lock().data.status_code = res.status().as_u16();
+        // Fingerprint the root page to filter out the responses that just serve it again
+        if opts.filter_root_similar {
+            let headers = res.headers().clone();
+            let body = res.text().await?;
+            runner::filters::fingerprint::Fingerprint::new(&body)
+                .with_headers(&opts.baseline_headers, &headers)
+                .store(&tree);
+        }
     }
 
+    // Record what a missing page looks like, so the next scans don't have to probe again
+    if let Some(path) = &opts.save_baseline {
+        let baseline =
+            runner::filters::baseline::Baseline::probe(&opts, &session, root_url.as_str()).await?;
+        baseline.save(path)?;
+        info!(
+            "Saved the baseline ({} status, {} bytes) to {}",
+            baseline.status_code.to_string().bold(),
+            baseline.size.to_string().bold(),
+            path.bold()
+        );
+    }
+    session.baseline = opts
+        .baseline_file
+        .as_deref()
+        .map(runner::filters::baseline::Baseline::load)
+        .transpose()?;
+
+    // Parents of a deep starting URL, probed once before the scan
+    if opts.scan_parents {
+        runner::parents::scan(&opts, &session, &tmp_client, &tree).await?;
+    }
+
+    // Load the results of a previous run to only print new findings
+    let previous_results = opts
+        .only_new
+        .as_ref()
+        .map(|path| utils::tree::load_previous(path))
+        .transpose()?
+        .map(Arc::new);
+
     let start_time = std::time::Instant::now();
+    let title = utils::title::start(&opts);
+
+    let slow_start = runner::slow_start::SlowStart::start(opts.slow_start, threads);
+    let autopilot = runner::autopilot::Autopilot::start(
+        opts.target_rps,
+        opts.max_concurrency.unwrap_or(threads).min(threads),
+    );
 
     if !opts.quiet {
         info!(
@@ -307,47 +799,77 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         );
     }
 
+    // Show the findings in a live table if asked to
+    let (tui, tui_quit) = Tui::start(&opts)?.unzip();
+
+    // Split the words into chunks for each thread, the depths with a wordlist of their own get their own chunks
+    let (chunks, depth_chunks) = if mode == Mode::Recursive {
+        let depth_chunks = runner::wordlists::load_depths(&opts)
+            .await?
+            .into_iter()
+            .map(|(depth, words)| {
+                (
+                    depth,
+                    Arc::new(runner::wordlists::chunks(&opts, &words, threads)),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        (
+            Arc::new(runner::wordlists::chunks(&opts, &words, threads)),
+            depth_chunks,
+        )
+    } else {
+        if !opts.depth_wordlist.is_empty() {
+            warn!("--depth-wordlist is only used in recursive mode");
+        }
+        (Arc::new(Vec::new()), HashMap::new())
+    };
+
+    let ctx = Arc::new(runner::context::ScanContext {
+        tree: tree.clone(),
+        previous_results,
+        slow_start,
+        findings: tui.as_ref().map(Tui::findings),
+        autopilot,
+        background: interactive.background,
+        session,
+        errors: Default::default(),
+        workers: Default::default(),
+        notifier: Default::default(),
+    });
+
     // Define the main function to run based on the mode
     let main_fun = match mode {
         Mode::Recursive => runner::recursive::Recursive::new(
             opts.clone(),
+            ctx.clone(),
             current_depth.clone(),
-            tree.clone(),
             current_indexes.clone(),
-            // Split the words into chunks of equal size for each thread
-            Arc::new(
-                words
-                    .iter()
-                    .fold(
-                        Vec::new(),
-                        |mut acc, (_, ParsedWordlist { words: w, .. })| {
-                            acc.extend(w.clone());
-                            acc
-                        },
-                    )
-                    .chunks(words.iter().fold(0, |acc, (_, v)| acc + v.words.len()) / threads)
-                    .map(|x| x.to_vec())
-                    .collect::<Vec<_>>(),
-            ),
+            chunks.clone(),
+            depth_chunks,
+            tested_words.clone(),
         )
         .run()
         .boxed(),
         Mode::Classic => runner::classic::Classic::new(
             url.clone(),
             opts.clone(),
-            tree.clone(),
+            ctx.clone(),
             // We do not need to chunk the words here as it is chunked in the Classic struct
             words.clone(),
             threads,
+            fixed_urls.clone(),
+            batches,
         )
         .run()
         .boxed(),
         Mode::Spider => {
-            runner::spider::Spider::new(url.clone(), opts.clone(), tree.clone(), threads)
+            runner::spider::Spider::new(url.clone(), opts.clone(), ctx.clone(), threads)
                 .run()
                 .boxed()
         }
     };
+    let webhook = ctx.session.webhook.start(&opts)?;
     // Run the main function with a timeout if specified
     let (task, handle) = if let Some(max_time) = opts.max_time {
         log::debug!("Setting timeout to {}s", max_time);
@@ -358,7 +880,22 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         abortable(timeout(Duration::from_secs(u64::MAX), main_fun))
     };
 
+    // Stop the scan when too many requests fail, the results so far are still saved
     let main_thread = tokio::spawn(task);
+    let breaker_task = opts.circuit_breaker.map(|threshold| {
+        let handle = handle.clone();
+        let window = opts.circuit_window.unwrap_or(DEFAULT_CIRCUIT_WINDOW);
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            ctx.session.breaker.wait().await;
+            error!(
+                "More than {}% of the last {} requests failed, aborting",
+                threshold.to_string().bold(),
+                window.to_string().bold()
+            );
+            handle.abort();
+        })
+    });
     let aborted = Arc::new(AtomicBool::new(false));
     // Create a channel to receive the abort signal
     let (tx, mut rx) = tokio::sync::oneshot::channel::<()>();
@@ -367,14 +904,37 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
     let ctrlc_tree = tree.clone();
     let ctrlc_depth = current_depth.clone();
     let ctrlc_words = words.clone();
+    let ctrlc_chunks = chunks.clone();
     let ctrlc_opts = opts.clone();
     let ctrlc_aborted = aborted.clone();
     let ctrlc_save_file = opts.save_file.clone();
+    let ctrlc_indexes = current_indexes.clone();
+    let ctrlc_ctx = ctx.clone();
+    let ctrlc_background = interactive.background;
 
     let (ctrlc_task, ctrlc_handle) = abortable(async move {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to listen to Ctrl-C");
+        // Quitting the live table before the end of the scan is handled like Ctrl-C
+        let tui_quit = async move {
+            if let Some(quit) = tui_quit {
+                if quit.await.is_ok() {
+                    return;
+                }
+            }
+            futures::future::pending::<()>().await
+        };
+        // The prompt handles Ctrl-C while a scan runs in the background
+        let ctrl_c = async move {
+            if ctrlc_background {
+                futures::future::pending::<()>().await;
+            }
+            tokio::signal::ctrl_c()
+                .await
+                .expect("Failed to listen to Ctrl-C")
+        };
+        tokio::select! {
+            _ = ctrl_c => {}
+            _ = tui_quit => {}
+        }
         println!();
         info!("Aborting...");
 
@@ -386,8 +946,13 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
                 tree: ctrlc_tree.clone(),
                 depth: ctrlc_depth.clone(),
                 wordlist_checksum: compute_checksum(&ctrlc_words),
-                indexes: current_indexes.lock().clone(),
+                indexes: ctrlc_indexes.lock().clone(),
                 opts: ctrlc_opts.clone(),
+                chunks: ctrlc_chunks
+                    .iter()
+                    .map(|chunk| chunk.iter().map(|(word, _)| word.clone()).collect())
+                    .collect(),
+                errors: ctrlc_ctx.errors.list(),
             });
             if let Ok(content) = content {
                 let mut file = tokio::fs::File::create(
@@ -418,9 +983,21 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
     let signals_task: JoinHandle<Result<Result<()>, futures::future::Aborted>> =
         tokio::spawn(ctrlc_task);
     let abort_res = main_thread.await?;
+    if let Some(breaker_task) = breaker_task {
+        breaker_task.abort();
+    }
+    if let Some(title) = title {
+        utils::title::stop(title);
+    }
+
+    // Leave the live table open until the user closes it
+    if let Some(tui) = tui {
+        tui.finish().await?;
+    }
 
     let timeout_res = match abort_res {
         Ok(res) => Some(res),
+        Err(_) if ctx.session.breaker.tripped() => Some(Ok(Ok(()))),
         Err(e) => {
             debug!("Aborted: {}", e);
             None
@@ -439,10 +1016,17 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
             if let Err(e) = run_res {
                 error!("{}", e);
             }
+            let failed = ctx.workers.failed();
+            if failed > 0 {
+                warn!(
+                    "{} chunks failed, the results are partial",
+                    failed.to_string().bold()
+                );
+            }
             if !opts.quiet {
                 println!(
                     "{} Done in {} with an average of {} req/s",
-                    SUCCESS.to_string().green(),
+                    theme::success(),
                     HumanDuration(std::time::Instant::now().duration_since(start_time))
                         .to_string()
                         .bold(),
@@ -466,24 +1050,150 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
 
             let root = tree.lock().root.clone().unwrap().clone();
 
-            if !opts.quiet {
+            // The tree would repeat the results left out of the terminal with `--max-print`
+            let hidden = ctx.session.printed.hidden();
+            if !opts.quiet && hidden == 0 {
                 print_tree(&*root.lock())?;
             }
 
+            // The tables would be drawn over the prompt of the interactive mode
+            if opts.signatures && !interactive.background {
+                println!(
+                    "{}",
+                    utils::table::build_signatures_table(&root.lock().flatten())
+                );
+                if let Some(histogram) = utils::table::build_time_histogram(
+                    &ctx.session.pool.times(),
+                    opts.histogram_buckets.unwrap_or(DEFAULT_HISTOGRAM_BUCKETS),
+                ) {
+                    print!("{}", histogram);
+                }
+                if let Some(timings) = utils::table::build_timing_table(&root.lock().flatten()) {
+                    println!("{}", timings);
+                }
+                if let Some(connections) = runner::pool::summary(ctx.session.pool.stats()) {
+                    println!("{}", connections);
+                }
+                if let Some(proxies) = ctx.session.proxies.summary() {
+                    println!("{}", proxies);
+                }
+            }
+            if !opts.quiet {
+                let counts = ctx
+                    .session
+                    .collapse
+                    .as_ref()
+                    .map(|collapse| collapse.lock().counts().clone())
+                    .unwrap_or_default();
+                for (size, count) in counts {
+                    info!(
+                        "Collapsed {} responses of {} bytes",
+                        count.to_string().bold(),
+                        size.to_string().bold()
+                    );
+                }
+            }
+            if let Some(slowest) = opts
+                .slowest
+                .filter(|_| !interactive.background)
+                .and_then(|count| utils::table::build_slowest_table(&root.lock().flatten(), count))
+            {
+                println!("{}", slowest);
+            }
+            if opts.method_report && !interactive.background {
+                match utils::table::build_method_table(&root.lock().flatten()) {
+                    Some(methods) => println!("{}", methods),
+                    None => info!("No URL got statuses of different classes across the methods"),
+                }
+            }
+
             // Remove save file after finishing resuming
-            if has_saved && !opts.keep_save {
+            if has_saved && !opts.keep_save && !ctx.session.breaker.tripped() {
                 tokio::fs::remove_file(opts.save_file.clone().unwrap()).await?;
             }
-            if opts.output.is_some() {
-                let res = utils::save_to_file(&opts, root, current_depth, tree.clone());
+            // Keep the failed requests around to retry them with `--rerun-errors`
+            let errors = ctx.errors.list();
+            if let Some(path) = opts.save_errors.as_ref().filter(|_| !errors.is_empty()) {
+                let content = serde_json::to_string(&Save {
+                    tree: tree.clone(),
+                    depth: current_depth.clone(),
+                    wordlist_checksum: compute_checksum(&words),
+                    indexes: current_indexes.lock().clone(),
+                    opts: opts.clone(),
+                    chunks: chunks
+                        .iter()
+                        .map(|chunk| chunk.iter().map(|(word, _)| word.clone()).collect())
+                        .collect(),
+                    errors: errors.clone(),
+                })?;
+                tokio::fs::write(path, content).await?;
+                warn!(
+                    "{} requests failed, retry them with {}",
+                    errors.len().to_string().bold(),
+                    format!("--rerun-errors {}", path).bold()
+                );
+            }
+            let (root_url, root_status, count) = {
+                let root = root.lock();
+                (
+                    root.data.url.clone(),
+                    root.data.status_code,
+                    root.flatten().len() - 1,
+                )
+            };
+            let mut outputs = opts.output.clone();
+            if !opts.output.is_empty() {
+                let res = utils::save_to_file(
+                    &opts,
+                    &ctx.session.har,
+                    root.clone(),
+                    current_depth,
+                    tree.clone(),
+                );
 
                 match res {
-                    Ok(_) => info!("Saved to {}", opts.output.unwrap().bold()),
+                    Ok(saved) => {
+                        info!("Saved to {}", saved.join(", ").bold());
+                        outputs = saved;
+                    }
                     Err(e) => {
                         error!("{}", e);
                     }
                 }
             }
+            if let Some(file) = &opts.emit_wordlist {
+                let entries = utils::tree::to_wordlist(
+                    &root.lock(),
+                    opts.emit_wordlist_mode.as_deref() == Some("urls"),
+                );
+                let content = entries.iter().map(|entry| format!("{}\n", entry)).join("");
+                match std::fs::write(file, content) {
+                    Ok(_) => info!(
+                        "Wrote {} words to {}",
+                        entries.len().to_string().bold(),
+                        file.bold()
+                    ),
+                    Err(e) => error!("Failed to write the wordlist to {}: {}", file.bold(), e),
+                }
+            }
+            if hidden > 0 && !opts.quiet {
+                if opts.output.is_empty() {
+                    warn!(
+                        "{} more results were not printed, use {} to save them",
+                        hidden.to_string().bold(),
+                        "--output".bold()
+                    );
+                } else {
+                    info!(
+                        "{} more results written to {}",
+                        hidden.to_string().bold(),
+                        outputs.join(", ").bold()
+                    );
+                }
+            }
+            ctx.notifier
+                .on_finish(&opts, &root_url, root_status, count)
+                .await;
         }
     }
 
@@ -495,6 +1205,9 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         }
     }
 
+    // Post the findings still waiting in the batch
+    ctx.session.webhook.finish(webhook).await;
+
     // Terminate the signal stream.
     ctrlc_handle.abort();
 
@@ -509,5 +1222,9 @@ pub async fn _main(opts: Opts) -> Result<Tree<TreeData>> {
         }
     }
     let tree = tree.lock().clone();
-    Ok(tree)
+    Ok(Scanned {
+        tree,
+        errors: ctx.errors.list(),
+        tripped: ctx.session.breaker.tripped(),
+    })
 }
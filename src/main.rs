@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use clap::{CommandFactory, Parser, ValueEnum};
+use clap::{CommandFactory, ValueEnum};
 use clap_complete::{Generator, Shell};
 use clap_complete_nushell::Nushell;
 use color_eyre::eyre::{eyre, Result};
@@ -9,7 +9,10 @@ use merge::Merge;
 use rwalk::{
     _main,
     cli::{self, opts::Opts},
-    utils::{self, constants::DEFAULT_CONFIG_PATH},
+    utils::{
+        self,
+        constants::{CIRCUIT_BREAKER_EXIT_CODE, DEFAULT_CONFIG_PATH},
+    },
 };
 use std::{
     path::{Path, PathBuf},
@@ -21,10 +24,31 @@ async fn main() -> Result<()> {
     utils::logger::init_logger();
     utils::init_panic()?;
 
-    let mut opts = Opts::parse();
+    let mut opts = Opts::try_parse_grouped_from(std::env::args()).unwrap_or_else(|e| e.exit());
 
-    if let Some(p) = opts.config {
+    if opts.list_recipes {
+        let dir = cli::recipe::dir()?;
+        let names = cli::recipe::list(&dir)?;
+        if names.is_empty() {
+            println!("No recipes in {}", dir.display());
+        }
+        for name in names {
+            println!("{}", name);
+        }
+        process::exit(0);
+    }
+    // Recipes come between the command line and the config file
+    if let Some(name) = opts.recipe.clone() {
+        let recipe = cli::recipe::load(&cli::recipe::dir()?, &name).await?;
+        opts.merge(recipe);
+        log::debug!("Using recipe: {}", name);
+    }
+
+    if let Some(p) = opts.config.clone() {
+        let print_effective_config = opts.print_effective_config;
         opts = Opts::from_path(p.clone()).await?;
+        // Asked for on the command line, not part of the config file
+        opts.print_effective_config |= print_effective_config;
         log::debug!("Using config file: {}", p);
     } else if let Some(home) = dirs::home_dir() {
         log::debug!("Home directory found: {}", home.display());
@@ -39,7 +63,17 @@ async fn main() -> Result<()> {
         log::debug!("No home directory found");
     }
 
+    // Profiles only fill in the options that are not set yet
+    if !opts.profile.is_empty() {
+        let profile = cli::profile::Profile::load(&opts.profile).await?;
+        opts.merge(profile.into());
+    }
+
     log::debug!("Parsed options: {:#?}", opts);
+    if opts.print_effective_config {
+        println!("{}", toml::to_string_pretty(&opts.redacted())?);
+        process::exit(0);
+    }
     if opts.open_config {
         // Open the config file in the default editor
 
@@ -91,6 +125,7 @@ async fn main() -> Result<()> {
     if opts.no_color {
         colored::control::set_override(false);
     }
+    utils::theme::init(&opts)?;
 
     let res = if opts.interactive {
         cli::interactive::main_interactive(opts).await
@@ -98,6 +133,10 @@ async fn main() -> Result<()> {
         _main(opts).await.map(|_| ())
     };
     if let Err(e) = res {
+        // Already reported when the breaker tripped
+        if e.is::<rwalk::runner::breaker::Tripped>() {
+            process::exit(CIRCUIT_BREAKER_EXIT_CODE);
+        }
         error!("{}", e);
         process::exit(1);
     }
@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{bail, Result};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+
+/// Minimum delay between two refreshes, requests failing meanwhile reuse the token just fetched
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// Bearer token fetched by `--auth-refresh-cmd` and when it was fetched
+    static ref TOKEN: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+    /// Held while the command runs, so a burst of 401s only runs it once
+    static ref REFRESHING: tokio::sync::Mutex<()> = tokio::sync::Mutex::new(());
+}
+
+/// The last token fetched, if any
+pub fn token() -> Option<String> {
+    TOKEN.lock().as_ref().map(|(token, _)| token.clone())
+}
+
+/// Run the command to get a fresh token, unless another request just did
+pub async fn refresh(cmd: &str) -> Result<String> {
+    let _refreshing = REFRESHING.lock().await;
+    if let Some((token, fetched)) = &*TOKEN.lock() {
+        if fetched.elapsed() < REFRESH_INTERVAL {
+            return Ok(token.clone());
+        }
+    }
+    let output = crate::utils::shell(cmd).output().await?;
+    if !output.status.success() {
+        bail!("{} exited with {}", cmd, output.status);
+    }
+    let token = parse_token(&String::from_utf8_lossy(&output.stdout))?;
+    log::debug!("Refreshed the bearer token");
+    *TOKEN.lock() = Some((token.clone(), Instant::now()));
+    Ok(token)
+}
+
+/// Replace the `Authorization` header of a request with the token
+pub fn set_bearer(request: &mut reqwest::Request, token: &str) {
+    if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+        request.headers_mut().insert(AUTHORIZATION, value);
+    }
+}
+
+/// The token is the trimmed output of the command, a leading `Bearer` is ignored
+fn parse_token(output: &str) -> Result<String> {
+    let token = output.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+    if token.is_empty() {
+        bail!("The command printed no token");
+    }
+    Ok(token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token() {
+        assert_eq!(parse_token("abc.def\n").unwrap(), "abc.def");
+        assert_eq!(parse_token("Bearer abc.def\n").unwrap(), "abc.def");
+        assert!(parse_token(" \n").is_err());
+    }
+}
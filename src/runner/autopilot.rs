@@ -0,0 +1,173 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    task::AbortHandle,
+};
+
+/// How often the number of concurrent requests is adjusted
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Adjusts the number of concurrent requests to reach a number of requests per second (`--target-rps`)
+#[derive(Clone, Debug)]
+pub struct Autopilot {
+    semaphore: Arc<Semaphore>,
+    /// Requests completed and their total latency in microseconds since the last adjustment
+    completed: Arc<AtomicUsize>,
+    latency: Arc<AtomicU64>,
+    /// Stops the adjustments once the last clone is dropped, at the end of the scan
+    _controller: Arc<Controller>,
+}
+
+#[derive(Debug)]
+struct Controller(AbortHandle);
+
+impl Drop for Controller {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// A slot to send a request, its latency is recorded when dropped
+pub struct Slot<'a> {
+    _permit: SemaphorePermit<'a>,
+    autopilot: &'a Autopilot,
+    start: Instant,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.autopilot.completed.fetch_add(1, Ordering::Relaxed);
+        self.autopilot
+            .latency
+            .fetch_add(self.start.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Autopilot {
+    /// Start with a single concurrent request, adjusted between 1 and `max_concurrency`
+    ///
+    /// Returns `None` if there is no target
+    pub fn start(target_rps: Option<u64>, max_concurrency: usize) -> Option<Self> {
+        let target = target_rps.filter(|t| *t > 0)?;
+        let max_concurrency = max_concurrency.max(1);
+        let semaphore = Arc::new(Semaphore::new(1));
+        let completed = Arc::new(AtomicUsize::new(0));
+        let latency = Arc::new(AtomicU64::new(0));
+        // The task only holds the counters, not the controller that aborts it
+        let (task_semaphore, task_completed, task_latency) =
+            (semaphore.clone(), completed.clone(), latency.clone());
+        let handle = tokio::spawn(async move {
+            let mut concurrency = 1;
+            loop {
+                tokio::time::sleep(INTERVAL).await;
+                let completed = task_completed.swap(0, Ordering::Relaxed);
+                let latency = Duration::from_micros(task_latency.swap(0, Ordering::Relaxed));
+                let next =
+                    next_concurrency(concurrency, completed, latency, target, max_concurrency);
+                if next != concurrency {
+                    log::debug!(
+                        "Autopilot: {} req/s, {} concurrent requests",
+                        completed as f64 / INTERVAL.as_secs_f64(),
+                        next
+                    );
+                }
+                if next > concurrency {
+                    task_semaphore.add_permits(next - concurrency);
+                    concurrency = next;
+                } else if next < concurrency {
+                    // Permits in use can't be taken back, the remainder is retried on the next tick
+                    concurrency -= task_semaphore.forget_permits(concurrency - next);
+                }
+            }
+        });
+        Some(Self {
+            semaphore,
+            completed,
+            latency,
+            _controller: Arc::new(Controller(handle.abort_handle())),
+        })
+    }
+
+    /// Wait for a slot to send a request
+    pub async fn acquire(&self) -> Option<Slot<'_>> {
+        let permit = self.semaphore.acquire().await.ok()?;
+        Some(Slot {
+            _permit: permit,
+            autopilot: self,
+            start: Instant::now(),
+        })
+    }
+}
+
+/// Number of concurrent requests needed to reach `target` requests per second given the observed latency
+///
+/// Moves halfway towards it to avoid oscillating, and doubles when nothing completed to measure.
+fn next_concurrency(
+    current: usize,
+    completed: usize,
+    latency: Duration,
+    target: u64,
+    max: usize,
+) -> usize {
+    let next = if completed == 0 {
+        current * 2
+    } else {
+        let average = latency.as_secs_f64() / completed as f64;
+        let needed = (target as f64 * average).ceil() as usize;
+        (current + needed).div_ceil(2)
+    };
+    next.clamp(1, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autopilot_noop() {
+        assert!(Autopilot::start(None, 10).is_none());
+        assert!(Autopilot::start(Some(0), 10).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_autopilot_stops() {
+        let autopilot = Autopilot::start(Some(10), 4).unwrap();
+        let controller = autopilot._controller.0.clone();
+        let clone = autopilot.clone();
+        drop(autopilot);
+        tokio::task::yield_now().await;
+        assert!(!controller.is_finished());
+        drop(clone);
+        tokio::task::yield_now().await;
+        assert!(controller.is_finished());
+    }
+
+    #[test]
+    fn test_next_concurrency() {
+        // 100 req/s at 200ms each needs 20 concurrent requests
+        assert_eq!(
+            next_concurrency(10, 50, Duration::from_secs(10), 100, 50),
+            15
+        );
+        assert_eq!(
+            next_concurrency(20, 100, Duration::from_secs(20), 100, 50),
+            20
+        );
+        // Too fast, slow down
+        assert_eq!(
+            next_concurrency(20, 200, Duration::from_secs(5), 100, 50),
+            12
+        );
+        assert_eq!(next_concurrency(1, 10, Duration::ZERO, 100, 50), 1);
+        // Nothing to measure yet, bounded by the maximum
+        assert_eq!(next_concurrency(4, 0, Duration::ZERO, 100, 50), 8);
+        assert_eq!(next_concurrency(40, 0, Duration::ZERO, 100, 50), 50);
+    }
+}
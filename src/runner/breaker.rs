@@ -0,0 +1,150 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+use crate::{cli::opts::Opts, utils::constants::DEFAULT_CIRCUIT_WINDOW};
+
+/// Trips when too many of the last requests failed (`--circuit-breaker`)
+#[derive(Debug)]
+struct Breaker {
+    /// Percentage of failed requests above which the breaker trips
+    threshold: f64,
+    window: usize,
+    /// Ring buffer of the last outcomes, `true` for a failure
+    outcomes: VecDeque<bool>,
+    failures: usize,
+}
+
+impl Breaker {
+    fn new(threshold: f64, window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            threshold,
+            window,
+            outcomes: VecDeque::with_capacity(window),
+            failures: 0,
+        }
+    }
+
+    /// Record an outcome, returns whether the breaker trips
+    ///
+    /// Nothing trips before the window is full, a few early errors are not worth aborting for.
+    fn record(&mut self, failed: bool) -> bool {
+        if self.outcomes.len() == self.window && self.outcomes.pop_front() == Some(true) {
+            self.failures -= 1;
+        }
+        self.outcomes.push_back(failed);
+        if failed {
+            self.failures += 1;
+        }
+        self.outcomes.len() == self.window
+            && self.failures as f64 * 100.0 > self.threshold * self.window as f64
+    }
+}
+
+/// Error returned by a scan aborted by `--circuit-breaker`
+#[derive(Debug)]
+pub struct Tripped;
+
+impl fmt::Display for Tripped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The circuit breaker tripped, the scan was aborted")
+    }
+}
+
+impl std::error::Error for Tripped {}
+
+/// Outcomes of the last requests of a scan, shared by all the workers
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    breaker: Mutex<Option<Breaker>>,
+    tripped: AtomicBool,
+    trip: Notify,
+}
+
+impl CircuitBreaker {
+    /// Disabled if `--circuit-breaker` is not set
+    pub fn new(opts: &Opts) -> Self {
+        Self {
+            breaker: Mutex::new(opts.circuit_breaker.map(|threshold| {
+                Breaker::new(
+                    threshold,
+                    opts.circuit_window.unwrap_or(DEFAULT_CIRCUIT_WINDOW),
+                )
+            })),
+            ..Default::default()
+        }
+    }
+
+    /// Record whether a request failed (connection error or 5xx)
+    pub fn record(&self, failed: bool) {
+        let tripped = self
+            .breaker
+            .lock()
+            .as_mut()
+            .is_some_and(|breaker| breaker.record(failed));
+        // No permit is stored, the waiters check the flag first
+        if tripped && !self.tripped.swap(true, Ordering::Relaxed) {
+            self.trip.notify_waiters();
+        }
+    }
+
+    pub fn tripped(&self) -> bool {
+        self.tripped.load(Ordering::Relaxed)
+    }
+
+    /// Wait for the breaker to trip
+    pub async fn wait(&self) {
+        let notified = self.trip.notified();
+        tokio::pin!(notified);
+        // Registered before checking the flag, so a trip in between is not missed
+        notified.as_mut().enable();
+        if !self.tripped() {
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker() {
+        let mut breaker = Breaker::new(50.0, 4);
+        // Nothing trips before the window is full
+        assert!(!breaker.record(true));
+        assert!(!breaker.record(true));
+        assert!(!breaker.record(true));
+        assert!(breaker.record(false));
+
+        let mut breaker = Breaker::new(50.0, 4);
+        for failed in [true, false, false, false, true] {
+            assert!(!breaker.record(failed));
+        }
+        // The oldest failure slid out of the window, 2 out of 4 is not more than 50%
+        assert!(!breaker.record(true));
+        assert!(breaker.record(true));
+    }
+
+    #[tokio::test]
+    async fn test_wait() {
+        let breaker = CircuitBreaker::new(&Opts {
+            circuit_breaker: Some(50.0),
+            circuit_window: Some(2),
+            ..Default::default()
+        });
+        breaker.record(false);
+        assert!(!breaker.tripped());
+        breaker.record(true);
+        breaker.record(true);
+        assert!(breaker.tripped());
+        // Already tripped, returns without a new notification
+        breaker.wait().await;
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::Result;
+
+use crate::{cli::opts::Opts, utils::join_path};
+
+use super::{session::Session, wordlists::ParsedWordlist};
+
+/// Maximum number of words probed, each of them costing two requests
+const SAMPLE_SIZE: usize = 5;
+
+/// Status code and body size of a response
+type Signature = (u16, usize);
+
+/// Probe the server with a few words and their case-flipped variants (`--detect-case-insensitive`)
+///
+/// Returns `Some(true)` when the server is case-insensitive, `None` when no word existed to tell.
+pub async fn detect(
+    opts: &Opts,
+    session: &Session,
+    base: &str,
+    wordlists: &HashMap<String, ParsedWordlist>,
+) -> Result<Option<bool>> {
+    let client = super::client::build(opts, &session.resolver, &session.pool)?;
+    let fetch = |path: String| {
+        let client = client.clone();
+        async move {
+            let request = super::client::build_request(opts, &join_path(base, &path), &client)?;
+            let response = super::client::execute(opts, session, &client, request).await?;
+            let status = response.status().as_u16();
+            Ok::<Signature, color_eyre::eyre::Report>((status, response.bytes().await?.len()))
+        }
+    };
+    // What the server answers for a path that doesn't exist, words answering the same tell nothing
+    let missing = fetch(format!(
+        "rwalk-{:x}",
+        md5::compute(std::process::id().to_string())
+    ))
+    .await?;
+
+    let samples = wordlists
+        .values()
+        .flat_map(|wordlist| &wordlist.words)
+        .filter(|word| flip_case(word) != **word)
+        .take(SAMPLE_SIZE);
+    for word in samples {
+        let original = fetch(word.clone()).await?;
+        if similar(original, missing) {
+            continue;
+        }
+        let flipped = fetch(flip_case(word)).await?;
+        log::debug!(
+            "Case probe: {} {:?}, {} {:?}",
+            word,
+            original,
+            flip_case(word),
+            flipped
+        );
+        return Ok(Some(similar(original, flipped)));
+    }
+    Ok(None)
+}
+
+/// Remove the words only differing by their case, they lead to the same pages on case-insensitive servers
+pub fn deduplicate_case_insensitive(wordlists: &mut HashMap<String, ParsedWordlist>) {
+    for ParsedWordlist { words, .. } in wordlists.values_mut() {
+        let mut seen = HashSet::new();
+        words.retain(|word| seen.insert(word.to_lowercase()));
+    }
+}
+
+fn flip_case(word: &str) -> String {
+    word.chars()
+        .map(|c| {
+            if c.is_lowercase() {
+                c.to_uppercase().next().unwrap_or(c)
+            } else {
+                c.to_lowercase().next().unwrap_or(c)
+            }
+        })
+        .collect()
+}
+
+/// Same status code and a body size within 5%
+fn similar(a: Signature, b: Signature) -> bool {
+    a.0 == b.0 && a.1.abs_diff(b.1) <= a.1.max(b.1) / 20
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flip_case() {
+        assert_eq!(flip_case("Admin"), "aDMIN");
+        assert_eq!(flip_case("backup-2024.zip"), "BACKUP-2024.ZIP");
+        assert_eq!(flip_case("123"), "123");
+    }
+
+    #[test]
+    fn test_deduplicate_case_insensitive() {
+        let mut wordlists = HashMap::new();
+        wordlists.insert(
+            "$".to_string(),
+            ParsedWordlist::new(
+                "".to_string(),
+                vec!["Admin", "admin", "ADMIN", "login"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            ),
+        );
+        deduplicate_case_insensitive(&mut wordlists);
+        assert_eq!(wordlists["$"].words, vec!["Admin", "login"]);
+    }
+
+    #[test]
+    fn test_similar() {
+        assert!(similar((200, 1000), (200, 1040)));
+        assert!(!similar((200, 1000), (200, 1100)));
+        assert!(!similar((200, 1000), (404, 1000)));
+    }
+}
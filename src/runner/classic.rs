@@ -1,20 +1,29 @@
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
     time::{Duration, Instant},
 };
 
 use crate::{
-    cli::opts::Opts,
+    cli::{
+        helpers::{parse_method, parse_wordlist},
+        opts::Opts,
+        tui::Finding,
+    },
     utils::{
-        constants::{ERROR, PROGRESS_CHARS, PROGRESS_TEMPLATE, SUCCESS, WARNING},
+        constants::{PROGRESS_CHARS, PROGRESS_TEMPLATE},
+        join_path, relative_path,
         scripting::{run_scripts, ScriptingResponse},
-        tree::{Tree, TreeData, UrlType},
+        theme, title,
+        tree::{TreeData, TreeNode, UrlType},
     },
 };
-use color_eyre::eyre::{eyre, Result};
+use color_eyre::eyre::{bail, eyre, Result};
 use colored::Colorize;
-use indicatif::ProgressBar;
+use indicatif::{ProgressBar, ProgressDrawTarget};
 use itertools::Itertools;
 use log::{debug, info};
 use parking_lot::Mutex;
@@ -22,277 +31,798 @@ use reqwest::Client;
 use serde_json::json;
 use url::Url;
 
-use super::{filters::utils::is_directory, wordlists::ParsedWordlist, Runner};
+use super::{
+    context::ScanContext,
+    filters::{
+        fingerprint::Fingerprint,
+        utils::{check_previous, is_directory},
+        Addition,
+    },
+    rate::RateMeter,
+    stream::Batches,
+    wordlists::ParsedWordlist,
+    Runner,
+};
 
 pub struct Classic {
     url: String,
     opts: Opts,
-    tree: Arc<Mutex<Tree<TreeData>>>,
+    ctx: Arc<ScanContext>,
     words: HashMap<String, ParsedWordlist>,
     threads: usize,
+    /// Fixed list of URLs requested instead of the generated ones (`--urls-file`)
+    urls: Option<Vec<String>>,
+    /// Rest of the wordlist, scanned once `words` is done (`--low-memory`)
+    batches: Option<Batches>,
+}
+
+/// State shared by the requests of a batch
+struct Shared {
+    opts: Opts,
+    ctx: Arc<ScanContext>,
+    client: Client,
+    progress: ProgressBar,
+    engine: Arc<rhai::Engine>,
+    unconfirmed: Arc<AtomicUsize>,
+    first_match: Arc<OnceLock<String>>,
+    rate: Option<RateMeter>,
+    recorded: Arc<Mutex<HashSet<String>>>,
+    root_fingerprint: Option<Fingerprint>,
+    needs_body: bool,
+    skips_body: bool,
 }
 
 impl Classic {
     pub fn new(
         url: String,
         opts: Opts,
-        tree: Arc<Mutex<Tree<TreeData>>>,
+        ctx: Arc<ScanContext>,
         words: HashMap<String, ParsedWordlist>,
         threads: usize,
+        urls: Option<Vec<String>>,
+        batches: Option<Batches>,
     ) -> Self {
         Self {
             url,
             opts,
-            tree,
+            ctx,
             words,
             threads,
+            urls,
+            batches,
         }
     }
 
     /// Generate all possible URLs using a cartesian product of the wordlists
-    fn generate_urls(&self) -> Vec<String> {
-        let products = self
+    ///
+    /// Each URL comes with the indexes of the wordlists its words were taken from, and the body when `--data` has fuzz keys.
+    /// With `--independent-keys`, every occurrence of a key gets its own word instead of sharing it.
+    fn generate_urls(&self) -> Vec<(String, String, Option<String>)> {
+        // Every occurrence of a fuzz key in the URL, from left to right
+        let slots = self
+            .words
+            .keys()
+            .flat_map(|k| self.url.match_indices(k.as_str()))
+            .sorted_by_key(|(i, _)| *i)
+            .collect::<Vec<_>>();
+        // Same in the body, filled after the URL
+        let data = self.opts.data.as_deref().unwrap_or_default();
+        let data_slots = self
             .words
+            .keys()
+            .flat_map(|k| data.match_indices(k.as_str()))
+            .sorted_by_key(|(i, _)| *i)
+            .collect::<Vec<_>>();
+        let keys = self.words.keys().sorted().collect::<Vec<_>>();
+        // Each slot takes its word from one of the dimensions of the product
+        let (dimensions, slot_dimensions): (Vec<&str>, Vec<usize>) = if self.opts.independent_keys {
+            (
+                slots.iter().chain(&data_slots).map(|(_, k)| *k).collect(),
+                (0..slots.len() + data_slots.len()).collect(),
+            )
+        } else {
+            (
+                keys.iter().map(|k| k.as_str()).collect(),
+                slots
+                    .iter()
+                    .chain(&data_slots)
+                    .map(|(_, k)| keys.iter().position(|key| key == k).unwrap())
+                    .collect(),
+            )
+        };
+        let (slot_dimensions, data_slot_dimensions) = slot_dimensions.split_at(slots.len());
+        let products = dimensions
             .iter()
-            .map(|(k, ParsedWordlist { words: v, .. })| {
-                v.iter().map(|w| (k, w)).collect::<Vec<_>>()
+            .map(|k| {
+                let wordlist = &self.words[*k];
+                wordlist
+                    .words
+                    .iter()
+                    .map(|w| (w, wordlist.origin(w)))
+                    .collect::<Vec<_>>()
             })
             .multi_cartesian_product()
             .collect::<Vec<_>>();
+        // Words substituted in the query string are form-encoded, those in the path are percent-encoded with `--encode`
+        let query_start = self.url.find('?').unwrap_or(self.url.len());
+        // The fixed part of the path, before its first fuzz key, where the base path gets inserted
+        let authority_start = self.url.find("://").map_or(0, |i| i + 3);
+        let path_start = self.url[authority_start..query_start]
+            .find('/')
+            .map_or(query_start, |i| authority_start + i);
+        let path_end = slots
+            .iter()
+            .map(|(i, _)| *i)
+            .find(|i| *i >= path_start)
+            .map_or(query_start, |i| i.min(query_start));
+        let path_prefix = &self.url[path_start..path_end];
         let mut urls = vec![];
+        let mut invalid = 0;
         for product in &products {
-            let mut url = self.url.clone();
-            for (k, v) in product {
-                url = url.replace(*k, v);
+            let mut url = String::new();
+            let mut last = 0;
+            for ((start, k), dimension) in slots.iter().zip(slot_dimensions) {
+                // Keys overlapping a previous one are left as is
+                if *start < last {
+                    continue;
+                }
+                url.push_str(&self.url[last..*start]);
+                let (word, _) = product[*dimension];
+                if *start >= query_start {
+                    url.extend(url::form_urlencoded::byte_serialize(word.as_bytes()));
+                } else if self.opts.encode {
+                    url.push_str(&encode_path(word));
+                } else {
+                    url.push_str(word);
+                }
+                last = start + k.len();
+            }
+            url.push_str(&self.url[last..]);
+            if let Some(base_path) = &self.opts.base_path {
+                url = with_base_path(&url, path_prefix, base_path);
             }
-            urls.push(url);
+            let body = if data_slots.is_empty() {
+                None
+            } else {
+                let mut body = String::new();
+                let mut last = 0;
+                for ((start, k), dimension) in data_slots.iter().zip(data_slot_dimensions) {
+                    if *start < last {
+                        continue;
+                    }
+                    body.push_str(&data[last..*start]);
+                    body.push_str(&encode_body(
+                        self.opts.body_type.as_deref(),
+                        product[*dimension].0,
+                    ));
+                    last = start + k.len();
+                }
+                body.push_str(&data[last..]);
+                // Words can still break the JSON, when the key is not in a string
+                if self.opts.body_type.as_deref() == Some("json")
+                    && serde_json::from_str::<serde_json::Value>(&body).is_err()
+                {
+                    invalid += 1;
+                    continue;
+                }
+                Some(body)
+            };
+            let origins = product.iter().map(|(_, origin)| origin).join(",");
+            urls.push((url, origins, body));
+        }
+        if invalid > 0 {
+            log::warn!(
+                "Skipped {} requests whose body is not valid JSON",
+                invalid.to_string().bold()
+            );
         }
         urls
     }
 
     async fn process_chunk(
-        chunk: Vec<String>,
-        client: Client,
-        progress: ProgressBar,
-        tree: Arc<Mutex<Tree<TreeData>>>,
-        opts: Opts,
-        engine: Arc<rhai::Engine>,
+        chunk: Vec<(String, String, Option<String>)>,
+        shared: Arc<Shared>,
     ) -> Result<()> {
-        for (index, url) in chunk.iter().enumerate() {
-            let mut url = url.clone();
-            let t1 = Instant::now();
-            if !opts.distributed.is_empty() {
-                let current = index % (opts.distributed.len() + 1);
-                if current != 0 {
-                    let host_for_this_request = &opts.distributed[current - 1];
-
-                    let parsed_url = url::Url::parse(&url)?;
-                    url = format!(
-                        "{}://{}{}",
-                        parsed_url.scheme(),
-                        host_for_this_request,
-                        parsed_url.path()
-                    );
+        let opts = &shared.opts;
+        // Each URL is requested once per method with `--methods`
+        let variants = if opts.methods.is_empty() {
+            vec![opts.clone()]
+        } else {
+            opts.methods
+                .iter()
+                .map(|method| Opts {
+                    method: Some(method.clone()),
+                    ..opts.clone()
+                })
+                .collect()
+        };
+        for (index, (url, wordlist, data_body)) in chunk.iter().enumerate() {
+            if shared.first_match.get().is_some() {
+                break;
+            }
+            let mut statuses = serde_json::Map::new();
+            let mut hits = Vec::new();
+            for opts in variants.iter() {
+                if shared.first_match.get().is_some() {
+                    break;
+                }
+                Self::process_request(
+                    &shared,
+                    opts,
+                    index,
+                    url,
+                    wordlist,
+                    data_body,
+                    &mut statuses,
+                    &mut hits,
+                )
+                .await?;
+            }
+            // Every node of this URL gets the status codes of all the methods (`--methods`)
+            if !opts.methods.is_empty() {
+                for hit in hits {
+                    let mut hit = hit.lock();
+                    if let Some(extra) = hit.data.extra.as_array_mut() {
+                        extra.push(json!({ "key": "methods", "value": statuses }));
+                    }
                 }
             }
-            let request = super::client::build_request(&opts, &url, &client)?;
+        }
 
-            let response = client.execute(request).await;
+        Ok(())
+    }
 
-            if let Some(throttle) = opts.throttle {
-                if throttle > 0 {
-                    let elapsed = t1.elapsed();
-                    let sleep_duration = Duration::from_secs_f64(1.0 / throttle as f64);
-                    if let Some(sleep) = sleep_duration.checked_sub(elapsed) {
-                        tokio::time::sleep(sleep).await;
-                    }
+    /// Request a URL with one of the methods and record it if it passes the filters
+    #[allow(clippy::too_many_arguments)]
+    async fn process_request(
+        shared: &Shared,
+        opts: &Opts,
+        index: usize,
+        url: &str,
+        wordlist: &str,
+        data_body: &Option<String>,
+        statuses: &mut serde_json::Map<String, serde_json::Value>,
+        hits: &mut Vec<Arc<Mutex<TreeNode<TreeData>>>>,
+    ) -> Result<()> {
+        let Shared {
+            ctx,
+            client,
+            progress,
+            engine,
+            unconfirmed,
+            first_match,
+            rate,
+            recorded,
+            root_fingerprint,
+            needs_body,
+            skips_body,
+            ..
+        } = shared;
+        let mut url = url.to_string();
+        if !opts.distributed.is_empty() {
+            let current = index % (opts.distributed.len() + 1);
+            if current != 0 {
+                let host_for_this_request = &opts.distributed[current - 1];
+
+                let parsed_url = url::Url::parse(&url)?;
+                url = format!(
+                    "{}://{}{}",
+                    parsed_url.scheme(),
+                    host_for_this_request,
+                    parsed_url.path()
+                );
+            }
+        }
+        // The body filled with the words of this request replaces the `--data` template
+        let data = data_body.as_deref().or(opts.data.as_deref());
+        let request = super::client::build_request_with_data(opts, &url, data, client)?;
+        // Sent again as-is with `--confirm`
+        let retry = request.try_clone();
+
+        let _permit = match &ctx.slow_start {
+            Some(slow_start) => slow_start.acquire().await,
+            None => None,
+        };
+        let slot = match &ctx.autopilot {
+            Some(autopilot) => autopilot.acquire().await,
+            None => None,
+        };
+        // Timed once the permits are acquired, waiting for them isn't the latency of the server
+        let t1 = Instant::now();
+        let response =
+            super::client::head_then_get(opts, &ctx.session, client, request, *needs_body).await;
+        drop(slot);
+
+        if let Some(throttle) = opts.throttle {
+            if throttle > 0 {
+                let elapsed = t1.elapsed();
+                let sleep_duration = Duration::from_secs_f64(1.0 / throttle as f64);
+                if let Some(sleep) = sleep_duration.checked_sub(elapsed) {
+                    tokio::time::sleep(sleep).await;
                 }
             }
-            match response {
-                Ok(mut response) => {
-                    let status_code = response.status().as_u16();
-                    let mut text = String::new();
+        }
+        // Pause after the statuses of `--backoff-on`, once the response is handled
+        let mut backoff = None;
+        match response {
+            Ok(mut response) => {
+                let status_code = response.status().as_u16();
+                backoff = super::client::backoff_delay(opts, status_code, response.headers());
+                if !opts.methods.is_empty() {
+                    statuses.insert(opts.method.clone().unwrap_or_default(), json!(status_code));
+                }
+                let mut body = Vec::new();
 
-                    // Read the response body into `text`
+                // Read the response body, the raw bytes are kept for `--filter-bytes`
+                // Left unread when only the status and the headers are filtered on
+                if !skips_body {
                     while let Ok(chunk) = response.chunk().await {
                         if let Some(chunk) = chunk {
-                            text.push_str(&String::from_utf8_lossy(&chunk));
+                            body.extend_from_slice(&chunk);
                         } else {
                             break;
                         }
                     }
-                    // Check if the response is filtered (`true` means we keep it)
-                    let filtered = super::filters::check(
-                        &opts,
-                        &progress,
-                        &text,
-                        t1.elapsed().as_millis(),
+                    super::client::Timing::finish(&mut response);
+                }
+                // Before `--confirm` sends another request to the same host
+                super::host_limit::release(&mut response);
+                let text = String::from_utf8_lossy(&body).to_string();
+                // Check if the response is filtered (`true` means we keep it)
+                let filtered = super::filters::check(
+                    opts,
+                    &ctx.session,
+                    progress,
+                    &text,
+                    &body,
+                    t1.elapsed().as_millis(),
+                    None,
+                    &response,
+                    engine,
+                ) && super::filters::check_method(opts, status_code);
+                // Suppress soft-404s serving the root page again (`--filter-root-similar`)
+                let filtered = filtered
+                    && !root_fingerprint
+                        .as_ref()
+                        .is_some_and(|fingerprint| fingerprint.matches(&text, response.headers()));
+                // Request the URL once more to make sure this is not a transient response
+                let filtered = if filtered && opts.confirm {
+                    let retry = match retry {
+                        Some(retry) => retry,
+                        None => super::client::build_request_with_data(opts, &url, data, client)?,
+                    };
+                    let confirmed = super::filters::confirm(
+                        opts,
+                        &ctx.session,
+                        retry,
+                        client,
+                        progress,
                         None,
-                        &response,
-                        &engine,
-                    );
-
-                    if filtered {
-                        // Parse what additional information should be shown
-                        let additions =
-                            super::filters::parse_show(&opts, &text, &response, &progress, &engine);
+                        engine,
+                    )
+                    .await?;
+                    if !confirmed {
+                        unconfirmed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    confirmed
+                } else {
+                    filtered
+                };
+                // Several words can lead to the same URL, it is only recorded once unless `--insert-duplicates`
+                let filtered = filtered
+                    && (opts.insert_duplicates
+                        || recorded.lock().insert(format!(
+                            "{} {} {}",
+                            opts.method.clone().unwrap_or_default(),
+                            url,
+                            data_body.as_deref().unwrap_or_default()
+                        )));
+                // Only the first match is kept with `--stop-on-match`, the other tasks stop before their next request
+                let filtered =
+                    filtered && (!opts.stop_on_match || first_match.set(url.clone()).is_ok());
 
+                if filtered {
+                    // Keep the body around for later analysis (`--dump-dir`)
+                    if let Err(err) = super::dump::write(opts, &url, &body).await {
                         progress.println(format!(
-                            "{} {} {} {}{}",
-                            if response.status().is_success() {
-                                SUCCESS.to_string().green()
-                            } else if response.status().is_redirection() {
-                                WARNING.to_string().yellow()
-                            } else {
-                                ERROR.to_string().red()
-                            },
-                            response.status().as_str().bold(),
-                            url,
-                            format!("{}ms", t1.elapsed().as_millis().to_string().bold()).dimmed(),
-                            additions.iter().fold("".to_string(), |acc, addition| {
-                                format!(
-                                    "{} | {}: {}",
-                                    acc,
-                                    addition.key.dimmed().bold(),
-                                    addition.value.dimmed()
-                                )
-                            })
+                            "{} Failed to dump the body of {}: {}",
+                            theme::warning(),
+                            &url,
+                            err
                         ));
+                    }
+                    ctx.session
+                        .har
+                        .record(opts, &url, data, &response, &text, t1.elapsed());
+                    ctx.notifier.on_match(opts, &url, status_code);
+                    // Parse what additional information should be shown
+                    let mut additions =
+                        super::filters::parse_show(opts, &text, &response, progress, engine);
+                    if !opts.methods.is_empty() {
+                        additions.push(Addition {
+                            key: "method".to_string(),
+                            value: opts.method.clone().unwrap_or_default(),
+                        });
+                    }
+                    if let Some(data_body) = data_body {
+                        additions.push(Addition {
+                            key: "body".to_string(),
+                            value: data_body.clone(),
+                        });
+                    }
 
-                        let parsed = Url::parse(&url)?;
-                        let mut tree = tree.lock().clone();
-                        let root_url = tree
-                            .root
-                            .clone()
-                            .ok_or(eyre!("Failed to get root URL from tree"))?
-                            .lock()
-                            .data
-                            .url
-                            .clone();
-                        let maybe_content_type = response.headers().get("content-type").map(|x| {
-                            x.to_str()
-                                .unwrap_or_default()
-                                .split(';')
-                                .next()
-                                .unwrap_or_default()
-                                .to_string()
+                    if let Some(note) =
+                        check_previous(opts, ctx.previous_results.as_deref(), &url, status_code)
+                    {
+                        if ctx.session.printed.allow() {
+                            progress.println(format!(
+                                "{} {} {} {}{}{}",
+                                theme::status(response.status().as_u16()),
+                                response.status().as_str().bold(),
+                                url,
+                                format!("{}ms", t1.elapsed().as_millis().to_string().bold())
+                                    .dimmed(),
+                                additions.iter().fold("".to_string(), |acc, addition| {
+                                    format!(
+                                        "{} | {}: {}",
+                                        acc,
+                                        addition.key.dimmed().bold(),
+                                        addition.value.dimmed()
+                                    )
+                                }),
+                                note
+                            ));
+                        }
+                        if let Some(findings) = &ctx.findings {
+                            let _ = findings.send(Finding {
+                                status_code,
+                                size: text.len(),
+                                time: t1.elapsed().as_millis(),
+                                url: url.clone(),
+                            });
+                        }
+                    }
+                    // Keep track of the wordlists these words come from
+                    if opts.wordlists.len() > 1 {
+                        additions.push(Addition {
+                            key: "wordlist".to_string(),
+                            value: wordlist.to_string(),
                         });
-                        let is_dir = is_directory(&opts, &response, text.clone(), &progress);
-                        let scripting_response =
-                            ScriptingResponse::from_response(response, Some(text)).await;
-                        let data = TreeData {
-                            url: url.clone(),
-                            depth: 0,
-                            path: parsed
-                                .path()
-                                .to_string()
-                                .replace(Url::parse(&root_url)?.path().to_string().as_str(), ""),
-                            status_code,
-                            extra: json!(additions),
-                            url_type: if is_dir {
-                                UrlType::Directory
-                            } else if let Some(content_type) = maybe_content_type {
-                                UrlType::File(content_type)
-                            } else {
-                                UrlType::Unknown
-                            },
-                            response: if opts.capture {
-                                Some(scripting_response.clone())
-                            } else {
-                                None
-                            },
-                        };
-                        run_scripts(&opts, &data, Some(scripting_response), progress.clone())
-                            .await
-                            .map_err(|err| {
-                                eyre!("Failed to run scripts on URL {}: {}", url, err)
-                            })?;
-                        tree.insert(data, tree.root.clone());
                     }
+                    // Remember the size and the time of the response for the signatures summary and --slowest
+                    if opts.signatures || opts.slowest.is_some() {
+                        additions.push(Addition {
+                            key: "size".to_string(),
+                            value: text.len().to_string(),
+                        });
+                        additions.push(Addition {
+                            key: "time".to_string(),
+                            value: t1.elapsed().as_millis().to_string(),
+                        });
+                    }
+
+                    let parsed = Url::parse(&url)?;
+                    let mut tree = ctx.tree.lock().clone();
+                    let root_url = tree
+                        .root
+                        .clone()
+                        .ok_or(eyre!("Failed to get root URL from tree"))?
+                        .lock()
+                        .data
+                        .url
+                        .clone();
+                    let maybe_content_type = response.headers().get("content-type").map(|x| {
+                        x.to_str()
+                            .unwrap_or_default()
+                            .split(';')
+                            .next()
+                            .unwrap_or_default()
+                            .to_string()
+                    });
+                    let is_dir = is_directory(opts, &response, text.clone(), progress);
+                    let extra = super::filters::extra(opts, &additions, &response);
+                    let scripting_response =
+                        ScriptingResponse::from_response(response, Some(text)).await;
+                    let data = TreeData {
+                        url: url.clone(),
+                        depth: 0,
+                        path: relative_path(&parsed, &Url::parse(&root_url)?),
+                        status_code,
+                        extra,
+                        url_type: if is_dir {
+                            UrlType::Directory
+                        } else if let Some(content_type) = maybe_content_type {
+                            UrlType::File(content_type)
+                        } else {
+                            UrlType::Unknown
+                        },
+                        response: if opts.capture {
+                            Some(scripting_response.clone())
+                        } else {
+                            None
+                        },
+                    };
+                    run_scripts(opts, &data, Some(scripting_response), progress.clone())
+                        .await
+                        .map_err(|err| eyre!("Failed to run scripts on URL {}: {}", url, err))?;
+                    ctx.session.webhook.send(&data);
+                    hits.push(tree.insert(data, tree.root.clone()));
                 }
-                Err(err) => {
-                    // Check if the error is a connection error and the user specified to consider it as a hit
-                    if opts.hit_connection_errors && err.is_connect() {
-                        progress.println(format!(
-                            "{} {} {} {}",
-                            SUCCESS.to_string().green(),
-                            "Connection error".bold(),
+            }
+            Err(err) => {
+                ctx.errors.record(&url, &err);
+                // Check if the error is a connection error and the user specified to consider it as a hit
+                let hit = opts.hit_connection_errors && err.is_connect();
+                // Recorded once and ending the scan with `--stop-on-match`, like the other matches
+                let new_hit = hit
+                    && (opts.insert_duplicates
+                        || recorded.lock().insert(format!(
+                            "{} {} {}",
+                            opts.method.clone().unwrap_or_default(),
                             url,
-                            format!("{}ms", t1.elapsed().as_millis().to_string().bold()).dimmed()
-                        ));
-                        let parsed = Url::parse(&url)?;
-                        let mut tree = tree.lock().clone();
-                        let root_url = tree
-                            .root
-                            .clone()
-                            .ok_or(eyre!("Failed to get root URL from tree"))?
-                            .lock()
-                            .data
-                            .url
-                            .clone();
-                        let data = TreeData {
-                            url: url.clone(),
-                            depth: 0,
-                            path: parsed
-                                .path()
-                                .to_string()
-                                .replace(Url::parse(&root_url)?.path().to_string().as_str(), ""),
-                            status_code: 0,
-                            extra: json!([]),
-                            url_type: UrlType::Unknown,
-                            response: None,
-                        };
-                        tree.insert(data.clone(), tree.root.clone());
-
-                        run_scripts(&opts, &data, None, progress.clone())
-                            .await
-                            .map_err(|err| {
-                                eyre!("Failed to run scripts on URL {}: {}", url, err)
-                            })?;
-                    } else {
-                        super::filters::utils::print_error(
-                            &opts,
-                            |msg| {
-                                progress.println(msg);
-                                Ok(())
-                            },
-                            &url,
-                            err,
-                        )?;
+                            data_body.as_deref().unwrap_or_default()
+                        )))
+                    && (!opts.stop_on_match || first_match.set(url.clone()).is_ok());
+                if new_hit {
+                    if let Some(note) =
+                        check_previous(opts, ctx.previous_results.as_deref(), &url, 0)
+                    {
+                        if ctx.session.printed.allow() {
+                            progress.println(format!(
+                                "{} {} {} {}{}",
+                                theme::success(),
+                                "Connection error".bold(),
+                                url,
+                                format!("{}ms", t1.elapsed().as_millis().to_string().bold())
+                                    .dimmed(),
+                                note
+                            ));
+                        }
                     }
+                    let parsed = Url::parse(&url)?;
+                    let mut tree = ctx.tree.lock().clone();
+                    let root_url = tree
+                        .root
+                        .clone()
+                        .ok_or(eyre!("Failed to get root URL from tree"))?
+                        .lock()
+                        .data
+                        .url
+                        .clone();
+                    let data = TreeData {
+                        url: url.clone(),
+                        depth: 0,
+                        path: relative_path(&parsed, &Url::parse(&root_url)?),
+                        status_code: 0,
+                        extra: json!([]),
+                        url_type: UrlType::Unknown,
+                        response: None,
+                    };
+                    tree.insert(data.clone(), tree.root.clone());
+
+                    run_scripts(opts, &data, None, progress.clone())
+                        .await
+                        .map_err(|err| eyre!("Failed to run scripts on URL {}: {}", url, err))?;
+                } else if !hit {
+                    super::filters::utils::print_error(
+                        opts,
+                        |msg| {
+                            progress.println(msg);
+                            Ok(())
+                        },
+                        &url,
+                        err,
+                    )?;
                 }
             }
-            progress.inc(1);
         }
-
+        progress.inc(1);
+        // Show the achieved rate to tell whether the throttle or the target is the bottleneck
+        if let Some(rate) = &rate {
+            progress.set_message(rate.record());
+        }
+        if let Some(backoff) = backoff {
+            tokio::time::sleep(backoff).await;
+        }
         Ok(())
     }
 }
 
+/// Insert the base path in the path of a URL, after the fixed part of the path given
+///
+/// The base path goes right after the host when the path doesn't start with that part any more.
+fn with_base_path(url: &str, prefix: &str, base_path: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    let path = parsed.path().to_string();
+    let (prefix, rest) = match path.strip_prefix(prefix) {
+        Some(rest) if !prefix.is_empty() => (prefix, rest),
+        _ => ("", path.as_str()),
+    };
+    let base = join_path(prefix, base_path);
+    match rest.trim_start_matches('/') {
+        "" => parsed.set_path(&base),
+        rest => parsed.set_path(&join_path(&base, rest)),
+    }
+    parsed.to_string()
+}
+
+/// A line of a `--urls-file`, with the options overridden for this URL (e.g. `depth=2 wl=big.txt`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub url: String,
+    pub overrides: Vec<(String, String)>,
+}
+
+impl Target {
+    /// Options to scan this target with, the global ones with the overrides applied
+    ///
+    /// The URL becomes the root of a scan of its own instead of being requested as-is.
+    pub fn opts(&self, opts: &Opts) -> Result<Opts> {
+        let mut opts = Opts {
+            url: Some(self.url.clone()),
+            urls_file: None,
+            ..opts.clone()
+        };
+        let mut wordlists = Vec::new();
+        for (key, value) in &self.overrides {
+            let invalid = |err: &dyn std::fmt::Display| {
+                eyre!("Invalid {} for {}: {} ({})", key, self.url, value, err)
+            };
+            match key.as_str() {
+                "depth" => opts.depth = Some(value.parse().map_err(|e| invalid(&e))?),
+                "threads" => opts.threads = Some(value.parse().map_err(|e| invalid(&e))?),
+                "wl" | "wordlist" => {
+                    wordlists.push(parse_wordlist(value).map_err(|e| invalid(&e))?)
+                }
+                "mode" => {
+                    if !MODES.contains(&value.as_str()) {
+                        return Err(invalid(&"unknown mode"));
+                    }
+                    opts.mode = Some(value.clone());
+                }
+                "method" => opts.method = Some(parse_method(value).map_err(|e| invalid(&e))?),
+                "output" => opts.output = vec![value.clone()],
+                _ => bail!("Unknown option for {}: {}", self.url, key),
+            }
+        }
+        if !wordlists.is_empty() {
+            opts.wordlists = wordlists;
+        }
+        Ok(opts)
+    }
+}
+
+/// Values accepted by `--mode`
+const MODES: [&str; 7] = ["recursive", "recursion", "r", "classic", "c", "spider", "s"];
+
+/// Parse the content of a `--urls-file`, skipping empty lines and comments
+///
+/// Each URL can be followed by `KEY=VALUE` overrides, separated by spaces
+pub fn parse_urls_file(content: &str) -> Result<Vec<Target>> {
+    content
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let mut parts = line.split_whitespace();
+            let url = parts.next().unwrap_or_default();
+            Url::parse(url)
+                .map_err(|err| eyre!("Invalid URL on line {}: {} ({})", i + 1, url, err))?;
+            let overrides = parts
+                .map(|part| {
+                    part.split_once('=')
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .ok_or_else(|| {
+                            eyre!(
+                                "Invalid option on line {}: {} (expected KEY=VALUE)",
+                                i + 1,
+                                part
+                            )
+                        })
+                })
+                .collect::<Result<_>>()?;
+            Ok(Target {
+                url: url.to_string(),
+                overrides,
+            })
+        })
+        .collect()
+}
+
+/// Size of the chunks to split `len` URLs into, so that there are at most `threads` chunks
+///
+/// Never returns 0, even with more threads than URLs or no URLs at all
+fn chunk_size(len: usize, threads: usize) -> usize {
+    let threads = threads.min(len).max(1);
+    len.div_ceil(threads).max(1)
+}
+
+/// Encode a word for the body of `--body-type`, as form value or inside a JSON string
+fn encode_body(body_type: Option<&str>, word: &str) -> String {
+    match body_type {
+        Some("form") => url::form_urlencoded::byte_serialize(word.as_bytes()).collect(),
+        Some("json") => {
+            let quoted = serde_json::to_string(word).unwrap_or_default();
+            quoted[1..quoted.len() - 1].to_string()
+        }
+        _ => word.to_string(),
+    }
+}
+
+/// Percent-encode a word for the path, its slashes are kept to fuzz several segments at once
+fn encode_path(word: &str) -> String {
+    word.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
 impl Runner for Classic {
-    async fn run(self) -> Result<()> {
+    async fn run(mut self) -> Result<()> {
         let spinner = ProgressBar::new_spinner();
+        // The live table replaces the progress bars and the scrolling output
+        if self.ctx.hides_progress() {
+            spinner.set_draw_target(ProgressDrawTarget::hidden());
+        }
         spinner.set_message("Generating URLs...".to_string());
         spinner.enable_steady_tick(Duration::from_millis(100));
 
-        let urls: Vec<String> = self.generate_urls();
+        let mut urls: Vec<(String, String, Option<String>)> = match &self.urls {
+            Some(urls) => urls
+                .iter()
+                .map(|url| (url.clone(), String::new(), None))
+                .collect(),
+            None => self.generate_urls(),
+        };
         spinner.finish_and_clear();
         if !self.opts.quiet {
             info!("Generated {} URLs", urls.len().to_string().bold());
         }
         debug!("URLs: {:?}", urls);
 
-        let progress = ProgressBar::new(urls.len() as u64).with_style(
+        let methods = self.opts.methods.len().max(1);
+        // The total of a streamed wordlist is estimated until the whole file is read
+        let total = match &self.batches {
+            Some(batches) => batches.estimate().max(urls.len()),
+            None => urls.len(),
+        };
+        let progress = ProgressBar::new((total * methods) as u64).with_style(
             indicatif::ProgressStyle::default_bar()
                 .template(PROGRESS_TEMPLATE)?
                 .progress_chars(PROGRESS_CHARS),
         );
+        if self.ctx.hides_progress() {
+            progress.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        title::track(&progress);
 
         progress.enable_steady_tick(Duration::from_millis(100));
-        let chunks = urls.chunks(urls.len() / self.threads).collect::<Vec<_>>();
-        let mut handles = Vec::with_capacity(chunks.len());
 
-        let client = super::client::build(&self.opts)?;
+        let client = super::client::build(
+            &self.opts,
+            &self.ctx.session.resolver,
+            &self.ctx.session.pool,
+        )?;
+        // Sent to the root, the URL still has its fuzz keys
+        let root_url = self
+            .ctx
+            .tree
+            .lock()
+            .root
+            .clone()
+            .ok_or(eyre!("Failed to get root URL from tree"))?
+            .lock()
+            .data
+            .url
+            .clone();
+        super::client::warmup(&self.opts, &self.ctx.session, &client, &root_url).await;
         let mut engine = rhai::Engine::new();
         engine.build_type::<ScriptingResponse>();
         let engine_opts = self.opts.clone();
@@ -303,30 +833,270 @@ impl Runner for Classic {
             }
         });
         let engine = Arc::new(engine);
-        for chunk in &chunks {
-            let chunk = chunk.to_vec();
-            let client = client.clone();
-            let progress = progress.clone();
-            let tree = self.tree.clone();
-            let opts = self.opts.clone();
-            let engine = engine.clone();
-            let res = tokio::spawn(async move {
-                Self::process_chunk(chunk, client, progress, tree, opts, engine).await
+        let root_fingerprint = Fingerprint::from_root(&self.opts, &self.ctx.tree);
+        let unconfirmed = Arc::new(AtomicUsize::new(0));
+        let first_match = Arc::new(OnceLock::new());
+        let recorded = Arc::new(Mutex::new(HashSet::new()));
+        loop {
+            // Dealt one by one with `--chunk-strategy round-robin`, so every thread starts with the first URLs
+            let chunks = if super::wordlists::round_robin(&self.opts) {
+                super::wordlists::interleave(&urls, self.threads)
+            } else {
+                urls.chunks(chunk_size(urls.len(), self.threads))
+                    .map(|chunk| chunk.to_vec())
+                    .collect::<Vec<_>>()
+            };
+            let mut handles = Vec::with_capacity(chunks.len());
+            let shared = Arc::new(Shared {
+                opts: self.opts.clone(),
+                ctx: self.ctx.clone(),
+                client: client.clone(),
+                progress: progress.clone(),
+                engine: engine.clone(),
+                unconfirmed: unconfirmed.clone(),
+                first_match: first_match.clone(),
+                rate: RateMeter::new(self.opts.throttle, chunks.len()),
+                recorded: recorded.clone(),
+                root_fingerprint: root_fingerprint.clone(),
+                needs_body: super::filters::needs_body(&self.opts),
+                skips_body: super::filters::skips_body(&self.opts),
             });
-            handles.push(res);
-        }
+            for chunk in chunks {
+                let shared = shared.clone();
+                handles.push(tokio::spawn(Self::process_chunk(chunk, shared)));
+            }
 
-        for handle in handles {
-            let res = handle
-                .await
-                .map_err(|err| eyre!("Failed to receive result from worker thread: {}", err))?;
-            if res.is_err() {
-                return Err(res.err().unwrap());
+            self.ctx.workers.join(&self.opts, handles).await?;
+
+            // Load the next batch of a streamed wordlist once this one is scanned
+            let Some(batches) = self
+                .batches
+                .as_mut()
+                .filter(|_| first_match.get().is_none())
+            else {
+                break;
+            };
+            let Some(mut words) = batches.next().await? else {
+                break;
+            };
+            let estimate = batches.estimate();
+            super::wordlists::prepare(&self.opts, &mut words)?;
+            if let Some(cmd) = &self.opts.scorer_cmd {
+                super::scorer::reorder(cmd, &mut words).await;
             }
+            self.words = words;
+            urls = self.generate_urls();
+            debug!("URLs: {:?}", urls);
+            let scanned = progress.position() as usize / methods;
+            progress.set_length((estimate.max(scanned + urls.len()) * methods) as u64);
         }
 
         progress.finish_and_clear();
 
+        if self.opts.confirm && !self.opts.quiet {
+            info!(
+                "{} candidates failed confirmation",
+                unconfirmed.load(Ordering::Relaxed).to_string().bold()
+            );
+        }
+        if let Some(url) = first_match.get().filter(|_| !self.opts.quiet) {
+            info!("Stopped at the first match: {}", url.bold());
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cli::opts::Wordlist, utils::tree::Tree};
+
+    fn classic(words: Vec<&str>) -> Classic {
+        let mut wordlists = HashMap::new();
+        wordlists.insert(
+            "$".to_string(),
+            ParsedWordlist::new(
+                "".to_string(),
+                words.into_iter().map(|w| w.to_string()).collect(),
+            ),
+        );
+        Classic::new(
+            "http://localhost/$".to_string(),
+            Opts::default(),
+            Arc::new(ScanContext::new(Arc::new(Mutex::new(Tree::new())))),
+            wordlists,
+            50,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_generate_urls() {
+        let mut classic = classic(vec!["a", "b"]);
+        classic.url = "http://localhost/$/$?q=$".to_string();
+        let urls = classic
+            .generate_urls()
+            .into_iter()
+            .map(|(url, _, _)| url)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec!["http://localhost/a/a?q=a", "http://localhost/b/b?q=b"]
+        );
+
+        classic.opts.independent_keys = true;
+        let urls = classic.generate_urls();
+        assert_eq!(urls.len(), 8);
+        assert_eq!(urls[1].0, "http://localhost/a/a?q=b");
+        assert_eq!(urls[2].0, "http://localhost/a/b?q=a");
+    }
+
+    #[test]
+    fn test_generate_urls_base_path() {
+        let mut classic = classic(vec!["a"]);
+        classic.opts.base_path = Some("/app".to_string());
+        classic.url = "http://localhost/api/$".to_string();
+        assert_eq!(classic.generate_urls()[0].0, "http://localhost/api/app/a");
+        // Keys in the host or the query leave the path in one piece
+        classic.url = "http://$.localhost/api?q=$".to_string();
+        assert_eq!(
+            classic.generate_urls()[0].0,
+            "http://a.localhost/api/app?q=a"
+        );
+        classic.url = "http://localhost?q=$".to_string();
+        assert_eq!(classic.generate_urls()[0].0, "http://localhost/app?q=a");
+    }
+
+    #[test]
+    fn test_generate_urls_encoding() {
+        let mut classic = classic(vec!["a b&c/d?"]);
+        classic.url = "http://localhost/$?q=$".to_string();
+        // The query is always encoded, the path only with `--encode`
+        assert_eq!(
+            classic.generate_urls()[0].0,
+            "http://localhost/a b&c/d??q=a+b%26c%2Fd%3F"
+        );
+        classic.opts.encode = true;
+        assert_eq!(
+            classic.generate_urls()[0].0,
+            "http://localhost/a%20b%26c/d%3F?q=a+b%26c%2Fd%3F"
+        );
+    }
+
+    #[test]
+    fn test_generate_bodies() {
+        let mut classic = classic(vec!["a&b", "\"c\"", "1"]);
+        classic.url = "http://localhost/login".to_string();
+        classic.opts.data = Some("user=$&pass=$".to_string());
+        classic.opts.body_type = Some("form".to_string());
+        let bodies = classic
+            .generate_urls()
+            .into_iter()
+            .map(|(_, _, body)| body.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bodies,
+            vec![
+                "user=a%26b&pass=a%26b",
+                "user=%22c%22&pass=%22c%22",
+                "user=1&pass=1"
+            ]
+        );
+
+        // Words are escaped in JSON strings, the bodies left invalid are skipped
+        classic.opts.data = Some(r#"{"user": "$", "id": $}"#.to_string());
+        classic.opts.body_type = Some("json".to_string());
+        let bodies = classic
+            .generate_urls()
+            .into_iter()
+            .map(|(_, _, body)| body.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec![r#"{"user": "1", "id": 1}"#]);
+        classic.opts.data = Some(r#"{"user": "$"}"#.to_string());
+        assert_eq!(
+            classic.generate_urls()[1].2.as_deref(),
+            Some(r#"{"user": "\"c\""}"#)
+        );
+    }
+
+    #[test]
+    fn test_encode_path() {
+        assert_eq!(encode_path("admin"), "admin");
+        assert_eq!(encode_path("a b/ü"), "a%20b/%C3%BC");
+        assert_eq!(encode_path("%2e%2e"), "%252e%252e");
+    }
+
+    #[test]
+    fn test_parse_urls_file() {
+        let urls = parse_urls_file("http://localhost/a\n\n# comment\n  http://localhost/b?q=1  \n")
+            .unwrap();
+        assert_eq!(
+            urls.iter().map(|target| &target.url).collect::<Vec<_>>(),
+            vec!["http://localhost/a", "http://localhost/b?q=1"]
+        );
+        assert!(urls.iter().all(|target| target.overrides.is_empty()));
+        assert!(parse_urls_file("http://localhost/a\nnot a url").is_err());
+        assert!(parse_urls_file("http://localhost/a depth").is_err());
+    }
+
+    #[test]
+    fn test_target_opts() {
+        let targets =
+            parse_urls_file("http://localhost/a depth=2 wl=big.txt wl=ext.txt:EXT").unwrap();
+        let opts = Opts {
+            urls_file: Some("urls.txt".to_string()),
+            depth: Some(1),
+            threads: Some(4),
+            ..Default::default()
+        };
+        let target_opts = targets[0].opts(&opts).unwrap();
+        assert_eq!(target_opts.url.as_deref(), Some("http://localhost/a"));
+        assert_eq!(target_opts.urls_file, None);
+        assert_eq!(target_opts.depth, Some(2));
+        assert_eq!(target_opts.threads, Some(4));
+        assert_eq!(
+            target_opts.wordlists,
+            vec![
+                Wordlist("big.txt".to_string(), vec![]),
+                Wordlist("ext.txt".to_string(), vec!["EXT".to_string()])
+            ]
+        );
+
+        let targets = parse_urls_file("http://localhost/a depth=deep").unwrap();
+        assert!(targets[0].opts(&opts).is_err());
+        let targets = parse_urls_file("http://localhost/a colour=blue").unwrap();
+        assert!(targets[0].opts(&opts).is_err());
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        assert_eq!(chunk_size(100, 10), 10);
+        assert_eq!(chunk_size(101, 10), 11);
+        assert_eq!(chunk_size(0, 10), 1);
+        assert_eq!(chunk_size(10, 0), 10);
+    }
+
+    #[test]
+    fn test_more_threads_than_urls() {
+        let classic = classic(vec!["admin"]);
+        let urls = classic.generate_urls();
+        assert_eq!(urls.len(), 1);
+        let chunks = urls
+            .chunks(chunk_size(urls.len(), classic.threads))
+            .collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 1);
+    }
+
+    #[test]
+    fn test_empty_wordlist() {
+        let classic = classic(vec![]);
+        let urls = classic.generate_urls();
+        assert!(urls.is_empty());
+        assert_eq!(
+            urls.chunks(chunk_size(urls.len(), classic.threads)).count(),
+            0
+        );
+    }
+}
@@ -1,20 +1,72 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use color_eyre::eyre::{Context, ContextCompat, Result};
+use color_eyre::eyre::{bail, eyre, Context, ContextCompat, Result};
 use http_rest_file::{model::Header, Parser};
 use reqwest::{
-    header::{HeaderMap, HeaderName},
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_LENGTH,
+        CONTENT_TYPE, COOKIE, HOST, LOCATION, PROXY_AUTHORIZATION, RETRY_AFTER,
+    },
     redirect::Policy,
-    Proxy,
+    ClientBuilder, Method, Proxy, StatusCode, Version,
 };
+use serde::Serialize;
 
 use crate::{
     cli::opts::Opts,
-    utils::constants::{DEFAULT_FOLLOW_REDIRECTS, DEFAULT_METHOD, DEFAULT_TIMEOUT},
+    runner::{
+        auth, dns, filters, host_limit, ntlm, pool::Pool, request_log, session::Session, sigv4,
+        template,
+    },
+    utils::constants::{
+        DEFAULT_FOLLOW_REDIRECTS, DEFAULT_METHOD, DEFAULT_RETRIES, DEFAULT_TIMEOUT,
+    },
 };
 
-pub fn build(opts: &Opts) -> Result<reqwest::Client> {
+/// Delay before the first retry when the server doesn't send a `Retry-After` header, doubled on each attempt
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// Upper bound for the delay between two retries, whatever the server asks for
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Parse a file of `Name: Value` headers (`--headers-file`), skipping blank lines and comments
+pub fn parse_headers_file(content: &str) -> Result<Vec<String>> {
+    content
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| eyre!("Invalid header on line {}: {}", i + 1, line))?;
+            if key.trim().parse::<HeaderName>().is_err()
+                || value.trim().parse::<HeaderValue>().is_err()
+            {
+                bail!("Invalid header on line {}: {}", i + 1, line);
+            }
+            Ok(line.to_string())
+        })
+        .collect()
+}
+
+/// Headers sent with every request (`-H`, `--cookies` and `--host-header`)
+pub fn default_headers(opts: &Opts) -> Result<HeaderMap> {
     let mut headers = HeaderMap::new();
+    // Shortcuts for content negotiation, the headers given with `-H` take precedence
+    for (name, value) in [
+        (ACCEPT, &opts.accept),
+        (ACCEPT_LANGUAGE, &opts.accept_language),
+    ] {
+        if let Some(value) = value {
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid {} header: {}", name, value))?;
+            headers.insert(name, value);
+        }
+    }
     opts.headers.clone().iter().for_each(|header| {
         let mut header = header.splitn(2, ':');
         let key = header.next().unwrap().trim();
@@ -30,25 +82,86 @@ pub fn build(opts: &Opts) -> Result<reqwest::Client> {
             format!("{}={}", key, value).parse().unwrap(),
         )]);
     });
+    // reqwest only sets the Host header from the URL when the request doesn't have one
+    if let Some(host) = &opts.host_header {
+        headers.insert(
+            HOST,
+            HeaderValue::from_str(host)
+                .with_context(|| format!("Invalid Host header: {}", host))?,
+        );
+    }
+    Ok(headers)
+}
+
+pub fn user_agent(opts: &Opts) -> String {
+    opts.user_agent
+        .clone()
+        .unwrap_or(format!("rwalk/{}", env!("CARGO_PKG_VERSION")))
+}
+
+pub fn build(opts: &Opts, resolver: &dns::Resolver, pool: &Pool) -> Result<reqwest::Client> {
+    let headers = default_headers(opts)?;
     let client = reqwest::Client::builder()
         .danger_accept_invalid_certs(opts.insecure)
-        .user_agent(
-            opts.user_agent
-                .clone()
-                .unwrap_or(format!("rwalk/{}", env!("CARGO_PKG_VERSION"))),
-        )
+        .danger_accept_invalid_hostnames(opts.no_verify_hostname)
+        .tls_info(filters::cert::enabled(opts))
+        .user_agent(user_agent(opts))
         .default_headers(headers)
-        .redirect(
-            if opts.follow_redirects.unwrap_or(DEFAULT_FOLLOW_REDIRECTS) > 0 {
-                Policy::limited(opts.follow_redirects.unwrap_or(DEFAULT_FOLLOW_REDIRECTS))
-            } else {
-                Policy::none()
-            },
-        )
+        // Redirects are followed by hand to find the loops and record the chain
+        .redirect(Policy::none())
+        .timeout(std::time::Duration::from_secs(
+            opts.timeout.unwrap_or(DEFAULT_TIMEOUT) as u64,
+        ));
+    // No idle connection is kept around to be reused
+    let client = if opts.no_keepalive {
+        client.pool_max_idle_per_host(0)
+    } else {
+        client
+    };
+    let client = match opts.http_version.as_deref() {
+        Some("1.1") => client.http1_only(),
+        Some("2") => http2(client, opts)?,
+        // Negotiated with the server unless a version is given
+        _ => client,
+    };
+    if cfg!(not(feature = "sigv4")) && opts.sigv4.is_some() {
+        bail!("AWS signing is not supported by this build, rebuild rwalk with the `sigv4` feature");
+    }
+    // Given in a config file, the credentials were not parsed yet
+    if opts
+        .sigv4
+        .as_deref()
+        .is_some_and(|s| sigv4::Credentials::parse(s).is_none())
+    {
+        bail!("Invalid --sigv4 credentials, expected ACCESS_KEY:SECRET_KEY:REGION:SERVICE[:SESSION_TOKEN]");
+    }
+    let client = proxy(client, opts)?;
+    let client = if opts.signatures {
+        // Every lookup is counted as a new connection
+        client.dns_resolver(Arc::new(pool.counting(resolver.clone())))
+    } else {
+        client.dns_resolver(Arc::new(resolver.clone()))
+    };
+
+    Ok(client.build()?)
+}
+
+/// Build a client only sharing the proxy and TLS settings, for requests that are not sent to the target
+pub fn build_plain(opts: &Opts) -> Result<reqwest::Client> {
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(opts.insecure)
+        .danger_accept_invalid_hostnames(opts.no_verify_hostname)
+        .user_agent(format!("rwalk/{}", env!("CARGO_PKG_VERSION")))
         .timeout(std::time::Duration::from_secs(
             opts.timeout.unwrap_or(DEFAULT_TIMEOUT) as u64,
         ));
-    let client = if let Some(proxy) = opts.proxy.clone() {
+    let client = proxy(client, opts)?;
+
+    Ok(client.build()?)
+}
+
+fn proxy(client: ClientBuilder, opts: &Opts) -> Result<ClientBuilder> {
+    if let Some(proxy) = opts.proxy.clone() {
         let proxy = Proxy::all(proxy)?;
         if let Some(auth) = opts.proxy_auth.clone() {
             let mut auth = auth.splitn(2, ':');
@@ -56,15 +169,42 @@ pub fn build(opts: &Opts) -> Result<reqwest::Client> {
             let password = auth.next().unwrap().trim();
 
             let proxy = proxy.basic_auth(username, password);
-            client.proxy(proxy)
+            Ok(client.proxy(proxy))
         } else {
-            client.proxy(proxy)
+            Ok(client.proxy(proxy))
         }
     } else {
-        client
-    };
+        Ok(client)
+    }
+}
 
-    Ok(client.build()?)
+#[cfg(feature = "http2")]
+fn http2(client: ClientBuilder, opts: &Opts) -> Result<ClientBuilder> {
+    // HTTP/2 can only be negotiated over TLS, so we have to assume plain HTTP servers speak it
+    if opts
+        .url
+        .as_ref()
+        .is_some_and(|url| url.starts_with("http://"))
+    {
+        Ok(client.http2_prior_knowledge())
+    } else {
+        Ok(client)
+    }
+}
+
+#[cfg(not(feature = "http2"))]
+fn http2(_client: ClientBuilder, _opts: &Opts) -> Result<ClientBuilder> {
+    bail!("HTTP/2 is not supported by this build, rebuild rwalk with the `http2` feature")
+}
+
+/// Describe the protocol negotiated for a response, noting when the server downgraded it
+pub fn describe_version(opts: &Opts, version: Version) -> String {
+    let downgraded = opts.http_version.as_deref() == Some("2") && version < Version::HTTP_2;
+    format!(
+        "{:?}{}",
+        version,
+        if downgraded { " (downgraded)" } else { "" }
+    )
 }
 
 pub fn get_sender(
@@ -86,7 +226,26 @@ pub fn get_sender(
     }
 }
 
+/// `Content-Type` of the `--data` bodies, given by `--body-type`
+pub fn content_type(opts: &Opts) -> Option<&'static str> {
+    match opts.body_type.as_deref() {
+        Some("form") => Some("application/x-www-form-urlencoded"),
+        Some("json") => Some("application/json"),
+        _ => None,
+    }
+}
+
 pub fn build_request(opts: &Opts, url: &str, client: &reqwest::Client) -> Result<reqwest::Request> {
+    build_request_with_data(opts, url, opts.data.as_deref(), client)
+}
+
+/// Build a request sending another body than `--data`, e.g. the template filled with the words of classic mode
+pub fn build_request_with_data(
+    opts: &Opts,
+    url: &str,
+    data: Option<&str>,
+    client: &reqwest::Client,
+) -> Result<reqwest::Request> {
     if let Some(request_file) = &opts.request_file {
         let path = Path::new(request_file);
         let model = Parser::parse_file(path).context("Failed to parse request file")?;
@@ -113,7 +272,661 @@ pub fn build_request(opts: &Opts, url: &str, client: &reqwest::Client) -> Result
         });
         Ok(sender.headers(headers).build()?)
     } else {
-        let sender = get_sender(opts.method.clone(), opts.data.clone(), url, client);
+        let mut sender = get_sender(opts.method.clone(), data.map(str::to_string), url, client);
+        // Only the requests carrying the body get its type, the one given with `-H` takes precedence
+        let sends_body = matches!(opts.method.as_deref(), Some("POST" | "PUT"));
+        let overridden = opts.headers.iter().any(|header| {
+            header
+                .split(':')
+                .next()
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case(CONTENT_TYPE.as_str()))
+        });
+        if let Some(content_type) = content_type(opts).filter(|_| sends_body && !overridden) {
+            sender = sender.header(CONTENT_TYPE, content_type);
+        }
         Ok(sender.build()?)
     }
 }
+
+/// A redirect followed before getting the final response (`--record-redirect-chain`)
+#[derive(Debug, Clone, Serialize)]
+pub struct RedirectHop {
+    pub url: String,
+    pub status_code: u16,
+}
+
+/// Redirects followed to get a response, stored in its extensions
+#[derive(Debug, Clone)]
+pub struct RedirectChain(pub Vec<RedirectHop>);
+
+/// URLs of a redirect loop the response is part of (A, B, A), stored in its extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectLoop(pub Vec<String>);
+
+/// Send the `--warmup` requests in parallel, to fill the connection pool and resolve the host before the timed requests
+///
+/// They are built and sent like the scan requests, with the same headers, authentication, proxies and host limits.
+pub async fn warmup(opts: &Opts, session: &Session, client: &reqwest::Client, url: &str) {
+    let Some(count) = opts.warmup.filter(|count| *count > 0) else {
+        return;
+    };
+    let requests = (0..count).map(|_| async {
+        if opts.signatures {
+            session.pool.record_request();
+        }
+        let request = build_request(opts, url, client)?;
+        // Read the body, a connection only goes back to the pool once the response is consumed
+        execute(opts, session, client, request)
+            .await?
+            .bytes()
+            .await?;
+        Ok::<_, color_eyre::eyre::Report>(())
+    });
+    for result in futures::future::join_all(requests).await {
+        if let Err(err) = result {
+            log::debug!("Warm-up request to {} failed: {}", url, err);
+        }
+    }
+}
+
+/// Marks the response to the HEAD request of `--head-then-get` in its extensions, its body is empty
+#[derive(Debug, Clone, Copy)]
+pub struct HeadResponse;
+
+/// Timings of a response, in its extensions with `--timing-detail`
+///
+/// reqwest doesn't expose the DNS and connection times, they are part of the time to the first byte.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    sent: Instant,
+    /// From sending the request to receiving the headers
+    pub ttfb: Duration,
+    /// From the headers to the end of the body, once it is read (see [`Timing::finish`])
+    pub download: Option<Duration>,
+}
+
+impl Timing {
+    /// Record that the body of the response was read
+    pub fn finish(response: &mut reqwest::Response) {
+        if let Some(timing) = response.extensions_mut().get_mut::<Timing>() {
+            timing.download = Some(timing.sent.elapsed().saturating_sub(timing.ttfb));
+        }
+    }
+}
+
+/// Send a HEAD request first, and the GET request itself only if the status passes the filters and the body is needed (`--head-then-get`)
+pub async fn head_then_get(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+    needs_body: bool,
+) -> reqwest::Result<reqwest::Response> {
+    if !opts.head_then_get || request.method() != Method::GET {
+        return execute(opts, session, client, request).await;
+    }
+    let Some(mut head) = request.try_clone() else {
+        return execute(opts, session, client, request).await;
+    };
+    *head.method_mut() = Method::HEAD;
+    let mut response = execute(opts, session, client, head).await?;
+    let status_code = response.status().as_u16();
+    // Some servers don't implement HEAD at all
+    let unsupported = matches!(
+        response.status(),
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    );
+    if unsupported || (needs_body && filters::status_passes(opts, status_code)) {
+        // Its slot of `--max-conns-per-host` is needed for the GET
+        drop(response);
+        return execute(opts, session, client, request).await;
+    }
+    response.extensions_mut().insert(HeadResponse);
+    Ok(response)
+}
+
+/// Send a request, following the redirects by hand
+///
+/// With `--record-redirect-chain`, the chain is available in the response extensions as a [`RedirectChain`]
+pub async fn execute(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    // The whole exchange goes through the same proxy of `--proxy-file`, the NTLM handshake needs a single connection
+    let proxy = session.proxies.pick(opts);
+    let client = proxy.map_or(client, |proxy| &proxy.client);
+    let response = authorize(opts, session, client, request).await;
+    if let Some(proxy) = proxy {
+        proxy.record(response.is_ok());
+    }
+    if let Ok(response) = &response {
+        session
+            .rate_limit
+            .record(response.status() == StatusCode::TOO_MANY_REQUESTS);
+    }
+    if opts.circuit_breaker.is_some() {
+        session.breaker.record(match &response {
+            Ok(response) => response.status().is_server_error(),
+            Err(_) => true,
+        });
+    }
+    response
+}
+
+/// Send a request with the current bearer token, refreshing it and retrying once on a 401 (`--auth-refresh-cmd`)
+async fn authorize(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    let Some(cmd) = &opts.auth_refresh_cmd else {
+        return follow(opts, session, client, request).await;
+    };
+    let mut request = request;
+    if let Some(token) = auth::token() {
+        auth::set_bearer(&mut request, &token);
+    }
+    let next = request.try_clone();
+    let response = follow(opts, session, client, request).await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+    let Some(mut next) = next else {
+        return Ok(response);
+    };
+    match auth::refresh(cmd).await {
+        Ok(token) => {
+            auth::set_bearer(&mut next, &token);
+            drop(response);
+            follow(opts, session, client, next).await
+        }
+        Err(err) => {
+            log::warn!("Failed to refresh the bearer token: {}", err);
+            Ok(response)
+        }
+    }
+}
+
+async fn follow(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    let limit = opts.follow_redirects.unwrap_or(DEFAULT_FOLLOW_REDIRECTS);
+    let mut chain = Vec::new();
+    // URLs of this request chain, a redirect to one of them closes a loop
+    let mut visited = vec![request.url().to_string()];
+    let mut next = request.try_clone();
+    let mut response = send(opts, session, client, request).await?;
+    while chain.len() < limit {
+        let Some(location) = location(&response) else {
+            break;
+        };
+        if let Some(cycle) = redirect_cycle(&visited, location.as_str()) {
+            response.extensions_mut().insert(RedirectLoop(cycle));
+            break;
+        }
+        let Some(mut request) = next else {
+            break;
+        };
+        visited.push(location.to_string());
+        chain.push(RedirectHop {
+            url: response.url().to_string(),
+            status_code: response.status().as_u16(),
+        });
+        // Only 307 and 308 keep the method and the body
+        let keep_body = matches!(
+            response.status(),
+            StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT
+        );
+        redirect_request(&mut request, location, keep_body);
+        next = request.try_clone();
+        host_limit::release(&mut response);
+        response = send(opts, session, client, request).await?;
+    }
+    if opts.record_redirect_chain {
+        response.extensions_mut().insert(RedirectChain(chain));
+    }
+    Ok(response)
+}
+
+/// Point a request to the next hop of a redirect chain
+///
+/// The credentials are not sent to another host, and the body headers go with the body
+fn redirect_request(request: &mut reqwest::Request, location: reqwest::Url, keep_body: bool) {
+    let previous = request.url();
+    if previous.scheme() != location.scheme()
+        || previous.host_str() != location.host_str()
+        || previous.port_or_known_default() != location.port_or_known_default()
+    {
+        for header in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION] {
+            request.headers_mut().remove(header);
+        }
+    }
+    if !keep_body {
+        *request.method_mut() = Method::GET;
+        *request.body_mut() = None;
+        for header in [CONTENT_TYPE, CONTENT_LENGTH] {
+            request.headers_mut().remove(header);
+        }
+    }
+    *request.url_mut() = location;
+}
+
+/// Where a redirect response points to, for the statuses reqwest follows (not 300 or 304)
+fn location(response: &reqwest::Response) -> Option<reqwest::Url> {
+    if !matches!(
+        response.status(),
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    ) {
+        return None;
+    }
+    response
+        .headers()
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(|location| response.url().join(location).ok())
+}
+
+/// The loop closed by redirecting to `location`, from its first visit
+fn redirect_cycle(visited: &[String], location: &str) -> Option<Vec<String>> {
+    let start = visited.iter().position(|url| url == location)?;
+    let mut cycle = visited[start..].to_vec();
+    cycle.push(location.to_string());
+    Some(cycle)
+}
+
+/// Send a request, retrying it while the server answers with one of the `--retry-status` codes
+async fn send(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    let retries = if opts.retry_status.is_empty() {
+        0
+    } else {
+        opts.retries.unwrap_or(DEFAULT_RETRIES)
+    };
+    let mut request = request;
+    let mut attempt = 0;
+    loop {
+        // Requests with a streamed body can't be cloned, so they are only sent once
+        let next = if attempt < retries {
+            request.try_clone()
+        } else {
+            None
+        };
+        let response = dispatch(opts, session, client, request).await?;
+        match next {
+            Some(next) if opts.retry_status.contains(&response.status().as_u16()) => {
+                attempt += 1;
+                let delay = retry_delay(response.headers(), attempt);
+                log::debug!(
+                    "Retrying {} in {:?} ({}/{})",
+                    response.url(),
+                    delay,
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+                request = next;
+            }
+            _ => return Ok(response),
+        }
+    }
+}
+
+/// Send a request, going through the NTLM handshake when `--ntlm` is set
+///
+/// The handshake is started right away to save a round trip, servers that don't ask for it ignore the header.
+async fn dispatch(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    let Some(credentials) = opts.ntlm.as_deref().and_then(ntlm::Credentials::parse) else {
+        return transmit(opts, session, client, request).await;
+    };
+    let mut request = request;
+    let mut scheme = "NTLM";
+    loop {
+        // The request is sent again to answer the challenge, so streamed bodies are sent without authentication
+        let Some(mut next) = request.try_clone() else {
+            return transmit(opts, session, client, request).await;
+        };
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, ntlm::negotiate(scheme).parse().unwrap());
+        let response = transmit(opts, session, client, request).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        if let Some(challenge) = ntlm::Challenge::from_headers(response.headers()) {
+            // Consume the body so the authenticated connection goes back to the pool
+            let _ = response.bytes().await;
+            next.headers_mut().insert(
+                AUTHORIZATION,
+                ntlm::authenticate(&credentials, &challenge)
+                    .parse()
+                    .unwrap(),
+            );
+            return transmit(opts, session, client, next).await;
+        }
+        if scheme == "NTLM" && ntlm::offers_negotiate(response.headers()) {
+            scheme = "Negotiate";
+            request = next;
+            continue;
+        }
+        return Ok(response);
+    }
+}
+
+/// Send a request as is, logging it first with `--log-requests`
+async fn transmit(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    mut request: reqwest::Request,
+) -> reqwest::Result<reqwest::Response> {
+    template::expand(opts, &mut request);
+    // Signed last, over the final URL and with a fresh date for each retry
+    #[cfg(feature = "sigv4")]
+    let request = match opts.sigv4.as_deref().and_then(sigv4::Credentials::parse) {
+        Some(credentials) => {
+            let host = opts
+                .host_header
+                .clone()
+                .unwrap_or_else(|| host(request.url()));
+            // Sent unsigned, the request would only get a 403 recorded as a result
+            sigv4::sign(
+                &credentials,
+                client,
+                request,
+                &host,
+                std::time::SystemTime::now(),
+            )?
+        }
+        None => request,
+    };
+    if opts.log_requests {
+        request_log::log(opts, &request);
+    }
+    if opts.signatures {
+        session.pool.record_request();
+    }
+    let permit = session.host_limit.acquire(request.url()).await;
+    let sent = Instant::now();
+    let mut response = client.execute(request).await?;
+    if opts.signatures {
+        session
+            .pool
+            .record_time(sent.elapsed().as_millis() as usize);
+    }
+    // Held until the body is read and the response dropped
+    if let Some(permit) = permit {
+        response.extensions_mut().insert(permit);
+    }
+    if opts.timing_detail {
+        response.extensions_mut().insert(Timing {
+            sent,
+            ttfb: sent.elapsed(),
+            download: None,
+        });
+    }
+    Ok(response)
+}
+
+/// `Host` header sent for a URL, with the port unless it is the default one
+#[cfg(feature = "sigv4")]
+fn host(url: &reqwest::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+/// How long to wait before the given retry attempt, honoring `Retry-After` (in seconds) when present
+fn retry_delay(headers: &HeaderMap, attempt: usize) -> Duration {
+    retry_after(headers)
+        .unwrap_or_else(|| RETRY_BACKOFF * 2u32.saturating_pow(attempt as u32 - 1))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// How long to pause after a response with a status of `--backoff-on`, or the `Retry-After` of the server if longer
+pub fn backoff_delay(opts: &Opts, status_code: u16, headers: &HeaderMap) -> Option<Duration> {
+    let delay = opts
+        .backoff_on
+        .iter()
+        .filter_map(|backoff| backoff.split_once(':'))
+        .find(|(status, _)| status.parse() == Ok(status_code))
+        .and_then(|(_, delay)| delay.parse().ok())
+        .map(Duration::from_millis)?;
+    Some(
+        delay.max(
+            retry_after(headers)
+                .unwrap_or_default()
+                .min(MAX_RETRY_DELAY),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers_file() {
+        let headers =
+            parse_headers_file("# Auth\nAuthorization: Bearer abc:def\n\n  X-Tenant: 42  \n")
+                .unwrap();
+        assert_eq!(
+            headers,
+            vec!["Authorization: Bearer abc:def", "X-Tenant: 42"]
+        );
+        assert!(parse_headers_file("X-Tenant 42").is_err());
+        assert!(parse_headers_file("Bad Name: 42").is_err());
+    }
+
+    #[test]
+    fn test_default_headers() {
+        let headers = default_headers(&Opts {
+            accept: Some("application/json".to_string()),
+            accept_language: Some("fr-FR".to_string()),
+            headers: vec!["Accept-Language: de".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(headers[ACCEPT], "application/json");
+        assert_eq!(headers[ACCEPT_LANGUAGE], "de");
+        assert!(default_headers(&Opts {
+            accept: Some("text/html\n".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_build_request_content_type() {
+        let client = reqwest::Client::new();
+        let opts = Opts {
+            method: Some("POST".to_string()),
+            body_type: Some("json".to_string()),
+            ..Default::default()
+        };
+        let request =
+            build_request_with_data(&opts, "http://localhost/", Some("{}"), &client).unwrap();
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            request.body().and_then(|body| body.as_bytes()),
+            Some(&b"{}"[..])
+        );
+        // Not on the requests without a body
+        let request = build_request(
+            &Opts {
+                method: Some("GET".to_string()),
+                ..opts.clone()
+            },
+            "http://localhost/",
+            &client,
+        )
+        .unwrap();
+        assert!(request.headers().get(CONTENT_TYPE).is_none());
+        let request = build_request(
+            &Opts {
+                headers: vec!["Content-Type: text/plain".to_string()],
+                ..opts
+            },
+            "http://localhost/",
+            &client,
+        )
+        .unwrap();
+        assert!(request.headers().get(CONTENT_TYPE).is_none());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_delay(&headers, 1), RETRY_BACKOFF);
+        assert_eq!(retry_delay(&headers, 3), RETRY_BACKOFF * 4);
+        assert_eq!(retry_delay(&headers, 20), MAX_RETRY_DELAY);
+
+        headers.insert(RETRY_AFTER, "2".parse().unwrap());
+        assert_eq!(retry_delay(&headers, 1), Duration::from_secs(2));
+        headers.insert(RETRY_AFTER, "3600".parse().unwrap());
+        assert_eq!(retry_delay(&headers, 1), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_redirect_cycle() {
+        let visited = ["http://x/a", "http://x/login", "http://x/sso"].map(String::from);
+        assert_eq!(redirect_cycle(&visited, "http://x/admin"), None);
+        assert_eq!(
+            redirect_cycle(&visited, "http://x/login"),
+            Some(vec![
+                "http://x/login".to_string(),
+                "http://x/sso".to_string(),
+                "http://x/login".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_redirect_request() {
+        let client = reqwest::Client::new();
+        let mut request = client
+            .post("http://x/login")
+            .header(AUTHORIZATION, "Bearer abc")
+            .header(COOKIE, "session=abc")
+            .header(CONTENT_TYPE, "application/json")
+            .body("{}")
+            .build()
+            .unwrap();
+        redirect_request(&mut request, "http://x/home".parse().unwrap(), true);
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer abc");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+
+        redirect_request(&mut request, "https://x/home".parse().unwrap(), false);
+        assert_eq!(request.method(), Method::GET);
+        assert!(request.body().is_none());
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+        assert!(request.headers().get(COOKIE).is_none());
+        assert!(request.headers().get(CONTENT_TYPE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_follow_redirect_loop() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let (status, location) = match request.split(' ').nth(1) {
+                    Some("/a") => ("302 Found", "/b"),
+                    Some("/b") => ("302 Found", "/a"),
+                    _ => ("304 Not Modified", "/a"),
+                };
+                let _ = stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                            status, location
+                        )
+                        .as_bytes(),
+                    )
+                    .await;
+            }
+        });
+        let opts = Opts::default();
+        let client = build(&opts, &dns::Resolver::default(), &Pool::default()).unwrap();
+        let get = |path: &str| {
+            client
+                .get(format!("http://{}{}", addr, path))
+                .build()
+                .unwrap()
+        };
+        let response = follow(&opts, &Session::default(), &client, get("/a"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.extensions().get::<RedirectLoop>(),
+            Some(&RedirectLoop(vec![
+                format!("http://{}/a", addr),
+                format!("http://{}/b", addr),
+                format!("http://{}/a", addr)
+            ]))
+        );
+        // Not followed, but not a loop either
+        let response = follow(&opts, &Session::default(), &client, get("/c"))
+            .await
+            .unwrap();
+        assert_eq!(response.extensions().get::<RedirectLoop>(), None);
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let opts = Opts {
+            backoff_on: vec!["429:2000".to_string(), "503:5000".to_string()],
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        assert_eq!(backoff_delay(&opts, 200, &headers), None);
+        assert_eq!(
+            backoff_delay(&opts, 429, &headers),
+            Some(Duration::from_secs(2))
+        );
+        // The longest of the two
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(
+            backoff_delay(&opts, 429, &headers),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            backoff_delay(&opts, 503, &headers),
+            Some(Duration::from_secs(5))
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::{
+    cli::tui::Findings,
+    utils::tree::{Tree, TreeData},
+};
+
+use super::{
+    autopilot::Autopilot, errors::Errors, notify::Notifier, session::Session,
+    slow_start::SlowStart, workers::Workers,
+};
+
+/// State of a scan shared by the runners and all their tasks
+pub struct ScanContext {
+    pub tree: Arc<Mutex<Tree<TreeData>>>,
+    /// Results of a previous run, only the new findings are printed (`--only-new`)
+    pub previous_results: Option<Arc<HashMap<String, u16>>>,
+    pub slow_start: Option<SlowStart>,
+    /// Live table of the findings (`--tui`)
+    pub findings: Option<Findings>,
+    pub autopilot: Option<Autopilot>,
+    /// Started in the background from the interactive mode
+    pub background: bool,
+    pub session: Session,
+    /// Failed requests, for `--save-errors` and `--rerun-errors`
+    pub errors: Errors,
+    pub workers: Workers,
+    pub notifier: Notifier,
+}
+
+impl ScanContext {
+    pub fn new(tree: Arc<Mutex<Tree<TreeData>>>) -> Self {
+        Self {
+            tree,
+            previous_results: None,
+            slow_start: None,
+            findings: None,
+            autopilot: None,
+            background: false,
+            session: Session::default(),
+            errors: Errors::default(),
+            workers: Workers::default(),
+            notifier: Notifier::default(),
+        }
+    }
+
+    /// Nothing is drawn under the live table or behind the prompt of the interactive mode
+    pub fn hides_progress(&self) -> bool {
+        self.findings.is_some() || self.background
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU16, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{bail, eyre, Result};
+use parking_lot::Mutex;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::{ACCEPT, CONTENT_TYPE},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    time::timeout,
+};
+
+use crate::cli::opts::Opts;
+
+/// Time to wait for an answer from the resolver
+const TIMEOUT: Duration = Duration::from_secs(5);
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// Truncated flag of the header, the answer didn't fit in a UDP datagram
+const TRUNCATED: u8 = 0x02;
+
+/// Addresses of a host, `expires` is `None` for the system lookups which don't give a TTL (kept for the whole scan)
+#[derive(Debug, Clone)]
+struct Cached {
+    addresses: Vec<IpAddr>,
+    expires: Option<Instant>,
+}
+
+impl Cached {
+    fn is_fresh(&self) -> bool {
+        self.expires.is_none_or(|expires| expires > Instant::now())
+    }
+}
+
+static NEXT_ID: AtomicU16 = AtomicU16::new(1);
+
+#[derive(Debug, Clone)]
+enum Upstream {
+    /// Plain DNS over UDP (`--dns-server`)
+    Udp(SocketAddr),
+    /// DNS over HTTPS (`--doh`), the DoH server itself is resolved by the system
+    Doh(String, reqwest::Client),
+}
+
+/// Resolves the names with the configured DNS server, or the system resolver when there is none
+///
+/// The hosts resolved before the scan (`--urls-file`) are not resolved again.
+#[derive(Debug, Clone, Default)]
+pub struct Resolver {
+    upstream: Option<Upstream>,
+    /// Addresses already resolved, shared by the clients of the scans until their TTL expires
+    cache: Arc<Mutex<HashMap<String, Cached>>>,
+}
+
+impl Resolver {
+    pub fn from_opts(opts: &Opts) -> Result<Self> {
+        let upstream = match (&opts.dns_server, &opts.doh) {
+            (Some(server), _) => Some(Upstream::Udp(parse_server(server)?)),
+            (None, Some(url)) => Some(Upstream::Doh(
+                url.clone(),
+                super::client::build_plain(opts)?,
+            )),
+            (None, None) => None,
+        };
+        Ok(Self {
+            upstream,
+            cache: Arc::default(),
+        })
+    }
+
+    /// Whether `--dns-server` or `--doh` is used instead of the system resolver
+    pub fn is_configured(&self) -> bool {
+        self.upstream.is_some()
+    }
+
+    /// Resolve a host to its IPv4 and IPv6 addresses
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        if let Some(addresses) = self.cached(host) {
+            return Ok(addresses);
+        }
+        let Some(upstream) = &self.upstream else {
+            return Ok(tokio::net::lookup_host((host, 0))
+                .await?
+                .map(|addr| addr.ip())
+                .collect());
+        };
+        let mut addresses = Vec::new();
+        let mut ttl = u32::MAX;
+        let mut error = None;
+        // A failed AAAA query doesn't discard the IPv4 addresses, and the other way around
+        for record_type in [TYPE_A, TYPE_AAAA] {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let records = match exchange(upstream, &query(host, record_type, id)).await {
+                Ok(response) => parse_response(&response, id),
+                Err(err) => Err(err),
+            };
+            match records {
+                Ok(records) => {
+                    for (ip, record_ttl) in records {
+                        addresses.push(ip);
+                        ttl = ttl.min(record_ttl);
+                    }
+                }
+                Err(err) => {
+                    log::debug!("Failed to resolve {} (type {}): {}", host, record_type, err);
+                    error.get_or_insert(err);
+                }
+            }
+        }
+        if addresses.is_empty() {
+            return Err(error.unwrap_or_else(|| eyre!("No address found for {}", host)));
+        }
+        self.cache.lock().insert(
+            host.to_string(),
+            Cached {
+                addresses: addresses.clone(),
+                expires: Some(Instant::now() + Duration::from_secs(ttl.into())),
+            },
+        );
+        Ok(addresses)
+    }
+
+    /// Addresses of a host resolved so far
+    fn cached(&self, host: &str) -> Option<Vec<IpAddr>> {
+        self.cache
+            .lock()
+            .get(host)
+            .filter(|cached| cached.is_fresh())
+            .map(|cached| cached.addresses.clone())
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addresses = resolver.lookup(name.as_str()).await?;
+            // Port 0 is replaced by the one of the URL
+            let addrs: Addrs = Box::new(addresses.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Send a query to the configured server and return its raw answer
+async fn exchange(upstream: &Upstream, query: &[u8]) -> Result<Vec<u8>> {
+    match upstream {
+        Upstream::Udp(server) => {
+            let socket = UdpSocket::bind(if server.is_ipv4() {
+                "0.0.0.0:0"
+            } else {
+                "[::]:0"
+            })
+            .await?;
+            socket.connect(server).await?;
+            socket.send(query).await?;
+            let mut buf = vec![0; 4096];
+            let len = timeout(TIMEOUT, socket.recv(&mut buf))
+                .await
+                .map_err(|_| eyre!("No answer from {}", server))??;
+            buf.truncate(len);
+            if buf.get(2).is_some_and(|flags| flags & TRUNCATED != 0) {
+                return exchange_tcp(server, query).await;
+            }
+            Ok(buf)
+        }
+        Upstream::Doh(url, client) => {
+            let response = client
+                .post(url)
+                .header(CONTENT_TYPE, DNS_MESSAGE)
+                .header(ACCEPT, DNS_MESSAGE)
+                .timeout(TIMEOUT)
+                .body(query.to_vec())
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(response.bytes().await?.to_vec())
+        }
+    }
+}
+
+/// Send the query again over TCP, for the answers truncated over UDP
+async fn exchange_tcp(server: &SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(server).await?;
+        // Messages are prefixed with their length over TCP
+        let mut message = (query.len() as u16).to_be_bytes().to_vec();
+        message.extend(query);
+        stream.write_all(&message).await?;
+        let len = stream.read_u16().await?;
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await?;
+        Ok(buf)
+    };
+    timeout(TIMEOUT, exchange)
+        .await
+        .map_err(|_| eyre!("No answer from {}", server))?
+}
+
+/// Resolve the hosts up front and in parallel, the addresses are cached until their TTL expires
+///
+/// Returns the hosts that could not be resolved
+pub async fn preresolve(resolver: &Resolver, hosts: &[String]) -> Vec<String> {
+    let lookups = hosts.iter().map(|host| {
+        let resolver = resolver.clone();
+        async move { (host, resolver.lookup(host).await.unwrap_or_default()) }
+    });
+    let mut unresolved = Vec::new();
+    for (host, addresses) in futures::future::join_all(lookups).await {
+        if addresses.is_empty() {
+            unresolved.push(host.clone());
+        } else if !resolver.is_configured() {
+            // The resolver already cached its answers with their TTL
+            resolver.cache.lock().insert(
+                host.clone(),
+                Cached {
+                    addresses,
+                    expires: None,
+                },
+            );
+        }
+    }
+    unresolved
+}
+
+/// `IP` or `IP:PORT`, port 53 by default
+fn parse_server(server: &str) -> Result<SocketAddr> {
+    server
+        .parse::<SocketAddr>()
+        .or_else(|_| server.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| eyre!("Invalid DNS server: {}", server))
+}
+
+/// Build a recursive query for a single record type
+fn query(host: &str, record_type: u16, id: u16) -> Vec<u8> {
+    let mut query = Vec::with_capacity(host.len() + 18);
+    query.extend(id.to_be_bytes());
+    // Recursion desired, one question
+    query.extend([0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in host.trim_end_matches('.').split('.') {
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    query.push(0);
+    query.extend(record_type.to_be_bytes());
+    // Class IN
+    query.extend(1u16.to_be_bytes());
+    query
+}
+
+/// Extract the A and AAAA records from the answer section, with their TTL in seconds
+fn parse_response(response: &[u8], id: u16) -> Result<Vec<(IpAddr, u32)>> {
+    let invalid = || eyre!("Invalid DNS response");
+    let read_u16 = |pos: usize| -> Result<u16> {
+        Ok(u16::from_be_bytes(
+            response.get(pos..pos + 2).ok_or_else(invalid)?.try_into()?,
+        ))
+    };
+    if read_u16(0)? != id {
+        bail!("DNS response does not match the query");
+    }
+    match read_u16(2)? & 0xf {
+        0 => {}
+        // NXDOMAIN, the name doesn't exist
+        3 => return Ok(Vec::new()),
+        rcode => bail!("DNS server failed with code {}", rcode),
+    }
+    let questions = read_u16(4)?;
+    let answers = read_u16(6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(response, pos).ok_or_else(invalid)? + 4;
+    }
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(response, pos).ok_or_else(invalid)?;
+        let record_type = read_u16(pos)?;
+        let ttl = u32::from_be_bytes(
+            response
+                .get(pos + 4..pos + 8)
+                .ok_or_else(invalid)?
+                .try_into()?,
+        );
+        let len = read_u16(pos + 8)? as usize;
+        pos += 10;
+        let data = response.get(pos..pos + len).ok_or_else(invalid)?;
+        match (record_type, len) {
+            (TYPE_A, 4) => addresses.push((IpAddr::from(<[u8; 4]>::try_from(data)?), ttl)),
+            (TYPE_AAAA, 16) => addresses.push((IpAddr::from(<[u8; 16]>::try_from(data)?), ttl)),
+            // CNAMEs are followed by the server, their targets come as separate records
+            _ => {}
+        }
+        pos += len;
+    }
+    Ok(addresses)
+}
+
+/// Position right after the (possibly compressed) name starting at `pos`
+fn skip_name(response: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *response.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // Pointer to a name elsewhere in the message
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server() {
+        assert_eq!(
+            parse_server("1.1.1.1").unwrap(),
+            "1.1.1.1:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_server("[::1]:5353").unwrap(),
+            "[::1]:5353".parse().unwrap()
+        );
+        assert!(parse_server("dns.example.com").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let mut response = query("example.com", TYPE_A, 42);
+        // Turn the query into a response with two answers
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[7] = 2;
+        // CNAME pointing to the question name, then the A record
+        response.extend([0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 12]);
+        response.extend([0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34]);
+        assert_eq!(
+            parse_response(&response, 42).unwrap(),
+            vec![("93.184.216.34".parse::<IpAddr>().unwrap(), 60)]
+        );
+        assert!(parse_response(&response, 43).is_err());
+
+        assert!(parse_response(&response[..40], 42).is_err());
+
+        // NXDOMAIN
+        response[3] = 0x83;
+        assert!(parse_response(&response, 42).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_truncated() {
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = udp.local_addr().unwrap();
+        let tcp = tokio::net::TcpListener::bind(server).await.unwrap();
+        // A queries are truncated over UDP, AAAA queries fail
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((len, peer)) = udp.recv_from(&mut buf).await {
+                let mut response = buf[..len].to_vec();
+                response[2] = 0x81 | TRUNCATED;
+                response[3] = 0x80;
+                if response[len - 3] == TYPE_AAAA as u8 {
+                    response[2] = 0x81;
+                    response[3] = 0x82;
+                }
+                let _ = udp.send_to(&response, peer).await;
+            }
+        });
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = tcp.accept().await {
+                let len = stream.read_u16().await.unwrap();
+                let mut response = vec![0; len as usize];
+                stream.read_exact(&mut response).await.unwrap();
+                response[2] = 0x81;
+                response[3] = 0x80;
+                response[7] = 1;
+                response.extend([0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 10, 0, 0, 1]);
+                let mut message = (response.len() as u16).to_be_bytes().to_vec();
+                message.extend(response);
+                stream.write_all(&message).await.unwrap();
+            }
+        });
+        let resolver = Resolver {
+            upstream: Some(Upstream::Udp(server)),
+            cache: Arc::default(),
+        };
+        assert_eq!(
+            resolver.lookup("truncated.example.com").await.unwrap(),
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(resolver.cached("truncated.example.com").is_some());
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use color_eyre::eyre::Result;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::cli::opts::Opts;
+
+/// Name of the file mapping dumped bodies to their URLs
+pub const MANIFEST_FILE: &str = "manifest.txt";
+
+/// Maximum length of the sanitized URL part of a file name
+const MAX_NAME_LEN: usize = 100;
+
+/// Build a file name from a URL, keeping it unique with a hash of the full URL
+pub fn file_name(url: &str) -> String {
+    let sanitized = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect::<String>();
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{}-{:08x}", sanitized, hasher.finish() as u32)
+}
+
+/// Write the body of a matched response to `--dump-dir` and record it in the manifest
+pub async fn write(opts: &Opts, url: &str, body: &[u8]) -> Result<()> {
+    let Some(dir) = &opts.dump_dir else {
+        return Ok(());
+    };
+    let dir = Path::new(dir);
+    tokio::fs::create_dir_all(dir).await?;
+
+    let body = match opts.max_body_size {
+        Some(max) if body.len() > max => &body[..max],
+        _ => body,
+    };
+    let name = file_name(url);
+    tokio::fs::write(dir.join(&name), body).await?;
+
+    // Each entry is written in a single call so that concurrent tasks don't interleave lines
+    let mut manifest = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(MANIFEST_FILE))
+        .await?;
+    manifest
+        .write_all(format!("{}\t{}\n", name, url).as_bytes())
+        .await?;
+    manifest.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        let name = file_name("http://localhost:8080/admin/index.php?a=b");
+        assert!(name.starts_with("localhost_8080_admin_index.php_a_b-"));
+        assert_ne!(name, file_name("http://localhost:8080/admin_index.php?a=b"));
+    }
+}
@@ -0,0 +1,20 @@
+use std::collections::BTreeSet;
+
+use parking_lot::Mutex;
+
+/// URLs whose requests ended in a connection error or a timeout, kept in the save file for `--rerun-errors`
+#[derive(Debug, Default)]
+pub struct Errors(Mutex<BTreeSet<String>>);
+
+impl Errors {
+    /// Record a failed request, only the network errors are worth retrying
+    pub fn record(&self, url: &str, err: &reqwest::Error) {
+        if err.is_connect() || err.is_timeout() {
+            self.0.lock().insert(url.to_string());
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.0.lock().iter().cloned().collect()
+    }
+}
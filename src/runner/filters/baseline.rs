@@ -0,0 +1,81 @@
+use std::path::Path;
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::opts::Opts, runner::session::Session, utils::join_path};
+
+/// Signature of the response to a path that doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub status_code: u16,
+    pub size: usize,
+    /// MD5 of the body
+    pub hash: String,
+}
+
+impl Baseline {
+    pub fn new(status_code: u16, body: &str) -> Self {
+        Self {
+            status_code,
+            size: body.len(),
+            hash: format!("{:x}", md5::compute(body)),
+        }
+    }
+
+    /// Same status code and same body, or only the same size with `by_size` (for pages reflecting the requested path)
+    pub fn matches(&self, status_code: u16, body: &str, by_size: bool) -> bool {
+        self.status_code == status_code
+            && ((by_size && self.size == body.len())
+                || self.hash == format!("{:x}", md5::compute(body)))
+    }
+
+    /// Request a random path under `base` to record what the server answers for missing pages (`--save-baseline`)
+    pub async fn probe(opts: &Opts, session: &Session, base: &str) -> Result<Self> {
+        let client = crate::runner::client::build(opts, &session.resolver, &session.pool)?;
+        let url = join_path(
+            base,
+            &format!("rwalk-{:x}", md5::compute(std::process::id().to_string())),
+        );
+        let request = crate::runner::client::build_request(opts, &url, &client)?;
+        let response = crate::runner::client::execute(opts, session, &client, request).await?;
+        let status_code = response.status().as_u16();
+        Ok(Self::new(status_code, &response.text().await?))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write the baseline to {}", path))
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(Path::new(path))
+            .with_context(|| format!("Failed to read the baseline from {}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("Invalid baseline file: {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baseline_matches() {
+        let baseline = Baseline::new(200, "<h1>Page /rwalk-1 not found</h1>");
+        assert!(baseline.matches(200, "<h1>Page /rwalk-1 not found</h1>", false));
+        assert!(!baseline.matches(200, "<h1>Page /admin-1 not found</h1>", false));
+        assert!(baseline.matches(200, "<h1>Page /admin-1 not found</h1>", true));
+        assert!(!baseline.matches(404, "<h1>Page /rwalk-1 not found</h1>", true));
+        assert!(!baseline.matches(200, "<h1>Admin</h1>", true));
+    }
+
+    #[test]
+    fn test_baseline_save_load() {
+        let path = std::env::temp_dir().join(format!("rwalk-baseline-{}.json", std::process::id()));
+        let path = path.to_string_lossy();
+        let baseline = Baseline::new(404, "Not found");
+        baseline.save(&path).unwrap();
+        assert_eq!(Baseline::load(&path).unwrap(), baseline);
+        std::fs::remove_file(&*path).unwrap();
+    }
+}
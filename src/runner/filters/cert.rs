@@ -0,0 +1,106 @@
+//! TLS certificate of the server (`--show cert`, `--match-cert-cn`), only available for HTTPS targets
+
+use std::fmt::Display;
+
+use reqwest::tls::TlsInfo;
+use x509_parser::{extensions::GeneralName, prelude::parse_x509_certificate};
+
+use crate::cli::opts::Opts;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    /// Common name of the subject, if any
+    pub common_name: Option<String>,
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names of the Subject Alternative Name extension
+    pub san: Vec<String>,
+}
+
+impl Certificate {
+    /// Parse the certificate of the connection the response came from
+    pub fn from_response(response: &reqwest::Response) -> Option<Self> {
+        let der = response.extensions().get::<TlsInfo>()?.peer_certificate()?;
+        let (_, cert) = parse_x509_certificate(der).ok()?;
+        let san = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|san| {
+                san.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(name) => Some(name.to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+        Some(Self {
+            common_name,
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            san,
+        })
+    }
+}
+
+impl Display for Certificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (issued by {})", self.subject, self.issuer)?;
+        if !self.san.is_empty() {
+            write!(f, " SAN: {}", self.san.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Check if the certificates have to be kept by the client
+pub fn enabled(opts: &Opts) -> bool {
+    opts.match_cert_cn.is_some()
+        || opts
+            .show
+            .iter()
+            .any(|show| matches!(show.to_lowercase().as_str(), "cert" | "certificate"))
+}
+
+/// Check the common name of the certificate against `--match-cert-cn`, responses without a certificate never match
+pub fn cn_matches(cert: Option<&Certificate>, regex: &str) -> bool {
+    let Some(regex) = super::utils::regex(regex) else {
+        return false;
+    };
+    cert.and_then(|cert| cert.common_name.as_deref())
+        .is_some_and(|cn| regex.is_match(cn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate() {
+        let cert = Certificate {
+            common_name: Some("api.internal.example.com".to_string()),
+            subject: "CN=api.internal.example.com".to_string(),
+            issuer: "C=US, O=Let's Encrypt, CN=R3".to_string(),
+            san: vec![
+                "api.internal.example.com".to_string(),
+                "www.example.com".to_string(),
+            ],
+        };
+        assert_eq!(
+            cert.to_string(),
+            "CN=api.internal.example.com (issued by C=US, O=Let's Encrypt, CN=R3) SAN: api.internal.example.com, www.example.com"
+        );
+        assert!(cn_matches(Some(&cert), r"\.internal\."));
+        assert!(!cn_matches(Some(&cert), "^www"));
+        assert!(!cn_matches(None, ".*"));
+    }
+}
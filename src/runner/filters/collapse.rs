@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, HashSet};
+
+/// Drops the responses of a size seen in a run of consecutive responses, likely the same error page
+#[derive(Debug, Clone, Default)]
+pub struct Collapse {
+    /// Responses of the same size in a row before the size is collapsed
+    threshold: usize,
+    last_size: Option<usize>,
+    run: usize,
+    collapsed: HashSet<usize>,
+    /// Responses dropped for each size
+    counts: BTreeMap<usize, usize>,
+}
+
+impl Collapse {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Record the size of a response, returns whether it should be dropped
+    pub fn record(&mut self, size: usize) -> bool {
+        if self.last_size == Some(size) {
+            self.run += 1;
+        } else {
+            self.last_size = Some(size);
+            self.run = 1;
+        }
+        if self.run > self.threshold {
+            self.collapsed.insert(size);
+        }
+        if self.collapsed.contains(&size) {
+            *self.counts.entry(size).or_default() += 1;
+            return true;
+        }
+        false
+    }
+
+    /// Number of responses dropped for each size
+    pub fn counts(&self) -> &BTreeMap<usize, usize> {
+        &self.counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse() {
+        let mut collapse = Collapse::new(2);
+        let dropped = [10, 10, 10, 20, 10, 20, 20, 30]
+            .iter()
+            .map(|size| collapse.record(*size))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dropped,
+            [false, false, true, false, true, false, false, false]
+        );
+        assert_eq!(collapse.counts, BTreeMap::from([(10, 2)]));
+    }
+}
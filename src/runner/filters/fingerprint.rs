@@ -0,0 +1,121 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use parking_lot::Mutex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::opts::Opts,
+    utils::tree::{Tree, TreeData},
+};
+
+/// Key under which the fingerprint is stored in the `extra` data of the tree root
+const ROOT_KEY: &str = "fingerprint";
+
+/// Allowed difference in size (percent) for a body to still be considered identical to the root
+const SIZE_TOLERANCE: usize = 5;
+
+/// Rough shape of a response body, used to spot soft-404s serving the root page (`--filter-root-similar`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub size: usize,
+    pub words: usize,
+    pub lines: usize,
+    /// Values of the `--baseline-headers` on the root page, `None` when the header was missing
+    #[serde(default)]
+    pub headers: BTreeMap<String, Option<String>>,
+}
+
+impl Fingerprint {
+    pub fn new(body: &str) -> Self {
+        Self {
+            size: body.len(),
+            words: body.split_whitespace().count(),
+            lines: body.lines().count(),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// Also fingerprint the values of the given headers (`--baseline-headers`)
+    pub fn with_headers(mut self, names: &[String], headers: &HeaderMap) -> Self {
+        self.headers = names
+            .iter()
+            .map(|name| (name.to_lowercase(), header_value(headers, name)))
+            .collect();
+        self
+    }
+
+    /// Check if a body is identical to the fingerprinted one
+    ///
+    /// The size may differ slightly, as pages often reflect the requested path.
+    /// The fingerprinted headers must have the same values.
+    pub fn matches(&self, body: &str, headers: &HeaderMap) -> bool {
+        let other = Self::new(body);
+        other.words == self.words
+            && other.lines == self.lines
+            && other.size.abs_diff(self.size) * 100 <= self.size * SIZE_TOLERANCE
+            && self
+                .headers
+                .iter()
+                .all(|(name, value)| header_value(headers, name) == *value)
+    }
+
+    /// Store the fingerprint on the tree root so it can be reused by the runners and saved with the tree
+    pub fn store(&self, tree: &Arc<Mutex<Tree<TreeData>>>) {
+        if let Some(root) = &tree.lock().root {
+            root.lock().data.extra = serde_json::json!({ ROOT_KEY: self });
+        }
+    }
+
+    /// Get the fingerprint of the tree root, if `--filter-root-similar` is enabled
+    pub fn from_root(opts: &Opts, tree: &Arc<Mutex<Tree<TreeData>>>) -> Option<Self> {
+        if !opts.filter_root_similar {
+            return None;
+        }
+        let root = tree.lock().root.clone()?;
+        let extra = root.lock().data.extra.get(ROOT_KEY).cloned()?;
+        serde_json::from_value(extra).ok()
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_matches() {
+        let fingerprint = Fingerprint::new("<html>\n<h1>Welcome home</h1>\n</html>");
+        let headers = HeaderMap::new();
+        assert!(fingerprint.matches("<html>\n<h1>Welcome home</h1>\n</html>", &headers));
+        assert!(fingerprint.matches("<html>\n<h1>Welcome home!</h1>\n</html>", &headers));
+        assert!(!fingerprint.matches("<html>\n<h1>Not found</h1>\n</html>", &headers));
+        assert!(!fingerprint.matches(
+            "<html>\n<h1>Welcome home</h1>\n<p>admin</p>\n</html>",
+            &headers
+        ));
+    }
+
+    #[test]
+    fn test_fingerprint_headers() {
+        let body = "<html>\n<h1>Welcome home</h1>\n</html>";
+        let mut root = HeaderMap::new();
+        root.insert("x-cache", "MISS".parse().unwrap());
+        let fingerprint = Fingerprint::new(body)
+            .with_headers(&["X-Cache".to_string(), "X-Backend".to_string()], &root);
+        assert!(fingerprint.matches(body, &root));
+
+        let mut other = root.clone();
+        other.insert("x-cache", "HIT".parse().unwrap());
+        assert!(!fingerprint.matches(body, &other));
+        // The header was missing on the root page
+        let mut other = root.clone();
+        other.insert("x-backend", "app-2".parse().unwrap());
+        assert!(!fingerprint.matches(body, &other));
+    }
+}
@@ -1,36 +1,114 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
 
 use colored::Colorize;
 use log::warn;
+use reqwest::header::CONTENT_LENGTH;
 use rhai::plugin::*;
 use serde::{Deserialize, Serialize};
 use utils::is_directory;
 
 use crate::{
-    cli::opts::Opts,
-    utils::{
-        check_range,
-        constants::{ERROR, WARNING},
-        parse_range_input,
-        scripting::ScriptingResponse,
+    cli::{helpers::KeyVal, opts::Opts},
+    runner::{
+        client::{describe_version, HeadResponse, RedirectChain, RedirectLoop, Timing},
+        session::Session,
     },
+    utils::{check_range, parse_range_input, scripting::ScriptingResponse, theme},
 };
 
+pub mod baseline;
+pub mod cert;
+pub mod collapse;
+pub mod fingerprint;
+pub mod similarity;
 pub mod utils;
 
 // Returns true if the response should be kept
+#[allow(clippy::too_many_arguments)]
 pub fn check(
     opts: &Opts,
+    session: &Session,
     progress: &indicatif::ProgressBar,
     res_text: &str,
+    body: &[u8],
     time: u128,
     depth: Option<usize>,
     response: &reqwest::Response,
     engine: &rhai::Engine,
 ) -> bool {
+    // Counted last, only the responses that would be shown make a run
+    passes(
+        opts, session, progress, res_text, body, time, depth, response, engine,
+    ) && !session.collapsed(body_size(response, res_text))
+}
+
+/// Check the response against the filters, without recording it for `--collapse-size`
+#[allow(clippy::too_many_arguments)]
+fn passes(
+    opts: &Opts,
+    session: &Session,
+    progress: &indicatif::ProgressBar,
+    res_text: &str,
+    body: &[u8],
+    time: u128,
+    depth: Option<usize>,
+    response: &reqwest::Response,
+    engine: &rhai::Engine,
+) -> bool {
+    // Shorthand to drop empty placeholder pages, applied whatever the other filters say
+    if opts
+        .min_content_length
+        .is_some_and(|min_content_length| body_size(response, res_text) < min_content_length)
+    {
+        return false;
+    }
+    // Binary signatures are searched in the raw body, `res_text` is lossily decoded
+    if opts
+        .filter_bytes
+        .as_deref()
+        .and_then(utils::decode_hex)
+        .is_some_and(|bytes| !utils::contains_bytes(body, &bytes))
+    {
+        return false;
+    }
+    // Session-setting endpoints, a response without `Set-Cookie` never matches
+    if opts
+        .filter_set_cookie
+        .as_deref()
+        .is_some_and(|regex| utils::set_cookie_matches(response.headers(), regex))
+        || opts
+            .match_set_cookie
+            .as_deref()
+            .is_some_and(|regex| !utils::set_cookie_matches(response.headers(), regex))
+    {
+        return false;
+    }
+    // Backend hostnames revealed by the certificate, plain HTTP responses have none
+    if opts.match_cert_cn.as_deref().is_some_and(|regex| {
+        !cert::cn_matches(cert::Certificate::from_response(response).as_ref(), regex)
+    }) {
+        return false;
+    }
+    // Responses looking like the missing page recorded with `--save-baseline`
+    if session.baseline.as_ref().is_some_and(|baseline| {
+        baseline.matches(response.status().as_u16(), res_text, opts.baseline_by_size)
+    }) {
+        return false;
+    }
+
     let mut outs: Vec<bool> = Vec::new();
+    // Filters of the same `--filter-or` group, any of them passing is enough
+    let mut groups: BTreeMap<usize, bool> = BTreeMap::new();
+    // Whether a filter that isn't negated matched, required with `--deny-by-default`
+    let mut allowed = false;
 
-    for filter in opts.filter.clone().iter_mut() {
+    let given = given_filters(opts).len();
+    for (i, filter) in opts.filter.clone().iter_mut().enumerate() {
+        let (group, key) = or_group(&filter.0);
+        filter.0 = key.to_string();
         // if the filter starts with [depth] then we parse the depth and remove it from the filter
         let filter_depth = if filter.0.starts_with('[') {
             let start_index = filter.0.find('[').unwrap();
@@ -43,7 +121,7 @@ pub fn check(
                 // warn!("Invalid depth filter: {}", depth.unwrap_err());
                 progress.println(format!(
                     "{} {} {}",
-                    ERROR.to_string().red(),
+                    theme::error(),
                     "Invalid depth filter".bold(),
                     depth.unwrap_err()
                 ));
@@ -58,7 +136,7 @@ pub fn check(
             // warn!("You provided a depth filter but you are not scanning recursively");
             progress.println(format!(
                 "{} {}",
-                WARNING.to_string().yellow(),
+                theme::warning(),
                 "You provided a depth filter but you are not scanning recursively".bold()
             ));
         }
@@ -77,7 +155,10 @@ pub fn check(
             "ends" => res_text.ends_with(&filter.1) ^ negated,
             "regex" => regex::Regex::new(&filter.1).unwrap().is_match(res_text) ^ negated,
             "length" | "size" => {
-                check_range(&parse_range_input(&filter.1).unwrap(), res_text.len()) ^ negated
+                check_range(
+                    &parse_range_input(&filter.1).unwrap(),
+                    body_size(response, res_text),
+                ) ^ negated
             }
             "hash" => filter.1.contains(&format!("{:x}", md5::compute(res_text))) ^ negated,
             "header" => {
@@ -112,7 +193,7 @@ pub fn check(
                             // warn!("Response is not valid JSON: {}", e);
                             progress.println(format!(
                                 "{} {} {}",
-                                ERROR.to_string().red(),
+                                theme::error(),
                                 "Response is not valid JSON".bold(),
                                 e
                             ));
@@ -131,7 +212,7 @@ pub fn check(
                     // warn!("Invalid JSON filter: {}", filter.1);
                     progress.println(format!(
                         "{} {}",
-                        ERROR.to_string().red(),
+                        theme::error(),
                         "Invalid JSON filter".bold()
                     ));
                     true
@@ -145,7 +226,7 @@ pub fn check(
                     // warn!("You provided a depth filter but you are not scanning recursively");
                     progress.println(format!(
                         "{} {}",
-                        WARNING.to_string().yellow(),
+                        theme::warning(),
                         "You provided a depth filter but you are not scanning recursively".bold()
                     ));
                     true
@@ -178,13 +259,12 @@ pub fn check(
                     let threshold_range = parse_range_input(threshold);
                     if let Ok(range) = threshold_range {
                         let value = value.trim_end_matches('=');
-                        let similarity = strsim::jaro_winkler(value, res_text);
-                        check_range(&range, (similarity * 100.0) as usize) ^ negated
+                        check_range(&range, similarity::similarity(opts, value, res_text)) ^ negated
                     } else {
                         // warn!("Invalid threshold in filter: {}", filter.1);
                         progress.println(format!(
                             "{} {} {}",
-                            ERROR.to_string().red(),
+                            theme::error(),
                             "Invalid threshold in filter".bold(),
                             filter.1
                         ));
@@ -192,11 +272,7 @@ pub fn check(
                     }
                 } else {
                     // warn!("Invalid filter: {}", filter.1);
-                    progress.println(format!(
-                        "{} {}",
-                        ERROR.to_string().red(),
-                        "Invalid filter".bold()
-                    ));
+                    progress.println(format!("{} {}", theme::error(), "Invalid filter".bold()));
                     true
                 }
             }
@@ -241,7 +317,7 @@ pub fn check(
                         .map_err(|e| {
                             progress.println(format!(
                                 "{} {} {}",
-                                ERROR.to_string().red(),
+                                theme::error(),
                                 "Error running script".bold(),
                                 e
                             ));
@@ -253,7 +329,7 @@ pub fn check(
                         } else {
                             progress.println(format!(
                                 "{} {}",
-                                ERROR.to_string().red(),
+                                theme::error(),
                                 "Script did not return a boolean".bold()
                             ));
                             true
@@ -262,25 +338,243 @@ pub fn check(
                         true
                     }
                 } else {
-                    progress.println(format!(
-                        "{} {}",
-                        ERROR.to_string().red(),
-                        "Unknown filter".bold()
-                    ));
+                    progress.println(format!("{} {}", theme::error(), "Unknown filter".bold()));
                     // Return true if the filter is unknown (to keep the response)
                     true
                 }
             }
         };
 
-        outs.push(out);
+        allowed |= out && !negated && i < given;
+        match group {
+            Some(group) => *groups.entry(group).or_default() |= out,
+            None => outs.push(out),
+        }
     }
+    outs.extend(groups.into_values());
 
-    if opts.or {
+    let kept = if opts.or {
         outs.iter().any(|&x| x)
     } else {
         outs.iter().all(|&x| x)
+    };
+    kept && (allowed || !opts.deny_by_default)
+}
+
+/// Size of the body, taken from `Content-Length` for the HEAD responses of `--head-then-get`
+fn body_size(response: &reqwest::Response, res_text: &str) -> usize {
+    if response.extensions().get::<HeadResponse>().is_none() {
+        return res_text.len();
+    }
+    response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Filters that can be checked on a HEAD response
+const HEAD_FILTERS: [&str; 4] = ["status", "size", "length", "header"];
+
+/// Check if the body is needed to filter and report the responses, otherwise the HEAD request of `--head-then-get` is enough
+pub fn needs_body(opts: &Opts) -> bool {
+    !opts.filter.iter().all(|filter| {
+        let key = or_group(&filter.0).1;
+        // Strip the `[depth]` prefix
+        let key = key.split_once(']').map_or(key, |(_, key)| key);
+        HEAD_FILTERS.contains(&key.trim_start_matches('!'))
+    }) || !opts.show.is_empty()
+        || !opts.show_json.is_empty()
+        || !opts.scripts.is_empty()
+        || opts.directory_script.is_some()
+        || opts.filter_bytes.is_some()
+        || opts.filter_root_similar
+        || opts.baseline_file.is_some()
+        || opts.dump_dir.is_some()
+        || opts.capture
+        || opts.signatures
+        || opts.slowest.is_some()
+        || crate::runner::har::enabled(opts)
+}
+
+/// Filters that only need the status and the headers of a response
+const STATUS_FILTERS: [&str; 4] = ["status", "header", "time", "url"];
+
+/// Check if the body can be left unread, when nothing filters or reports on it (overridden by `--force-body`)
+pub fn skips_body(opts: &Opts) -> bool {
+    !opts.force_body
+        && !needs_body(opts)
+        && opts.filter.iter().all(|filter| {
+            let key = or_group(&filter.0).1;
+            let key = key.split_once(']').map_or(key, |(_, key)| key);
+            STATUS_FILTERS.contains(&key.trim_start_matches('!'))
+        })
+        && opts.min_content_length.is_none()
+        && opts.collapse_size.is_none()
+        && !opts.timing_detail
+        && !opts.tui
+}
+
+/// Filters given by the user, without the default status filter
+fn given_filters(opts: &Opts) -> &[KeyVal<String, String>] {
+    let len = opts.filter.len();
+    &opts.filter[..len - usize::from(opts.default_status && len > 0)]
+}
+
+/// Check if a filter can let responses through with `--deny-by-default`, the negated ones only drop them
+pub fn has_allow_filter(opts: &Opts) -> bool {
+    given_filters(opts).iter().any(|filter| {
+        let key = or_group(&filter.0).1;
+        let key = key.split_once(']').map_or(key, |(_, key)| key);
+        !key.starts_with('!')
+    })
+}
+
+/// Check the status filters alone, before downloading the body (`--head-then-get`)
+///
+/// The filters of `--filter-or` groups or given for a depth are left to [`check`]
+pub fn status_passes(opts: &Opts, status_code: u16) -> bool {
+    opts.or
+        || opts.filter.iter().all(|KeyVal(key, value)| {
+            let (group, key) = or_group(key);
+            if group.is_some() || key.trim_start_matches('!') != "status" {
+                return true;
+            }
+            parse_range_input(value)
+                .map(|range| check_range(&range, status_code as usize) ^ key.starts_with('!'))
+                .unwrap_or(true)
+        })
+}
+
+/// Check the status code against the ranges given for the method of the request (`--method-filter`)
+///
+/// Methods without a range are dropped as soon as one is given.
+pub fn check_method(opts: &Opts, status_code: u16) -> bool {
+    let method = opts.method.as_deref().unwrap_or_default();
+    opts.method_filter.is_empty()
+        || opts.method_filter.iter().any(|KeyVal(key, range)| {
+            key.eq_ignore_ascii_case(method)
+                && parse_range_input(range)
+                    .is_ok_and(|range| check_range(&range, status_code as usize))
+        })
+}
+
+/// Split the `(N)` prefix added to the filters of a `--filter-or` group from the filter key
+pub fn or_group(key: &str) -> (Option<usize>, &str) {
+    key.strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .and_then(|(group, key)| Some((Some(group.parse().ok()?), key)))
+        .unwrap_or((None, key))
+}
+
+/// Send a request once more and check if the response still passes the filters (`--confirm`)
+///
+/// This weeds out hits caused by transient responses from flaky servers
+pub async fn confirm(
+    opts: &Opts,
+    session: &Session,
+    request: reqwest::Request,
+    client: &reqwest::Client,
+    progress: &indicatif::ProgressBar,
+    depth: Option<usize>,
+    engine: &rhai::Engine,
+) -> color_eyre::eyre::Result<bool> {
+    let t1 = Instant::now();
+    let response = crate::runner::client::execute(opts, session, client, request).await;
+
+    if let Some(throttle) = opts.throttle {
+        if throttle > 0 {
+            let elapsed = t1.elapsed();
+            let sleep_duration = Duration::from_secs_f64(1.0 / throttle as f64);
+            if let Some(sleep) = sleep_duration.checked_sub(elapsed) {
+                tokio::time::sleep(sleep).await;
+            }
+        }
+    }
+    match response {
+        Ok(mut response) => {
+            let mut body = Vec::new();
+            while let Ok(chunk) = response.chunk().await {
+                if let Some(chunk) = chunk {
+                    body.extend_from_slice(&chunk);
+                } else {
+                    break;
+                }
+            }
+            let text = String::from_utf8_lossy(&body);
+            // The first response was already recorded for `--collapse-size`
+            Ok(passes(
+                opts,
+                session,
+                progress,
+                &text,
+                &body,
+                t1.elapsed().as_millis(),
+                depth,
+                &response,
+                engine,
+            ))
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+/// Build the extra data stored in the tree for a response
+///
+/// With `--save-headers`, the response headers (all of them or the given subset) are added as an object keyed by name.
+/// With `--record-redirect-chain`, the redirects followed to get the response are added in order.
+/// With `--save-resolution`, the IP address the response came from is added.
+/// With `--timing-detail`, the time to the first byte, to download the body and their total are added in milliseconds.
+pub fn extra(
+    opts: &Opts,
+    additions: &[Addition],
+    response: &reqwest::Response,
+) -> serde_json::Value {
+    let mut extra = serde_json::json!(additions);
+    if let Some(names) = &opts.save_headers {
+        let mut headers = serde_json::Map::new();
+        for (name, value) in response.headers() {
+            if !names.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name.as_str())) {
+                continue;
+            }
+            let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+            // Repeated headers are joined like they would be in a single header line
+            match headers.get_mut(name.as_str()) {
+                Some(serde_json::Value::String(existing)) => {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                }
+                _ => {
+                    headers.insert(name.to_string(), serde_json::Value::String(value));
+                }
+            }
+        }
+        if let Some(extra) = extra.as_array_mut() {
+            extra.push(serde_json::json!({ "key": "headers", "value": headers }));
+        }
+    }
+    if let Some(RedirectChain(chain)) = response.extensions().get::<RedirectChain>() {
+        if let Some(extra) = extra.as_array_mut().filter(|_| !chain.is_empty()) {
+            extra.push(serde_json::json!({ "key": "redirects", "value": chain }));
+        }
+    }
+    if let Some(addr) = response.remote_addr().filter(|_| opts.save_resolution) {
+        if let Some(extra) = extra.as_array_mut() {
+            extra.push(serde_json::json!({ "key": "ip", "value": addr.ip().to_string() }));
+        }
+    }
+    if let Some(timing) = response.extensions().get::<Timing>() {
+        if let Some(extra) = extra.as_array_mut() {
+            let mut value = serde_json::json!({ "ttfb": timing.ttfb.as_millis() as u64 });
+            if let Some(download) = timing.download {
+                value["download"] = serde_json::json!(download.as_millis() as u64);
+                value["total"] = serde_json::json!((timing.ttfb + download).as_millis() as u64);
+            }
+            extra.push(serde_json::json!({ "key": "timing", "value": value }));
+        }
     }
+    extra
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -385,12 +679,25 @@ pub fn parse_show(
                     }),
                 });
             }
+            "cert" | "certificate" => {
+                if let Some(cert) = cert::Certificate::from_response(response) {
+                    additions.push(Addition {
+                        key: "cert".to_string(),
+                        value: cert.to_string(),
+                    });
+                }
+            }
+            "protocol" | "version" => {
+                additions.push(Addition {
+                    key: "protocol".to_string(),
+                    value: describe_version(opts, response.version()),
+                });
+            }
             "similar" | "similarity" => {
                 // similar:value
-                let similarity = strsim::jaro_winkler(show.1, text);
                 additions.push(Addition {
                     key: "similarity".to_string(),
-                    value: format!("{}%", (similarity * 100.0) as usize),
+                    value: format!("{}%", similarity::similarity(opts, show.1, text)),
                 });
             }
             e => {
@@ -425,7 +732,7 @@ pub fn parse_show(
                         .map_err(|e| {
                             progress.println(format!(
                                 "{} {} {}",
-                                ERROR.to_string().red(),
+                                theme::error(),
                                 "Error running script".bold(),
                                 e
                             ));
@@ -438,15 +745,195 @@ pub fn parse_show(
                         });
                     }
                 } else {
-                    progress.println(format!(
-                        "{} {}",
-                        ERROR.to_string().red(),
-                        "Unknown addition".bold()
-                    ));
+                    progress.println(format!("{} {}", theme::error(), "Unknown addition".bold()));
                 }
             }
         }
     }
 
+    // Values extracted from JSON bodies, nothing is shown for other bodies or missing paths
+    if !opts.show_json.is_empty() {
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(text) {
+            for path in &opts.show_json {
+                if let Some(value) = utils::json_path(&json, path) {
+                    additions.push(Addition {
+                        key: path.clone(),
+                        value: match value {
+                            serde_json::Value::String(value) => value.clone(),
+                            value => value.to_string(),
+                        },
+                    });
+                }
+            }
+        }
+    }
+
+    // Always record the negotiated protocol when a specific HTTP version was requested
+    if opts.http_version.is_some() && !additions.iter().any(|a| a.key == "protocol") {
+        additions.push(Addition {
+            key: "protocol".to_string(),
+            value: describe_version(opts, response.version()),
+        });
+    }
+    // Loops usually come from a misconfigured authentication, always worth a look
+    if let Some(RedirectLoop(cycle)) = response.extensions().get::<RedirectLoop>() {
+        additions.push(Addition {
+            key: "redirect loop".to_string(),
+            value: cycle.join(" -> "),
+        });
+    }
+
     additions
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(filters: &[(&str, &str)]) -> Opts {
+        Opts {
+            filter: filters
+                .iter()
+                .map(|(key, value)| KeyVal(key.to_string(), value.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_status_passes() {
+        let opts = filters(&[("status", "200-299,403"), ("size", "10-")]);
+        assert!(status_passes(&opts, 200));
+        assert!(status_passes(&opts, 403));
+        assert!(!status_passes(&opts, 404));
+        assert!(!status_passes(&filters(&[("!status", "404")]), 404));
+        // Left to the other filters of the group
+        assert!(status_passes(&filters(&[("(1)status", "200")]), 404));
+        assert!(status_passes(
+            &Opts {
+                or: true,
+                ..filters(&[("status", "200")])
+            },
+            404
+        ));
+    }
+
+    #[test]
+    fn test_needs_body() {
+        assert!(!needs_body(&filters(&[
+            ("status", "200"),
+            ("[1]size", "10-"),
+            ("(1)!header", "Server=nginx")
+        ])));
+        assert!(needs_body(&filters(&[
+            ("status", "200"),
+            ("contains", "admin")
+        ])));
+        assert!(needs_body(&Opts {
+            show: vec!["hash".to_string()],
+            ..filters(&[("status", "200")])
+        }));
+    }
+
+    #[test]
+    fn test_skips_body() {
+        assert!(skips_body(&filters(&[
+            ("status", "200-299"),
+            ("(1)!header", "Server=nginx")
+        ])));
+        // The size is only known once the body is read
+        assert!(!skips_body(&filters(&[("status", "200"), ("size", "10-")])));
+        assert!(!skips_body(&Opts {
+            force_body: true,
+            ..filters(&[("status", "200")])
+        }));
+    }
+
+    #[test]
+    fn test_check_method() {
+        let opts = Opts {
+            method_filter: vec![
+                KeyVal("GET".to_string(), "200-299".to_string()),
+                KeyVal("post".to_string(), "405".to_string()),
+            ],
+            ..Default::default()
+        };
+        let with = |method: &str| Opts {
+            method: Some(method.to_string()),
+            ..opts.clone()
+        };
+        assert!(check_method(&with("GET"), 204));
+        assert!(!check_method(&with("GET"), 405));
+        assert!(check_method(&with("POST"), 405));
+        // Methods without a range are dropped
+        assert!(!check_method(&with("PUT"), 200));
+        assert!(check_method(&Opts::default(), 500));
+    }
+
+    #[test]
+    fn test_has_allow_filter() {
+        assert!(!has_allow_filter(&filters(&[])));
+        assert!(!has_allow_filter(&filters(&[
+            ("!status", "404"),
+            ("[1]!size", "0"),
+            ("(1)!contains", "Not Found")
+        ])));
+        assert!(has_allow_filter(&filters(&[
+            ("!status", "404"),
+            ("(1)[2]status", "200")
+        ])));
+        // The default status filter wasn't given by the user
+        assert!(!has_allow_filter(&Opts {
+            default_status: true,
+            ..filters(&[("!size", "0"), ("status", "200-299")])
+        }));
+    }
+
+    #[test]
+    fn test_deny_by_default() {
+        let response = |status: u16| {
+            reqwest::Response::from(
+                http::Response::builder()
+                    .status(status)
+                    .body("Welcome")
+                    .unwrap(),
+            )
+        };
+        let passes = |opts: &Opts, status: u16| {
+            check(
+                opts,
+                &Session::default(),
+                &indicatif::ProgressBar::hidden(),
+                "Welcome",
+                b"Welcome",
+                0,
+                None,
+                &response(status),
+                &rhai::Engine::new(),
+            )
+        };
+        let negated = Opts {
+            deny_by_default: true,
+            default_status: true,
+            ..filters(&[
+                ("!status", "404"),
+                ("!contains", "Not Found"),
+                ("status", "200-299"),
+            ])
+        };
+        assert!(!passes(&negated, 200));
+        assert!(passes(
+            &Opts {
+                deny_by_default: false,
+                ..negated.clone()
+            },
+            200
+        ));
+        let allowed = Opts {
+            deny_by_default: true,
+            ..filters(&[("!contains", "Not Found"), ("status", "200-299")])
+        };
+        assert!(passes(&allowed, 200));
+        assert!(!passes(&allowed, 500));
+    }
+}
@@ -0,0 +1,126 @@
+//! Similarity of two responses, shared by the `similar` filter, `--show similar` and `--skip-similar-children`
+//!
+//! The algorithm is chosen with `--similarity-algo`.
+
+use std::collections::HashMap;
+
+use crate::cli::opts::Opts;
+
+/// Similarity of two texts in percent, with the algorithm of `--similarity-algo`
+pub fn similarity(opts: &Opts, a: &str, b: &str) -> usize {
+    match opts.similarity_algo.as_deref() {
+        Some("hash") => exact(a, b),
+        Some("simhash") => simhash_similarity(a, b),
+        Some("token-ratio") => token_ratio(a, b),
+        _ => jaro_winkler(a, b),
+    }
+}
+
+/// Edit-based similarity, slow on large bodies but fine-grained (default)
+fn jaro_winkler(a: &str, b: &str) -> usize {
+    (strsim::jaro_winkler(a, b) * 100.0) as usize
+}
+
+/// 100 for identical texts, 0 otherwise
+fn exact(a: &str, b: &str) -> usize {
+    if a == b {
+        100
+    } else {
+        0
+    }
+}
+
+/// Part of the bits shared by the simhashes of the texts, a few changed words only flip a few bits
+fn simhash_similarity(a: &str, b: &str) -> usize {
+    if a == b {
+        return 100;
+    }
+    let distance = (simhash(a) ^ simhash(b)).count_ones() as usize;
+    (64 - distance) * 100 / 64
+}
+
+/// Word frequencies in common, regardless of their order
+fn token_ratio(a: &str, b: &str) -> usize {
+    let (a, b) = (frequencies(a), frequencies(b));
+    let total = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return 100;
+    }
+    let common = a
+        .iter()
+        .map(|(token, count)| (*count).min(b.get(token).copied().unwrap_or_default()))
+        .sum::<usize>();
+    common * 2 * 100 / total
+}
+
+fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+}
+
+fn frequencies(text: &str) -> HashMap<&str, usize> {
+    let mut frequencies = HashMap::new();
+    for token in tokens(text) {
+        *frequencies.entry(token).or_default() += 1;
+    }
+    frequencies
+}
+
+fn simhash(text: &str) -> u64 {
+    let mut weights = [0i64; 64];
+    for token in tokens(text) {
+        let hash = hash(token);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if (hash >> bit) & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |simhash, (bit, _)| simhash | (1 << bit))
+}
+
+/// FNV-1a followed by the SplitMix64 finalizer, stable from one run to another
+fn hash(token: &str) -> u64 {
+    let mut hash = token.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(algo: &str) -> Opts {
+        Opts {
+            similarity_algo: Some(algo.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        let page = (0..60)
+            .map(|i| format!("<p>item {}</p>", i))
+            .collect::<String>();
+        let changed = page.replace("item 7<", "item 700<");
+        for algo in ["jaro-winkler", "hash", "simhash", "token-ratio"] {
+            assert_eq!(similarity(&opts(algo), &page, &page), 100);
+        }
+        assert_eq!(similarity(&opts("hash"), &page, &changed), 0);
+        assert!(similarity(&opts("simhash"), &page, &changed) > 80);
+        assert!(similarity(&opts("simhash"), &page, "Not Found") < 80);
+        assert!(similarity(&opts("token-ratio"), &page, &changed) > 95);
+        assert!(similarity(&opts("token-ratio"), &page, "Not Found") < 10);
+        assert!(similarity(&opts("jaro-winkler"), "<h1>Blog</h1>", "<h1>Blog 2</h1>") > 90);
+        assert_eq!(similarity(&opts("jaro-winkler"), "", "index"), 0);
+    }
+}
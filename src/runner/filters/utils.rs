@@ -1,17 +1,36 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use colored::Colorize;
-use reqwest::StatusCode;
+use reqwest::{
+    header::{HeaderMap, LOCATION, SET_COOKIE},
+    StatusCode,
+};
 use rhai::plugin::*;
 
 use crate::{
     cli::opts::Opts,
-    utils::{
-        constants::{ERROR, WARNING},
-        scripting::ScriptingResponse,
-    },
+    utils::{scripting::ScriptingResponse, theme},
 };
 use color_eyre::eyre::Result;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use url::Url;
+
+lazy_static! {
+    /// Regexes of the options, compiled on their first use
+    static ref REGEXES: Mutex<HashMap<String, regex::Regex>> = Mutex::new(HashMap::new());
+}
+
+/// Compiled regex of a pattern given in the options, `None` if it is invalid
+pub fn regex(pattern: &str) -> Option<regex::Regex> {
+    let mut regexes = REGEXES.lock();
+    if let Some(regex) = regexes.get(pattern) {
+        return Some(regex.clone());
+    }
+    let regex = regex::Regex::new(pattern).ok()?;
+    regexes.insert(pattern.to_string(), regex.clone());
+    Some(regex)
+}
 
 pub fn print_error(
     opts: &Opts,
@@ -23,14 +42,14 @@ pub fn print_error(
         if err.is_timeout() {
             print_fn(format!(
                 "{} {} {}",
-                ERROR.to_string().red(),
+                theme::error(),
                 "Timeout reached".bold(),
                 url
             ))?;
         } else if err.is_redirect() {
             print_fn(format!(
                 "{} {} {} {}",
-                WARNING.to_string().yellow(),
+                theme::warning(),
                 "Redirect limit reached".bold(),
                 url,
                 "Check --follow-redirects".dimmed()
@@ -38,7 +57,7 @@ pub fn print_error(
         } else if err.is_connect() {
             print_fn(format!(
                 "{} {} {} {}",
-                ERROR.to_string().red(),
+                theme::error(),
                 "Connection error".bold(),
                 url,
                 format!("({})", err).dimmed()
@@ -46,7 +65,7 @@ pub fn print_error(
         } else if err.is_request() {
             print_fn(format!(
                 "{} {} {} {}",
-                ERROR.to_string().red(),
+                theme::error(),
                 "Request error".bold(),
                 url,
                 format!("({})", err).dimmed()
@@ -54,7 +73,7 @@ pub fn print_error(
         } else {
             print_fn(format!(
                 "{} {} {} {}",
-                ERROR.to_string().red(),
+                theme::error(),
                 "Unknown Error".bold(),
                 url,
                 format!("({})", err).dimmed()
@@ -64,6 +83,24 @@ pub fn print_error(
     Ok(())
 }
 
+/// Check a hit against the results of a previous run (`--only-new`)
+///
+/// Returns `None` if the hit should not be printed, otherwise a note to append to the printed line
+pub fn check_previous(
+    opts: &Opts,
+    previous: Option<&HashMap<String, u16>>,
+    url: &str,
+    status_code: u16,
+) -> Option<String> {
+    match previous.and_then(|previous| previous.get(url)) {
+        None => Some("".to_string()),
+        Some(&old) if old != status_code && opts.show_status_changes => {
+            Some(format!(" {}", format!("(was {})", old).dimmed()))
+        }
+        Some(_) => None,
+    }
+}
+
 pub fn is_html_directory(body: &str) -> bool {
     let body = body.to_lowercase();
     // Apache
@@ -129,7 +166,7 @@ pub fn is_directory(
             .map_err(|e| {
                 progress.println(format!(
                     "{} {} {}",
-                    ERROR.to_string().red(),
+                    theme::error(),
                     "Error running script".bold(),
                     e
                 ));
@@ -141,7 +178,7 @@ pub fn is_directory(
             } else {
                 progress.println(format!(
                     "{} {}",
-                    ERROR.to_string().red(),
+                    theme::error(),
                     "Script did not return a boolean".bold()
                 ));
             }
@@ -202,3 +239,159 @@ pub fn is_directory(
 
     false
 }
+
+/// Check if a request was redirected to the same path with a trailing slash (`--recurse-redirects`)
+///
+/// Works whether the redirect was followed or not, only the same host counts
+pub fn is_slash_redirect(requested: &str, response: &reqwest::Response) -> bool {
+    let Ok(requested) = Url::parse(requested) else {
+        return false;
+    };
+    let target = if response.status().is_redirection() {
+        response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| response.url().join(location).ok())
+    } else {
+        Some(response.url().clone())
+    };
+    target.is_some_and(|target| is_slash_of(&requested, &target))
+}
+
+fn is_slash_of(requested: &Url, target: &Url) -> bool {
+    !requested.path().ends_with('/')
+        && target.host_str() == requested.host_str()
+        && target.path() == format!("{}/", requested.path())
+}
+
+/// Decode hex digits into bytes, spaces between them are ignored
+pub fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let digits = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<_>>>()?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
+
+/// Check if `bytes` appear anywhere in `body`
+pub fn contains_bytes(body: &[u8], bytes: &[u8]) -> bool {
+    bytes.is_empty() || body.windows(bytes.len()).any(|window| window == bytes)
+}
+
+/// Check if one of the `Set-Cookie` headers matches the regex, never when there is none
+pub fn set_cookie_matches(headers: &HeaderMap, regex: &str) -> bool {
+    let Some(regex) = self::regex(regex) else {
+        return false;
+    };
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| regex.is_match(value))
+}
+
+/// Get the value at a JSON path such as `$.data.items[0].id` or `$['content-type']`
+pub fn json_path<'a>(json: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let mut rest = path.trim().strip_prefix('$').unwrap_or(path.trim());
+    let mut value = json;
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('[') {
+            let (key, after) = inner.split_once(']')?;
+            let key = key.trim_matches(|c| c == '\'' || c == '"');
+            value = match key.parse::<usize>() {
+                Ok(index) if value.is_array() => value.get(index)?,
+                _ => value.get(key)?,
+            };
+            rest = after;
+        } else {
+            let inner = rest.strip_prefix('.').unwrap_or(rest);
+            let end = inner.find(['.', '[']).unwrap_or(inner.len());
+            value = value.get(&inner[..end])?;
+            rest = &inner[end..];
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_slash_of() {
+        let requested = Url::parse("http://localhost/admin").unwrap();
+        let target = |url: &str| Url::parse(url).unwrap();
+        assert!(is_slash_of(&requested, &target("http://localhost/admin/")));
+        assert!(is_slash_of(&requested, &target("https://localhost/admin/")));
+        assert!(!is_slash_of(&requested, &target("http://localhost/login/")));
+        assert!(!is_slash_of(
+            &requested,
+            &target("http://example.com/admin/")
+        ));
+        assert!(!is_slash_of(&requested, &target("http://localhost/admin")));
+        assert!(!is_slash_of(
+            &target("http://localhost/admin/"),
+            &target("http://localhost/admin//")
+        ));
+    }
+
+    #[test]
+    fn test_json_path() {
+        let json = serde_json::json!({
+            "data": { "id": 42, "items": [{ "name": "admin" }], "content-type": "json" }
+        });
+        assert_eq!(json_path(&json, "$.data.id"), Some(&serde_json::json!(42)));
+        assert_eq!(
+            json_path(&json, "$.data.items[0].name"),
+            Some(&serde_json::json!("admin"))
+        );
+        assert_eq!(
+            json_path(&json, "data['content-type']"),
+            Some(&serde_json::json!("json"))
+        );
+        assert_eq!(json_path(&json, "$"), Some(&json));
+        assert_eq!(json_path(&json, "$.data.missing"), None);
+        assert_eq!(json_path(&json, "$.data.items[1]"), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("504b0304"), Some(vec![0x50, 0x4b, 0x03, 0x04]));
+        assert_eq!(
+            decode_hex("89 50 4E 47"),
+            Some(vec![0x89, 0x50, 0x4e, 0x47])
+        );
+        assert_eq!(decode_hex("504"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+
+    #[test]
+    fn test_contains_bytes() {
+        let body = b"\x89PNG\r\n\x1a\n\x00\x00";
+        assert!(contains_bytes(body, &[0x89, 0x50, 0x4e, 0x47]));
+        assert!(contains_bytes(body, &[0x1a, 0x0a, 0x00]));
+        assert!(!contains_bytes(body, b"PK"));
+        assert!(!contains_bytes(b"", b"PK"));
+    }
+
+    #[test]
+    fn test_set_cookie_matches() {
+        let mut headers = HeaderMap::new();
+        assert!(!set_cookie_matches(&headers, ".*"));
+        headers.append(SET_COOKIE, "theme=dark; Path=/".parse().unwrap());
+        headers.append(SET_COOKIE, "PHPSESSID=abc; HttpOnly".parse().unwrap());
+        assert!(set_cookie_matches(&headers, "(?i)sess"));
+        assert!(set_cookie_matches(&headers, "^theme="));
+        assert!(!set_cookie_matches(&headers, "^token="));
+    }
+}
@@ -0,0 +1,184 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, LOCATION};
+use serde_json::{json, Value};
+
+use crate::{
+    cli::opts::Opts,
+    utils::{constants::DEFAULT_METHOD, utc},
+};
+
+/// Check if one of the outputs is an HTTP Archive
+pub fn enabled(opts: &Opts) -> bool {
+    opts.output
+        .iter()
+        .any(|output| output.trim_end_matches(".gz").ends_with(".har"))
+}
+
+/// Matched requests and responses of a scan, written to the `.har` outputs at the end of it
+#[derive(Debug, Default)]
+pub struct Har(Mutex<Vec<Value>>);
+
+impl Har {
+    /// Record a matched response, bodies are cut to `--max-body-size`
+    ///
+    /// The request is rebuilt from the options, with the headers sent with every request.
+    pub fn record(
+        &self,
+        opts: &Opts,
+        url: &str,
+        data: Option<&str>,
+        response: &reqwest::Response,
+        text: &str,
+        time: Duration,
+    ) {
+        if !enabled(opts) {
+            return;
+        }
+        let started = SystemTime::now() - time;
+        let mut request_headers = super::client::default_headers(opts).unwrap_or_default();
+        if let Ok(user_agent) = super::client::user_agent(opts).parse() {
+            request_headers.insert(reqwest::header::USER_AGENT, user_agent);
+        }
+        let query = reqwest::Url::parse(url)
+            .map(|url| {
+                url.query_pairs()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let version = format!("{:?}", response.version());
+        let mut request = json!({
+            "method": opts.method.as_deref().unwrap_or(DEFAULT_METHOD),
+            "url": url,
+            "httpVersion": version,
+            "cookies": [],
+            "headers": headers(&request_headers),
+            "queryString": query,
+            "headersSize": -1,
+            "bodySize": data.map_or(0, str::len),
+        });
+        if let Some(data) = data {
+            // `--body-type` is set on the request itself, not with the other headers
+            let mut content_type = header(&request_headers, CONTENT_TYPE);
+            if content_type.is_empty() {
+                content_type = super::client::content_type(opts)
+                    .unwrap_or_default()
+                    .to_string();
+            }
+            request["postData"] = json!({
+                "mimeType": content_type,
+                "text": data,
+            });
+        }
+        let ms = time.as_secs_f64() * 1000.0;
+        let entry = json!({
+            "startedDateTime": timestamp(started),
+            "time": ms,
+            "request": request,
+            "response": {
+                "status": response.status().as_u16(),
+                "statusText": response.status().canonical_reason().unwrap_or_default(),
+                "httpVersion": version,
+                "cookies": [],
+                "headers": headers(response.headers()),
+                "content": {
+                    "size": text.len(),
+                    "mimeType": header(response.headers(), CONTENT_TYPE),
+                    "text": truncate(text, opts.max_body_size),
+                },
+                "redirectURL": header(response.headers(), LOCATION),
+                "headersSize": -1,
+                "bodySize": text.len(),
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": ms, "receive": 0 },
+        });
+        self.0.lock().push(entry);
+    }
+
+    /// The archive of all the recorded entries
+    pub fn export(&self, pretty: bool) -> serde_json::Result<String> {
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "rwalk", "version": env!("CARGO_PKG_VERSION") },
+                "entries": self.0.lock().clone(),
+            }
+        });
+        if pretty {
+            serde_json::to_string_pretty(&har)
+        } else {
+            serde_json::to_string(&har)
+        }
+    }
+}
+
+fn headers(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({ "name": name.as_str(), "value": String::from_utf8_lossy(value.as_bytes()) })
+        })
+        .collect()
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> String {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string())
+        .unwrap_or_default()
+}
+
+/// Cut a body to at most `max` bytes, without splitting a character
+fn truncate(text: &str, max: Option<usize>) -> &str {
+    match max {
+        Some(max) if text.len() > max => {
+            let end = (0..=max)
+                .rev()
+                .find(|i| text.is_char_boundary(*i))
+                .unwrap_or(0);
+            &text[..end]
+        }
+        _ => text,
+    }
+}
+
+/// Format a time as ISO 8601 in UTC, e.g. `2024-05-01T12:30:00.250Z`
+fn timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_millis(1_709_210_096_250)),
+            "2024-02-29T12:34:56.250Z"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("hello", None), "hello");
+        assert_eq!(truncate("hello", Some(3)), "hel");
+        assert_eq!(truncate("héllo", Some(2)), "h");
+    }
+}
@@ -0,0 +1,132 @@
+//! Cap on the requests in flight to each host (`--max-conns-per-host`), whatever the number of threads
+
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::cli::opts::Opts;
+
+/// Slot of a request to a host, kept in the extensions of its response so that it is only freed once the response is dropped
+#[derive(Debug, Clone)]
+pub struct HostPermit(#[allow(dead_code)] Arc<OwnedSemaphorePermit>);
+
+/// Slots of each host, shared by the requests of a scan
+#[derive(Debug, Default)]
+pub struct HostLimit {
+    limit: Option<usize>,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimit {
+    pub fn new(opts: &Opts) -> Self {
+        Self {
+            limit: opts.max_conns_per_host.filter(|limit| *limit > 0),
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for a slot to send a request to the host of the URL, `None` without `--max-conns-per-host`
+    pub async fn acquire(&self, url: &reqwest::Url) -> Option<HostPermit> {
+        let limit = self.limit?;
+        let semaphore = self
+            .semaphores
+            .lock()
+            .entry(key(url))
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone();
+        semaphore
+            .acquire_owned()
+            .await
+            .ok()
+            .map(Arc::new)
+            .map(HostPermit)
+    }
+}
+
+/// Free the slot of a response before it is dropped, e.g. once its body is read
+///
+/// Needed before sending another request to the same host while the response is still around.
+pub fn release(response: &mut reqwest::Response) {
+    response.extensions_mut().remove::<HostPermit>();
+}
+
+/// Host and port the requests of a URL are sent to
+fn key(url: &reqwest::Url) -> String {
+    format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::session::Session;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_acquire() {
+        let limit = HostLimit::new(&Opts {
+            max_conns_per_host: Some(1),
+            ..Default::default()
+        });
+        let url = |s: &str| reqwest::Url::parse(s).unwrap();
+        let first = limit.acquire(&url("http://a.example.com/x")).await.unwrap();
+        // Another host has a slot of its own
+        assert!(limit.acquire(&url("http://b.example.com/")).await.is_some());
+        let same_host = url("http://a.example.com:80/y");
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            limit.acquire(&same_host),
+        );
+        assert!(waiting.await.is_err());
+        drop(first);
+        assert!(limit
+            .acquire(&url("http://a.example.com/y"))
+            .await
+            .is_some());
+        assert!(HostLimit::default()
+            .acquire(&url("http://a.example.com/"))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_head_then_get() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    )
+                    .await;
+            }
+        });
+        let opts = Opts {
+            head_then_get: true,
+            max_conns_per_host: Some(1),
+            ..Default::default()
+        };
+        let session = Session {
+            host_limit: HostLimit::new(&opts),
+            ..Default::default()
+        };
+        let client = reqwest::Client::new();
+        let request = client.get(format!("http://{}/", addr)).build().unwrap();
+        // The HEAD response must give its slot back before the GET
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            crate::runner::client::head_then_get(&opts, &session, &client, request, true),
+        )
+        .await
+        .expect("the GET waited for the slot of the HEAD request")
+        .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+}
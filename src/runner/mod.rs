@@ -1,9 +1,37 @@
+pub mod auth;
+pub mod autopilot;
+pub mod breaker;
+pub mod case;
 pub mod classic;
 pub mod client;
+pub mod context;
+pub mod dns;
+pub mod dump;
+pub mod errors;
 pub mod filters;
+pub mod har;
+pub mod host_limit;
+pub mod notify;
+pub mod ntlm;
+pub mod parents;
+pub mod passive;
+pub mod pool;
+pub mod printed;
+pub mod proxies;
+pub mod rate;
+pub mod rate_limit;
 pub mod recursive;
+pub mod request_log;
+pub mod scorer;
+pub mod session;
+pub mod sigv4;
+pub mod slow_start;
 pub mod spider;
+pub mod stream;
+pub mod template;
+pub mod webhook;
 pub mod wordlists;
+pub mod workers;
 
 use std::future::Future;
 
@@ -0,0 +1,80 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::{cli::opts::Opts, utils::shell};
+
+/// Runs `--notify-cmd` for the matches and at the end of a scan
+#[derive(Debug, Default)]
+pub struct Notifier {
+    /// Commands still running, waited for before the end of the scan
+    pending: Mutex<Vec<JoinHandle<()>>>,
+    matches: AtomicUsize,
+}
+
+impl Notifier {
+    /// Run `--notify-cmd` in the background for a match, with `--notify-on-match`
+    pub fn on_match(&self, opts: &Opts, url: &str, status_code: u16) {
+        let count = self.matches.fetch_add(1, Ordering::Relaxed) + 1;
+        let Some(cmd) = opts.notify_cmd.as_deref().filter(|_| opts.notify_on_match) else {
+            return;
+        };
+        let cmd = expand(cmd, url, status_code, count);
+        self.pending.lock().push(tokio::spawn(run(cmd)));
+    }
+
+    /// Run `--notify-cmd` once the scan is over, after the commands of the matches
+    pub async fn on_finish(&self, opts: &Opts, url: &str, status_code: u16, count: usize) {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        futures::future::join_all(pending).await;
+        if let Some(cmd) = &opts.notify_cmd {
+            run(expand(cmd, url, status_code, count)).await;
+        }
+    }
+}
+
+/// Failures are only reported, they don't stop the scan
+async fn run(cmd: String) {
+    match shell(&cmd).status().await {
+        Ok(status) if !status.success() => {
+            log::warn!("Notification command exited with {}: {}", status, cmd)
+        }
+        Err(err) => log::warn!("Failed to run the notification command {}: {}", cmd, err),
+        Ok(_) => {}
+    }
+}
+
+/// Replace the placeholders, values are quoted as they may come from the server
+fn expand(cmd: &str, url: &str, status_code: u16, count: usize) -> String {
+    cmd.replace("{url}", &quote(url))
+        .replace("{status}", &status_code.to_string())
+        .replace("{count}", &count.to_string())
+}
+
+fn quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', ""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_expand() {
+        assert_eq!(
+            expand(
+                "notify-send {status} {url} ({count})",
+                "http://localhost/a';rm -rf ~'",
+                200,
+                3
+            ),
+            "notify-send 200 'http://localhost/a'\\'';rm -rf ~'\\''' (3)"
+        );
+    }
+}
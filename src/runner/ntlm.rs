@@ -0,0 +1,342 @@
+//! NTLMv2 authentication (`--ntlm`), as described in [MS-NLMP](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-nlmp)
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md_5_digest::Md5;
+use reqwest::header::{HeaderMap, WWW_AUTHENTICATE};
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+/// UNICODE | OEM | REQUEST_TARGET | NTLM | ALWAYS_SIGN | EXTENDED_SESSIONSECURITY | 128 | 56
+const FLAGS: u32 = 0xa008_8207;
+/// Target info entry holding the server time, in which case the LMv2 response must be empty
+const AV_TIMESTAMP: u16 = 7;
+/// Seconds between 1601-01-01 (Windows FILETIME epoch) and 1970-01-01
+const FILETIME_OFFSET: u64 = 11_644_473_600;
+
+/// Credentials given as `[DOMAIN\]USER:PASSWORD`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub domain: String,
+    pub user: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (login, password) = s.split_once(':')?;
+        let (domain, user) = login.split_once('\\').unwrap_or(("", login));
+        if user.is_empty() {
+            return None;
+        }
+        Some(Self {
+            domain: domain.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+/// What the server sent back in its `WWW-Authenticate` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// `NTLM` or `Negotiate`, the answer has to use the same scheme
+    pub scheme: &'static str,
+    pub server_challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+
+impl Challenge {
+    /// Find an NTLM challenge in the `WWW-Authenticate` headers
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| {
+                let (scheme, token) = value.trim().split_once(' ')?;
+                let scheme = ["NTLM", "Negotiate"]
+                    .into_iter()
+                    .find(|s| s.eq_ignore_ascii_case(scheme))?;
+                Self::parse(scheme, &STANDARD.decode(token.trim()).ok()?)
+            })
+    }
+
+    fn parse(scheme: &'static str, message: &[u8]) -> Option<Self> {
+        if message.len() < 32 || &message[..8] != SIGNATURE || read_u32(message, 8)? != 2 {
+            return None;
+        }
+        let target_info = if message.len() >= 48 {
+            let len = read_u16(message, 40)? as usize;
+            let offset = read_u32(message, 44)? as usize;
+            message.get(offset..offset + len)?.to_vec()
+        } else {
+            Vec::new()
+        };
+        Some(Self {
+            scheme,
+            server_challenge: message[24..32].try_into().ok()?,
+            target_info,
+        })
+    }
+
+    /// Server time from the target info, if any
+    fn timestamp(&self) -> Option<u64> {
+        let mut info = self.target_info.as_slice();
+        while info.len() >= 4 {
+            let id = u16::from_le_bytes([info[0], info[1]]);
+            let len = u16::from_le_bytes([info[2], info[3]]) as usize;
+            let value = info.get(4..4 + len)?;
+            match id {
+                0 => return None,
+                AV_TIMESTAMP => return Some(u64::from_le_bytes(value.try_into().ok()?)),
+                _ => info = &info[4 + len..],
+            }
+        }
+        None
+    }
+}
+
+/// Whether the server only offers `Negotiate`, meaning the handshake has to be restarted with that scheme
+pub fn offers_negotiate(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.trim().eq_ignore_ascii_case("Negotiate"))
+}
+
+/// First message of the handshake, as an `Authorization` header value
+pub fn negotiate(scheme: &str) -> String {
+    let mut message = SIGNATURE.to_vec();
+    message.extend(1u32.to_le_bytes());
+    message.extend(FLAGS.to_le_bytes());
+    // Empty domain and workstation
+    message.extend([0; 16]);
+    format!("{} {}", scheme, STANDARD.encode(message))
+}
+
+/// Last message of the handshake, answering the server challenge
+pub fn authenticate(credentials: &Credentials, challenge: &Challenge) -> String {
+    let client_challenge = rand_bytes();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let timestamp =
+        (now.as_secs() + FILETIME_OFFSET) * 10_000_000 + now.subsec_nanos() as u64 / 100;
+    format!(
+        "{} {}",
+        challenge.scheme,
+        STANDARD.encode(authenticate_message(
+            credentials,
+            challenge,
+            client_challenge,
+            timestamp
+        ))
+    )
+}
+
+fn authenticate_message(
+    credentials: &Credentials,
+    challenge: &Challenge,
+    client_challenge: [u8; 8],
+    timestamp: u64,
+) -> Vec<u8> {
+    let key = ntowf_v2(credentials);
+    let server_timestamp = challenge.timestamp();
+
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend(server_timestamp.unwrap_or(timestamp).to_le_bytes());
+    blob.extend(client_challenge);
+    blob.extend([0; 4]);
+    blob.extend(&challenge.target_info);
+    blob.extend([0; 4]);
+    let mut nt_response = hmac_md5(&key, &[&challenge.server_challenge, &blob]).to_vec();
+    nt_response.extend(blob);
+
+    let lm_response = if server_timestamp.is_some() {
+        vec![0; 24]
+    } else {
+        let mut lm_response =
+            hmac_md5(&key, &[&challenge.server_challenge, &client_challenge]).to_vec();
+        lm_response.extend(client_challenge);
+        lm_response
+    };
+
+    let fields = [
+        lm_response,
+        nt_response,
+        utf16(&credentials.domain),
+        utf16(&credentials.user),
+        // Workstation and session key
+        Vec::new(),
+        Vec::new(),
+    ];
+    let mut message = SIGNATURE.to_vec();
+    message.extend(3u32.to_le_bytes());
+    let mut offset = 64u32;
+    for field in &fields {
+        message.extend((field.len() as u16).to_le_bytes());
+        message.extend((field.len() as u16).to_le_bytes());
+        message.extend(offset.to_le_bytes());
+        offset += field.len() as u32;
+    }
+    message.extend((FLAGS & !0x2).to_le_bytes());
+    for field in fields {
+        message.extend(field);
+    }
+    message
+}
+
+/// NTOWFv2: HMAC-MD5 of the uppercased user and the domain, keyed with the MD4 of the password
+fn ntowf_v2(credentials: &Credentials) -> [u8; 16] {
+    let password_hash = md4(&utf16(&credentials.password));
+    hmac_md5(
+        &password_hash,
+        &[&utf16(&format!(
+            "{}{}",
+            credentials.user.to_uppercase(),
+            credentials.domain
+        ))],
+    )
+}
+
+fn hmac_md5(key: &[u8], data: &[&[u8]]) -> [u8; 16] {
+    let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC takes keys of any size");
+    for part in data {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// MD4 (RFC 1320), only used to hash the password
+fn md4(data: &[u8]) -> [u8; 16] {
+    Md4::digest(data).into()
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        message.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(message: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        message.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Client challenge, from a cryptographically secure generator seeded by the OS
+fn rand_bytes() -> [u8; 8] {
+    rand::random()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_parse_credentials() {
+        assert_eq!(
+            Credentials::parse("CORP\\john:pass:word"),
+            Some(Credentials {
+                domain: "CORP".to_string(),
+                user: "john".to_string(),
+                password: "pass:word".to_string(),
+            })
+        );
+        assert_eq!(Credentials::parse("john:").unwrap().domain, "");
+        assert!(Credentials::parse("john").is_none());
+        assert!(Credentials::parse("CORP\\:pass").is_none());
+    }
+
+    #[test]
+    fn test_md4() {
+        assert_eq!(hex(&md4(b"")), "31d6cfe0d16ae931b73c59d7e0c089c0");
+        assert_eq!(hex(&md4(b"abc")), "a448017aaf21d8525fc10ae87aa6729d");
+        assert_eq!(
+            hex(&md4(&utf16("Password"))),
+            "a4f49c406510bdcab6824ee7c30fd852"
+        );
+    }
+
+    /// Example from [MS-NLMP] 4.2.4
+    #[test]
+    fn test_authenticate_message() {
+        let credentials = Credentials {
+            domain: "Domain".to_string(),
+            user: "User".to_string(),
+            password: "Password".to_string(),
+        };
+        assert_eq!(
+            hex(&ntowf_v2(&credentials)),
+            "0c868a403bfd7a93a3001ef22ef02e3f"
+        );
+
+        let mut target_info = vec![2, 0, 12, 0];
+        target_info.extend(utf16("Domain"));
+        target_info.extend([1, 0, 12, 0]);
+        target_info.extend(utf16("Server"));
+        target_info.extend([0, 0, 0, 0]);
+        let challenge = Challenge {
+            scheme: "NTLM",
+            server_challenge: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+            target_info,
+        };
+        let message = authenticate_message(&credentials, &challenge, [0xaa; 8], 0);
+        let field = |offset: usize| {
+            let len = read_u16(&message, offset).unwrap() as usize;
+            let start = read_u32(&message, offset + 4).unwrap() as usize;
+            &message[start..start + len]
+        };
+        assert_eq!(
+            hex(field(12)),
+            "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa"
+        );
+        assert_eq!(hex(&field(20)[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+        assert_eq!(field(28), utf16("Domain"));
+        assert_eq!(field(36), utf16("User"));
+    }
+
+    #[test]
+    fn test_challenge_from_headers() {
+        let mut message = SIGNATURE.to_vec();
+        message.extend(2u32.to_le_bytes());
+        message.extend([0; 8]);
+        message.extend(FLAGS.to_le_bytes());
+        message.extend([1, 2, 3, 4, 5, 6, 7, 8]);
+        message.extend([0; 8]);
+        message.extend([4, 0, 4, 0, 48, 0, 0, 0]);
+        message.extend([0, 0, 0, 0]);
+
+        let mut headers = HeaderMap::new();
+        headers.append(WWW_AUTHENTICATE, "Basic realm=\"x\"".parse().unwrap());
+        assert!(Challenge::from_headers(&headers).is_none());
+        headers.append(
+            WWW_AUTHENTICATE,
+            format!("Negotiate {}", STANDARD.encode(&message))
+                .parse()
+                .unwrap(),
+        );
+        let challenge = Challenge::from_headers(&headers).unwrap();
+        assert_eq!(challenge.scheme, "Negotiate");
+        assert_eq!(challenge.server_challenge, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(challenge.target_info, vec![0, 0, 0, 0]);
+        assert_eq!(challenge.timestamp(), None);
+    }
+
+    #[test]
+    fn test_rand_bytes() {
+        assert_ne!(rand_bytes(), rand_bytes());
+    }
+}
@@ -0,0 +1,123 @@
+//! Parent directories of the root URL, probed once before the scan (`--scan-parents`)
+
+use std::sync::Arc;
+
+use color_eyre::eyre::Result;
+use colored::Colorize;
+use parking_lot::Mutex;
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::{
+    cli::opts::Opts,
+    runner::session::Session,
+    utils::{
+        theme,
+        tree::{Tree, TreeData, UrlType},
+    },
+};
+
+/// Parents of a URL up to the host root, the closest first (`/a/b/c` gives `/a/b`, `/a` and `/`)
+pub fn parents(url: &Url) -> Vec<Url> {
+    let segments = url
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    (0..segments.len())
+        .rev()
+        .map(|len| {
+            let mut parent = url.clone();
+            parent.set_path(&format!("/{}", segments[..len].join("/")));
+            parent.set_query(None);
+            parent.set_fragment(None);
+            parent
+        })
+        .collect()
+}
+
+/// Request the parents of the root and add the ones found to the tree, next to the results of the scan
+///
+/// Those answering `404` or not passing the status filters are left out, as well as the URLs already in the tree.
+pub async fn scan(
+    opts: &Opts,
+    session: &Session,
+    client: &reqwest::Client,
+    tree: &Arc<Mutex<Tree<TreeData>>>,
+) -> Result<()> {
+    let Some(root) = tree.lock().root.clone() else {
+        return Ok(());
+    };
+    let root_url = Url::parse(&root.lock().data.url)?;
+    let known = root
+        .lock()
+        .flatten()
+        .into_iter()
+        .map(|data| data.url.trim_end_matches('/').to_string())
+        .collect::<Vec<_>>();
+    for parent in parents(&root_url) {
+        if known.contains(&parent.as_str().trim_end_matches('/').to_string()) {
+            continue;
+        }
+        let request = super::client::build_request(opts, parent.as_str(), client)?;
+        let status_code = match super::client::execute(opts, session, client, request).await {
+            Ok(response) => response.status().as_u16(),
+            Err(err) => {
+                log::debug!("Failed to request the parent {}: {}", parent, err);
+                continue;
+            }
+        };
+        if status_code == StatusCode::NOT_FOUND.as_u16()
+            || !super::filters::status_passes(opts, status_code)
+        {
+            continue;
+        }
+        if !opts.quiet && session.printed.allow() {
+            println!(
+                "{} {} {} {}",
+                theme::status(status_code),
+                status_code.to_string().bold(),
+                parent,
+                "(parent)".dimmed()
+            );
+        }
+        tree.lock().insert(
+            TreeData {
+                url: parent.to_string(),
+                depth: 0,
+                path: parent.path().to_string(),
+                status_code,
+                extra: serde_json::json!([{ "key": "parent", "value": "true" }]),
+                // Not recursed into, the scan stays under the root
+                url_type: UrlType::Unknown,
+                response: None,
+            },
+            Some(root.clone()),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parents() {
+        let parents = |url: &str| {
+            parents(&Url::parse(url).unwrap())
+                .iter()
+                .map(Url::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            parents("https://example.com/a/b/c/?q=1"),
+            vec![
+                "https://example.com/a/b",
+                "https://example.com/a",
+                "https://example.com/"
+            ]
+        );
+        assert!(parents("https://example.com/").is_empty());
+    }
+}
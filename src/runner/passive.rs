@@ -0,0 +1,262 @@
+//! Responses captured by a previous scan run through the filters again, without any request (`--passive`)
+//!
+//! The captures are read from an HTTP Archive (`.har` output) or from a `--dump-dir` directory.
+
+use std::{path::Path, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::{bail, Context, Result};
+use colored::Colorize;
+use parking_lot::Mutex;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    ResponseBuilderExt, Url,
+};
+use serde_json::Value;
+
+use crate::{
+    cli::opts::Opts,
+    utils::{
+        relative_path,
+        scripting::ScriptingResponse,
+        theme,
+        tree::{Tree, TreeData, UrlType},
+    },
+};
+
+use super::{dump::MANIFEST_FILE, filters::utils::is_directory, session::Session};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub url: String,
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    /// Raw bytes, for `--filter-bytes`
+    pub body: Vec<u8>,
+    /// Response time in milliseconds, 0 when unknown
+    pub time: u128,
+}
+
+impl Capture {
+    /// Response as the runners get it from the client
+    fn to_response(&self) -> Result<reqwest::Response> {
+        let mut builder = http::Response::builder()
+            .status(self.status_code)
+            .url(Url::parse(&self.url)?);
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                builder = builder.header(name, value);
+            }
+        }
+        Ok(builder.body(self.body.clone())?.into())
+    }
+}
+
+/// Read the captures of a `.har` file or of a `--dump-dir` directory
+pub fn load(path: &str) -> Result<Vec<Capture>> {
+    let path = Path::new(path);
+    if path.is_dir() {
+        return load_dump(path);
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display().to_string().bold()))?;
+    parse_har(&content)
+}
+
+/// Entries of an HTTP Archive, base64 bodies are decoded
+pub fn parse_har(content: &str) -> Result<Vec<Capture>> {
+    let har: Value = serde_json::from_str(content).context("Invalid HAR file")?;
+    let Some(entries) = har["log"]["entries"].as_array() else {
+        bail!("Invalid HAR file, missing log.entries");
+    };
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let response = &entry["response"];
+            let content = &response["content"];
+            let text = content["text"].as_str().unwrap_or_default();
+            let body = if content["encoding"] == "base64" {
+                STANDARD.decode(text).ok()?
+            } else {
+                text.as_bytes().to_vec()
+            };
+            Some(Capture {
+                url: entry["request"]["url"].as_str()?.to_string(),
+                status_code: response["status"].as_u64()? as u16,
+                headers: response["headers"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|header| {
+                        Some((
+                            header["name"].as_str()?.to_string(),
+                            header["value"].as_str()?.to_string(),
+                        ))
+                    })
+                    .collect(),
+                body,
+                time: entry["time"].as_f64().unwrap_or_default() as u128,
+            })
+        })
+        .collect())
+}
+
+/// Bodies of a dump directory, only matched responses are dumped so their status is taken as 200
+fn load_dump(dir: &Path) -> Result<Vec<Capture>> {
+    let manifest = std::fs::read_to_string(dir.join(MANIFEST_FILE)).with_context(|| {
+        format!(
+            "Missing {} in {}",
+            MANIFEST_FILE,
+            dir.display().to_string().bold()
+        )
+    })?;
+    manifest
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(name, url)| {
+            let body = std::fs::read(dir.join(name))
+                .with_context(|| format!("Failed to read the dumped body {}", name.bold()))?;
+            Ok(Capture {
+                url: url.to_string(),
+                status_code: 200,
+                headers: Vec::new(),
+                body,
+                time: 0,
+            })
+        })
+        .collect()
+}
+
+/// Run the captures through the filters, the ones kept are added to the tree under its root
+///
+/// Returns the number of captures kept.
+pub fn replay(
+    opts: &Opts,
+    session: &Session,
+    captures: &[Capture],
+    tree: &Arc<Mutex<Tree<TreeData>>>,
+) -> Result<usize> {
+    let Some(root) = tree.lock().root.clone() else {
+        return Ok(0);
+    };
+    let root_url = Url::parse(&root.lock().data.url)?;
+    let progress = indicatif::ProgressBar::hidden();
+    let mut engine = rhai::Engine::new();
+    engine.build_type::<ScriptingResponse>();
+    let mut kept = 0;
+    for capture in captures {
+        let response = match capture.to_response() {
+            Ok(response) => response,
+            Err(err) => {
+                log::warn!("Skipping the capture of {}: {}", capture.url.bold(), err);
+                continue;
+            }
+        };
+        let text = String::from_utf8_lossy(&capture.body).to_string();
+        if !(super::filters::check(
+            opts,
+            session,
+            &progress,
+            &text,
+            &capture.body,
+            capture.time,
+            None,
+            &response,
+            &engine,
+        ) && super::filters::check_method(opts, capture.status_code))
+        {
+            continue;
+        }
+        kept += 1;
+        let additions = super::filters::parse_show(opts, &text, &response, &progress, &engine);
+        if !opts.quiet && session.printed.allow() {
+            println!(
+                "{} {} {}{}",
+                theme::status(capture.status_code),
+                capture.status_code.to_string().bold(),
+                capture.url,
+                additions.iter().fold("".to_string(), |acc, addition| {
+                    format!(
+                        "{} | {}: {}",
+                        acc,
+                        addition.key.dimmed().bold(),
+                        addition.value.dimmed()
+                    )
+                })
+            );
+        }
+        let url = Url::parse(&capture.url)?;
+        let content_type = response.headers().get("content-type").map(|value| {
+            value
+                .to_str()
+                .unwrap_or_default()
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+        let is_dir = is_directory(opts, &response, text.clone(), &progress);
+        let data = TreeData {
+            url: capture.url.clone(),
+            depth: 0,
+            path: relative_path(&url, &root_url),
+            status_code: capture.status_code,
+            extra: super::filters::extra(opts, &additions, &response),
+            url_type: if is_dir {
+                UrlType::Directory
+            } else if let Some(content_type) = content_type {
+                UrlType::File(content_type)
+            } else {
+                UrlType::Unknown
+            },
+            response: None,
+        };
+        tree.lock().insert(data, Some(root.clone()));
+    }
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_har() {
+        let captures = parse_har(
+            r#"{"log": {"entries": [
+                {"time": 12.5, "request": {"url": "http://x/admin"}, "response": {"status": 403,
+                    "headers": [{"name": "Server", "value": "nginx"}], "content": {"text": "Forbidden"}}},
+                {"request": {"url": "http://x/logo"}, "response": {"status": 200, "headers": [],
+                    "content": {"text": "UE5H", "encoding": "base64"}}},
+                {"request": {}, "response": {"status": 200}}
+            ]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            captures,
+            vec![
+                Capture {
+                    url: "http://x/admin".to_string(),
+                    status_code: 403,
+                    headers: vec![("Server".to_string(), "nginx".to_string())],
+                    body: b"Forbidden".to_vec(),
+                    time: 12,
+                },
+                Capture {
+                    url: "http://x/logo".to_string(),
+                    status_code: 200,
+                    headers: Vec::new(),
+                    body: b"PNG".to_vec(),
+                    time: 0,
+                },
+            ]
+        );
+        let response = captures[0].to_response().unwrap();
+        assert_eq!(response.status().as_u16(), 403);
+        assert_eq!(response.url().as_str(), "http://x/admin");
+        assert_eq!(response.headers()["server"], "nginx");
+    }
+}
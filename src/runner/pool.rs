@@ -0,0 +1,134 @@
+//! Connection reuse and response times, reported with `--signatures`
+//!
+//! The pool of reqwest can't be observed, but every new connection resolves its host first:
+//! counting the lookups gives the number of connections opened. Connections to IP addresses are not counted.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use colored::Colorize;
+use parking_lot::Mutex;
+use reqwest::dns::{Name, Resolve, Resolving};
+
+use super::dns;
+
+/// Share of the requests opening a connection above which the pool is worth tuning
+const NEW_CONNECTIONS_WARNING: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub requests: usize,
+    pub opened: usize,
+}
+
+impl Stats {
+    pub fn reused(&self) -> usize {
+        self.requests.saturating_sub(self.opened)
+    }
+}
+
+/// Requests sent by a scan and connections opened for them
+#[derive(Debug, Default)]
+pub struct Pool {
+    requests: AtomicUsize,
+    /// Shared with the resolvers of the clients, see [`Pool::counting`]
+    opened: Arc<AtomicUsize>,
+    /// Time until the headers of every response, in milliseconds, whatever the filters say
+    times: Mutex<Vec<usize>>,
+}
+
+impl Pool {
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_time(&self, ms: usize) {
+        self.times.lock().push(ms);
+    }
+
+    pub fn times(&self) -> Vec<usize> {
+        self.times.lock().clone()
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            requests: self.requests.load(Ordering::Relaxed),
+            opened: self.opened.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wrap the resolver of a client to count the connections it opens
+    pub fn counting(&self, inner: dns::Resolver) -> Counting {
+        Counting {
+            inner,
+            opened: self.opened.clone(),
+        }
+    }
+}
+
+/// Line printed at the end of the scan, `None` if nothing was sent
+pub fn summary(stats: Stats) -> Option<String> {
+    if stats.requests == 0 {
+        return None;
+    }
+    let mut out = format!(
+        "Connections: {} opened, {} reused for {} requests",
+        stats.opened.to_string().bold(),
+        stats.reused().to_string().bold(),
+        stats.requests.to_string().bold()
+    );
+    if stats.opened * 100 > stats.requests * NEW_CONNECTIONS_WARNING {
+        out.push_str(
+            &" (most requests opened a new connection, check that the server keeps them alive or lower --threads)"
+                .dimmed()
+                .to_string(),
+        );
+    }
+    Some(out)
+}
+
+/// Resolver counting the new connections, wrapping the one used by the client
+#[derive(Debug, Clone)]
+pub struct Counting {
+    inner: dns::Resolver,
+    opened: Arc<AtomicUsize>,
+}
+
+impl Resolve for Counting {
+    fn resolve(&self, name: Name) -> Resolving {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        self.inner.resolve(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(
+            summary(Stats {
+                requests: 0,
+                opened: 0
+            }),
+            None
+        );
+        assert_eq!(
+            summary(Stats {
+                requests: 100,
+                opened: 4
+            })
+            .unwrap(),
+            "Connections: 4 opened, 96 reused for 100 requests"
+        );
+        assert!(summary(Stats {
+            requests: 100,
+            opened: 80
+        })
+        .unwrap()
+        .contains("most requests opened a new connection"));
+    }
+}
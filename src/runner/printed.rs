@@ -0,0 +1,33 @@
+//! Cap on the discoveries printed to the terminal (`--max-print`), the tree and the outputs still get all of them
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::cli::opts::Opts;
+
+/// Discoveries counted by a scan
+#[derive(Debug, Default)]
+pub struct Printed {
+    printed: AtomicUsize,
+    limit: Option<usize>,
+}
+
+impl Printed {
+    pub fn new(opts: &Opts) -> Self {
+        Self {
+            printed: AtomicUsize::new(0),
+            limit: opts.max_print,
+        }
+    }
+
+    /// Count a discovery, returns whether it can still be printed
+    pub fn allow(&self) -> bool {
+        self.printed.fetch_add(1, Ordering::Relaxed) < self.limit.unwrap_or(usize::MAX)
+    }
+
+    /// Number of discoveries that were not printed
+    pub fn hidden(&self) -> usize {
+        self.printed
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.limit.unwrap_or(usize::MAX))
+    }
+}
@@ -0,0 +1,187 @@
+//! Rotation across the proxies of `--proxy-file`, one client per proxy
+//!
+//! A proxy failing too many requests in a row is skipped for the rest of the scan, unless all of them are.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use color_eyre::eyre::{bail, Context, Result};
+use colored::Colorize;
+use tabled::{builder::Builder, settings::Style};
+
+use crate::cli::opts::Opts;
+
+use super::{dns::Resolver, pool::Pool};
+
+/// Failed requests in a row after which a proxy is considered dead
+const MAX_FAILURES: usize = 5;
+
+#[derive(Debug)]
+pub struct Proxy {
+    pub url: String,
+    pub client: reqwest::Client,
+    successes: AtomicUsize,
+    errors: AtomicUsize,
+    /// Failures since the last success
+    failures: AtomicUsize,
+}
+
+impl Proxy {
+    fn new(url: String, client: reqwest::Client) -> Self {
+        Self {
+            url,
+            client,
+            successes: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    fn alive(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) < MAX_FAILURES
+    }
+
+    /// Record the outcome of a request sent through this proxy
+    pub fn record(&self, ok: bool) {
+        if ok {
+            self.successes.fetch_add(1, Ordering::Relaxed);
+            self.failures.store(0, Ordering::Relaxed);
+        } else {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            if self.failures.fetch_add(1, Ordering::Relaxed) + 1 == MAX_FAILURES {
+                log::warn!(
+                    "Proxy {} failed {} requests in a row, skipping it",
+                    self.url.bold(),
+                    MAX_FAILURES
+                );
+            }
+        }
+    }
+}
+
+/// Proxy URLs of a `--proxy-file`, one per line, blank lines and comments are skipped
+pub fn parse(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Proxies of `--proxy-file` used by a scan
+#[derive(Debug, Default)]
+pub struct Proxies {
+    proxies: Vec<Proxy>,
+    next: AtomicUsize,
+}
+
+impl Proxies {
+    /// Build a client for each proxy of `--proxy-file`, the other options are shared
+    pub fn new(opts: &Opts, resolver: &Resolver, pool: &Pool) -> Result<Self> {
+        let mut proxies = Vec::new();
+        if let Some(file) = &opts.proxy_file {
+            let content = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read proxy file: {}", file.bold().red()))?;
+            for url in parse(&content) {
+                let client = super::client::build(
+                    &Opts {
+                        proxy: Some(url.clone()),
+                        proxy_file: None,
+                        ..opts.clone()
+                    },
+                    resolver,
+                    pool,
+                )
+                .with_context(|| format!("Invalid proxy: {}", url.bold().red()))?;
+                proxies.push(Proxy::new(url, client));
+            }
+            if proxies.is_empty() {
+                bail!("No proxy found in {}", file.bold());
+            }
+        }
+        Ok(Self {
+            proxies,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Proxy for the next request, in turn or at random with `--rotate-proxies random`
+    pub fn pick(&self, opts: &Opts) -> Option<&Proxy> {
+        let alive = self
+            .proxies
+            .iter()
+            .filter(|proxy| proxy.alive())
+            .collect::<Vec<_>>();
+        // Better to keep trying than to send the requests without a proxy
+        let candidates = if alive.is_empty() {
+            self.proxies.iter().collect()
+        } else {
+            alive
+        };
+        if candidates.is_empty() {
+            return None;
+        }
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        let index = match opts.rotate_proxies.as_deref() {
+            Some("random") => RandomState::new().hash_one(next) as usize,
+            _ => next,
+        } % candidates.len();
+        Some(candidates[index])
+    }
+
+    /// Requests that went through each proxy and how many of them failed, shown with `--signatures`
+    pub fn summary(&self) -> Option<String> {
+        if self.proxies.is_empty() {
+            return None;
+        }
+        let mut builder = Builder::default();
+        builder.push_record(vec!["Proxy", "Successes", "Errors"]);
+        for proxy in &self.proxies {
+            let url = if proxy.alive() {
+                proxy.url.clone()
+            } else {
+                format!("{} {}", proxy.url, "(dead)".dimmed())
+            };
+            builder.push_record(vec![
+                url,
+                proxy.successes.load(Ordering::Relaxed).to_string(),
+                proxy.errors.load(Ordering::Relaxed).to_string(),
+            ]);
+        }
+        Some(builder.build().with(Style::modern_rounded()).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("# Pool A\nhttp://10.0.0.1:8080\n\n  https://10.0.0.2:3128  \n"),
+            vec!["http://10.0.0.1:8080", "https://10.0.0.2:3128"]
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let proxy = Proxy::new("http://10.0.0.1:8080".to_string(), reqwest::Client::new());
+        for _ in 0..MAX_FAILURES - 1 {
+            proxy.record(false);
+        }
+        assert!(proxy.alive());
+        // A success in between resets the count
+        proxy.record(true);
+        for _ in 0..MAX_FAILURES {
+            proxy.record(false);
+        }
+        assert!(!proxy.alive());
+        assert_eq!(proxy.errors.load(Ordering::Relaxed), MAX_FAILURES * 2 - 1);
+        assert_eq!(proxy.successes.load(Ordering::Relaxed), 1);
+    }
+}
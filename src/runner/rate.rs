@@ -0,0 +1,88 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// Period over which the achieved rate is averaged
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// Rolling requests/sec, compared to the rate asked for with `--throttle`
+#[derive(Clone, Debug)]
+pub struct RateMeter {
+    completions: Arc<Mutex<VecDeque<Instant>>>,
+    target: usize,
+}
+
+impl RateMeter {
+    /// `throttle` is per thread, so the target rate is `throttle * threads`
+    ///
+    /// Returns `None` when there is no throttle to compare to
+    pub fn new(throttle: Option<usize>, threads: usize) -> Option<Self> {
+        let throttle = throttle.filter(|t| *t > 0)?;
+        Some(Self {
+            completions: Arc::new(Mutex::new(VecDeque::new())),
+            target: throttle * threads.max(1),
+        })
+    }
+
+    /// Record a completed request and describe the achieved rate, e.g. `42.0/50 req/s (84%)`
+    pub fn record(&self) -> String {
+        self.record_at(Instant::now())
+    }
+
+    fn record_at(&self, now: Instant) -> String {
+        let mut completions = self.completions.lock();
+        completions.push_back(now);
+        while completions
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > WINDOW)
+        {
+            completions.pop_front();
+        }
+        // Only the part of the window that has elapsed counts, so the rate is right from the start
+        let elapsed = completions
+            .front()
+            .map_or(0.0, |first| now.duration_since(*first).as_secs_f64());
+        let rate = if elapsed > 0.0 {
+            (completions.len() - 1) as f64 / elapsed
+        } else {
+            0.0
+        };
+        format!(
+            "{:.1}/{} req/s ({:.0}%)",
+            rate,
+            self.target,
+            rate / self.target as f64 * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_meter() {
+        assert!(RateMeter::new(None, 10).is_none());
+        assert!(RateMeter::new(Some(0), 10).is_none());
+
+        let meter = RateMeter::new(Some(5), 2).unwrap();
+        let start = Instant::now();
+        assert_eq!(meter.record_at(start), "0.0/10 req/s (0%)");
+        for i in 1..=10 {
+            meter.record_at(start + Duration::from_millis(200 * i));
+        }
+        assert_eq!(
+            meter.record_at(start + Duration::from_millis(2200)),
+            "5.0/10 req/s (50%)"
+        );
+        // Completions older than the window are forgotten
+        assert_eq!(
+            meter.record_at(start + Duration::from_secs(20)),
+            "0.0/10 req/s (0%)"
+        );
+    }
+}
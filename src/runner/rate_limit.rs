@@ -0,0 +1,133 @@
+//! Advisory throttle when the server starts answering with `429 Too Many Requests`
+//!
+//! Nothing is slowed down, `--target-rps` does that, the rate of the other responses is only used for a suggestion.
+
+use std::{collections::VecDeque, time::Instant};
+
+use colored::Colorize;
+use parking_lot::Mutex;
+
+use crate::cli::opts::Opts;
+
+/// Number of outcomes and response times kept
+const WINDOW: usize = 50;
+/// Rate limited responses in the window above which the suggestion is printed
+const THRESHOLD: usize = 10;
+/// Part of the observed rate suggested, to stay under the limit
+const MARGIN: f64 = 0.8;
+
+#[derive(Debug, Default)]
+struct Detector {
+    /// Ring buffer of the last outcomes, `true` for a 429
+    outcomes: VecDeque<bool>,
+    limited: usize,
+    /// When the last responses that weren't rate limited came
+    times: VecDeque<Instant>,
+    advised: bool,
+}
+
+impl Detector {
+    /// Record a response, returns the rate of the other responses (req/s) the first time too many are rate limited
+    fn record(&mut self, limited: bool, now: Instant) -> Option<f64> {
+        if self.outcomes.len() == WINDOW && self.outcomes.pop_front() == Some(true) {
+            self.limited -= 1;
+        }
+        self.outcomes.push_back(limited);
+        if limited {
+            self.limited += 1;
+        } else {
+            if self.times.len() == WINDOW {
+                self.times.pop_front();
+            }
+            self.times.push_back(now);
+        }
+        if self.advised || self.limited < THRESHOLD {
+            return None;
+        }
+        self.advised = true;
+        let (first, last) = (self.times.front()?, self.times.back()?);
+        let elapsed = last.duration_since(*first).as_secs_f64();
+        (elapsed > 0.0).then(|| (self.times.len() - 1) as f64 / elapsed)
+    }
+}
+
+/// Rate limited responses of a scan
+#[derive(Debug, Default)]
+pub struct RateLimit {
+    /// Disabled when `--target-rps` already adapts the rate
+    detector: Option<Mutex<Detector>>,
+    /// Threads of the scan, for the suggested throttle
+    threads: usize,
+}
+
+impl RateLimit {
+    pub fn new(opts: &Opts, threads: usize) -> Self {
+        Self {
+            detector: opts.target_rps.is_none().then(Mutex::default),
+            threads,
+        }
+    }
+
+    /// Record whether a response was rate limited, printing a suggested throttle once when they pile up
+    pub fn record(&self, limited: bool) {
+        let rate = self
+            .detector
+            .as_ref()
+            .and_then(|detector| detector.lock().record(limited, Instant::now()));
+        if let Some(rate) = rate {
+            log::warn!(
+                "The server is rate limiting, {} of the last {} responses were {}, try {} (about {:.1} req/s got through)",
+                format!("{}+", THRESHOLD),
+                WINDOW,
+                "429".bold(),
+                suggestion(rate, self.threads).bold(),
+                rate
+            );
+        }
+    }
+}
+
+/// Options staying under the rate, `--throttle` is per thread so fewer threads are needed for slow servers
+fn suggestion(rate: f64, threads: usize) -> String {
+    let rate = rate * MARGIN;
+    let threads = threads.max(1);
+    let throttle = (rate / threads as f64).floor() as usize;
+    if throttle >= 1 {
+        format!("--throttle {}", throttle)
+    } else {
+        format!("--threads {} --throttle 1", (rate.floor() as usize).max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_detector() {
+        let start = Instant::now();
+        let mut detector = Detector::default();
+        // 10 responses per second until the server starts limiting
+        for i in 0..20 {
+            let now = start + Duration::from_millis(i * 100);
+            assert_eq!(detector.record(false, now), None);
+        }
+        let now = start + Duration::from_secs(2);
+        for _ in 0..THRESHOLD - 1 {
+            assert_eq!(detector.record(true, now), None);
+        }
+        let rate = detector.record(true, now).unwrap();
+        assert!((rate - 10.0).abs() < 0.01);
+        // Only advised once
+        assert_eq!(detector.record(true, now), None);
+    }
+
+    #[test]
+    fn test_suggestion() {
+        assert_eq!(suggestion(100.0, 10), "--throttle 8");
+        assert_eq!(suggestion(5.0, 10), "--threads 4 --throttle 1");
+        assert_eq!(suggestion(0.5, 10), "--threads 1 --throttle 1");
+    }
+}
@@ -1,9 +1,12 @@
 use colored::Colorize;
-use indicatif::MultiProgress;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
 use serde_json::json;
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
     time::{Duration, Instant},
 };
 use tokio::task::JoinHandle;
@@ -12,174 +15,365 @@ use color_eyre::eyre::{eyre, Result};
 use parking_lot::Mutex;
 
 use crate::{
-    cli::opts::Opts,
+    cli::{opts::Opts, tui::Finding},
     utils::{
-        constants::{DEFAULT_DEPTH, ERROR, PROGRESS_CHARS, PROGRESS_TEMPLATE, SUCCESS, WARNING},
+        constants::{DEFAULT_DEPTH, PROGRESS_CHARS, PROGRESS_TEMPLATE},
+        join_path,
         scripting::{run_scripts, ScriptingResponse},
-        tree::{Tree, TreeData, TreeNode, UrlType},
+        theme, title,
+        tree::{TreeData, TreeNode, UrlType},
+    },
+};
+
+use super::{
+    context::ScanContext,
+    filters::{
+        fingerprint::Fingerprint,
+        similarity::similarity,
+        utils::{check_previous, is_directory, is_slash_redirect},
+        Addition,
     },
+    rate::RateMeter,
 };
 
-use super::filters::utils::is_directory;
+/// Chunks of a wordlist, by the depth it is used at
+type DepthWordlists = HashMap<usize, Arc<Vec<Vec<(String, usize)>>>>;
 
 pub struct Recursive {
     opts: Opts,
+    ctx: Arc<ScanContext>,
     depth: Arc<Mutex<usize>>,
-    tree: Arc<Mutex<Tree<TreeData>>>,
     current_indexes: Arc<Mutex<HashMap<String, Vec<usize>>>>,
-    chunks: Arc<Vec<Vec<String>>>,
+    chunks: Arc<Vec<Vec<(String, usize)>>>,
+    /// Chunks of the depths with a wordlist of their own (`--depth-wordlist`)
+    depth_chunks: DepthWordlists,
+    /// Words tested before resuming, for each URL (see `utils::tree::from_save`)
+    tested_words: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    /// Bodies of the directories to compare their children with, and the children too similar to recurse into (`--skip-similar-children`)
+    bodies: Arc<Mutex<HashMap<String, String>>>,
+    similar_children: Arc<Mutex<HashSet<String>>>,
+}
+
+/// State shared by the chunks of a node
+struct Shared {
+    opts: Opts,
+    ctx: Arc<ScanContext>,
+    client: reqwest::Client,
+    progress: ProgressBar,
+    root_progress: MultiProgress,
+    depth: Arc<Mutex<usize>>,
+    previous_node: Arc<Mutex<TreeNode<TreeData>>>,
+    indexes: Arc<Mutex<HashMap<String, Vec<usize>>>>,
+    engine: Arc<rhai::Engine>,
+    unconfirmed: Arc<AtomicUsize>,
+    first_match: Arc<OnceLock<String>>,
+    rate: Option<RateMeter>,
+    tested_words: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    bodies: Arc<Mutex<HashMap<String, String>>>,
+    similar_children: Arc<Mutex<HashSet<String>>>,
+    root_fingerprint: Option<Fingerprint>,
 }
 
 impl super::Runner for Recursive {
     async fn run(self) -> Result<()> {
-        while *self.depth.lock() < self.opts.depth.unwrap_or(DEFAULT_DEPTH) {
-            let previous_nodes = self.tree.lock().get_nodes_at_depth(*self.depth.lock());
-
-            let mut handles = Vec::new();
-            let mut progresses = HashMap::new();
-            let depth = self.depth.clone();
-            let root_progress = MultiProgress::new();
-            // Create a progress bar for each previous node
-            for previous_node in &previous_nodes {
-                let root_progress = root_progress.clone();
-                if previous_node.lock().data.url_type != UrlType::Directory
-                    && !self.opts.force_recursion
-                {
-                    log::debug!("Skipping not-directory {}", previous_node.lock().data.url);
+        let unconfirmed = Arc::new(AtomicUsize::new(0));
+        let first_match = Arc::new(OnceLock::new());
+        let max_depth = self.opts.depth.unwrap_or(DEFAULT_DEPTH);
+        if self.opts.skip_similar_children.is_some() {
+            self.fetch_root_body().await;
+        }
+        if self.opts.depth_first {
+            // Explore each branch down to the max depth before moving on to its siblings
+            // Scanned nodes are skipped thanks to their saved indexes, so a resumed scan starts over from the root
+            let mut stack = self.ctx.tree.lock().get_nodes_at_depth(0);
+            while let Some(node) = stack.pop().filter(|_| first_match.get().is_none()) {
+                let node_depth = node.lock().data.depth;
+                if node_depth >= max_depth {
                     continue;
                 }
-                let depth = depth.clone();
+                *self.depth.lock() = node_depth;
+                self.scan(vec![node.clone()], &unconfirmed, &first_match)
+                    .await?;
+                stack.extend(node.lock().children.iter().rev().cloned());
+            }
+        } else {
+            while *self.depth.lock() < max_depth && first_match.get().is_none() {
+                let previous_nodes = self
+                    .ctx
+                    .tree
+                    .lock()
+                    .get_nodes_at_depth(*self.depth.lock())
+                    .into_iter()
+                    .filter(|node| self.scannable(node))
+                    .collect::<Vec<_>>();
+                match self.opts.max_nodes_per_depth {
+                    // Too many progress bars and tasks at once overwhelm both the terminal and the target
+                    Some(max) if previous_nodes.len() > max => {
+                        let stop = self.opts.max_nodes_action.as_deref() == Some("stop");
+                        log::warn!(
+                            "{} directories at depth {}, more than {}: {}",
+                            previous_nodes.len().to_string().bold(),
+                            *self.depth.lock(),
+                            max.to_string().bold(),
+                            if stop {
+                                "stopping the recursion".to_string()
+                            } else {
+                                format!("scanning them {} at a time", max)
+                            }
+                        );
+                        if stop {
+                            break;
+                        }
+                        for batch in previous_nodes.chunks(max.max(1)) {
+                            if first_match.get().is_some() {
+                                break;
+                            }
+                            self.scan(batch.to_vec(), &unconfirmed, &first_match)
+                                .await?;
+                        }
+                    }
+                    _ => {
+                        self.scan(previous_nodes, &unconfirmed, &first_match)
+                            .await?
+                    }
+                }
+
+                // Go to the next depth (/a/b/c -> /a/b/c/d)
+                *self.depth.lock() += 1;
+            }
+        }
+        if self.opts.confirm && !self.opts.quiet {
+            log::info!(
+                "{} candidates failed confirmation",
+                unconfirmed.load(Ordering::Relaxed).to_string().bold()
+            );
+        }
+        if let Some(url) = first_match.get().filter(|_| !self.opts.quiet) {
+            log::info!("Stopped at the first match: {}", url.bold());
+        }
+        Ok(())
+    }
+}
+
+impl Recursive {
+    /// Scan the given nodes in parallel, each of them with all the chunks of words
+    async fn scan(
+        &self,
+        previous_nodes: Vec<Arc<Mutex<TreeNode<TreeData>>>>,
+        unconfirmed: &Arc<AtomicUsize>,
+        first_match: &Arc<OnceLock<String>>,
+    ) -> Result<()> {
+        let mut handles = Vec::new();
+        let mut progresses = HashMap::new();
+        let depth = self.depth.clone();
+        // The children of these nodes are one level deeper
+        let chunks = self
+            .depth_chunks
+            .get(&(*depth.lock() + 1))
+            .unwrap_or(&self.chunks)
+            .clone();
+        let root_progress = MultiProgress::new();
+        let root_fingerprint = Fingerprint::from_root(&self.opts, &self.ctx.tree);
+        // The live table replaces the progress bars and the scrolling output
+        if self.ctx.hides_progress() {
+            root_progress.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        // Create a progress bar for each previous node
+        for previous_node in &previous_nodes {
+            let root_progress = root_progress.clone();
+            if !self.scannable(previous_node) {
+                continue;
+            }
+            let depth = depth.clone();
+            // Not held across the warm-up below, the future must stay `Send`
+            let position = {
                 let mut indexes = self.current_indexes.lock();
                 let index = indexes
                     .entry(previous_node.lock().data.url.clone())
-                    .or_insert_with(|| vec![0; self.chunks.len()]);
-                let pb = root_progress
-                    .add(indicatif::ProgressBar::new(
-                        (self.chunks.iter().map(|chunk| chunk.len()).sum::<usize>()) as u64,
-                    ))
-                    .with_style(
-                        indicatif::ProgressStyle::default_bar()
-                            .template(PROGRESS_TEMPLATE)?
-                            .progress_chars(PROGRESS_CHARS),
-                    )
-                    .with_message(format!(
-                        "/{}",
-                        previous_node.lock().data.path.trim_start_matches('/')
-                    ))
-                    .with_prefix(format!("d={}", *depth.lock()))
-                    .with_position(index.iter().sum::<usize>() as u64);
-                pb.enable_steady_tick(Duration::from_millis(100));
+                    .or_insert_with(|| vec![0; chunks.len()]);
+                index.iter().sum::<usize>() as u64
+            };
+            let pb = root_progress
+                .add(indicatif::ProgressBar::new(
+                    (chunks.iter().map(|chunk| chunk.len()).sum::<usize>()) as u64,
+                ))
+                .with_style(
+                    indicatif::ProgressStyle::default_bar()
+                        .template(PROGRESS_TEMPLATE)?
+                        .progress_chars(PROGRESS_CHARS),
+                )
+                .with_message(format!(
+                    "/{}",
+                    previous_node.lock().data.path.trim_start_matches('/')
+                ))
+                .with_prefix(format!("d={}", *depth.lock()))
+                .with_position(position);
+            pb.enable_steady_tick(Duration::from_millis(100));
+            title::track(&pb);
 
-                progresses.insert(previous_node.lock().data.url.clone(), pb);
+            progresses.insert(previous_node.lock().data.url.clone(), pb);
 
-                let progress = progresses
-                    .get(&previous_node.lock().data.url)
-                    .ok_or(eyre!("Failed to get progress bar"))?
-                    .clone();
+            let progress = progresses
+                .get(&previous_node.lock().data.url)
+                .ok_or(eyre!("Failed to get progress bar"))?
+                .clone();
 
-                let client = super::client::build(&self.opts)?;
-                let mut engine = rhai::Engine::new();
-                engine.build_type::<ScriptingResponse>();
-                let engine_opts = self.opts.clone();
-                let engine_progress = progress.clone();
-                engine.on_print(move |s| {
-                    if !engine_opts.quiet {
-                        engine_progress.println(s);
-                    }
-                });
-                let engine = Arc::new(engine);
-                for (i, chunk) in self.chunks.iter().enumerate() {
-                    let tree = self.tree.clone();
-                    let previous_node = previous_node.clone();
-                    let chunk = chunk.clone();
-                    let client = client.clone();
-                    let progress = progress.clone();
-                    let indexes = self.current_indexes.clone();
-                    let opts = self.opts.clone();
-                    let depth = depth.clone();
-                    let root_progress = root_progress.clone();
-                    let engine = engine.clone();
-                    let chunk_handle: JoinHandle<Result<()>> = tokio::spawn(async move {
-                        let previous_node = previous_node.clone();
-                        Self::process_chunk(
-                            chunk,
-                            client,
-                            progress,
-                            root_progress.clone(),
-                            tree,
-                            opts,
-                            depth,
-                            previous_node.clone(),
-                            indexes,
-                            engine,
-                            i,
-                        )
-                        .await
-                    });
-                    handles.push(chunk_handle);
+            let client = super::client::build(
+                &self.opts,
+                &self.ctx.session.resolver,
+                &self.ctx.session.pool,
+            )?;
+            // Each node has its own client, hence its own connections to warm up
+            let url = previous_node.lock().data.url.clone();
+            super::client::warmup(&self.opts, &self.ctx.session, &client, &url).await;
+            let mut engine = rhai::Engine::new();
+            engine.build_type::<ScriptingResponse>();
+            let engine_opts = self.opts.clone();
+            let engine_progress = progress.clone();
+            engine.on_print(move |s| {
+                if !engine_opts.quiet {
+                    engine_progress.println(s);
                 }
+            });
+            let shared = Arc::new(Shared {
+                opts: self.opts.clone(),
+                ctx: self.ctx.clone(),
+                client,
+                progress,
+                root_progress,
+                depth,
+                previous_node: previous_node.clone(),
+                indexes: self.current_indexes.clone(),
+                engine: Arc::new(engine),
+                unconfirmed: unconfirmed.clone(),
+                first_match: first_match.clone(),
+                rate: RateMeter::new(self.opts.throttle, chunks.len()),
+                tested_words: self.tested_words.clone(),
+                bodies: self.bodies.clone(),
+                similar_children: self.similar_children.clone(),
+                root_fingerprint: root_fingerprint.clone(),
+            });
+            for (i, chunk) in chunks.iter().enumerate() {
+                let chunk_handle: JoinHandle<Result<()>> =
+                    tokio::spawn(Self::process_chunk(chunk.clone(), i, shared.clone()));
+                handles.push(chunk_handle);
             }
+        }
 
-            for handle in handles {
-                let res = handle
-                    .await
-                    .map_err(|err| eyre!("Failed to receive result from worker thread: {}", err))?;
-                if res.is_err() {
-                    return Err(res.err().unwrap());
-                }
-            }
+        self.ctx.workers.join(&self.opts, handles).await?;
 
-            // Go to the next depth (/a/b/c -> /a/b/c/d)
-            *depth.lock() += 1;
-        }
         Ok(())
     }
-}
 
-impl Recursive {
+    /// Check if the children of a node should be scanned
+    fn scannable(&self, node: &Arc<Mutex<TreeNode<TreeData>>>) -> bool {
+        let url = node.lock().data.url.clone();
+        if node.lock().data.url_type != UrlType::Directory && !self.opts.force_recursion {
+            log::debug!("Skipping not-directory {}", url);
+            return false;
+        }
+        if self.similar_children.lock().contains(&url) {
+            log::debug!("Skipping {}, similar to its parent", url);
+            return false;
+        }
+        true
+    }
+
+    /// Keep the body of the root to compare its children with
+    async fn fetch_root_body(&self) {
+        let Some(root) = self.ctx.tree.lock().root.clone() else {
+            return;
+        };
+        let url = root.lock().data.url.clone();
+        let body = match super::client::build(
+            &self.opts,
+            &self.ctx.session.resolver,
+            &self.ctx.session.pool,
+        ) {
+            Ok(client) => match client.get(&url).send().await {
+                Ok(response) => response.text().await.ok(),
+                Err(_) => None,
+            },
+            Err(_) => None,
+        };
+        if let Some(body) = body {
+            self.bodies.lock().insert(url, body);
+        }
+    }
+
     pub fn new(
         opts: Opts,
+        ctx: Arc<ScanContext>,
         depth: Arc<Mutex<usize>>,
-        tree: Arc<Mutex<Tree<TreeData>>>,
         current_indexes: Arc<Mutex<HashMap<String, Vec<usize>>>>,
-        chunks: Arc<Vec<Vec<String>>>,
+        chunks: Arc<Vec<Vec<(String, usize)>>>,
+        depth_chunks: DepthWordlists,
+        tested_words: Arc<Mutex<HashMap<String, HashSet<String>>>>,
     ) -> Self {
         Self {
             opts,
+            ctx,
             depth,
-            tree,
             current_indexes,
             chunks,
+            depth_chunks,
+            tested_words,
+            bodies: Arc::new(Mutex::new(HashMap::new())),
+            similar_children: Arc::new(Mutex::new(HashSet::new())),
         }
     }
-    #[allow(clippy::too_many_arguments)]
     async fn process_chunk(
-        chunk: Vec<String>,
-        client: reqwest::Client,
-        progress: indicatif::ProgressBar,
-        root_progress: indicatif::MultiProgress,
-        tree: Arc<Mutex<Tree<TreeData>>>,
-        opts: Opts,
-        depth: Arc<Mutex<usize>>,
-        previous_node: Arc<Mutex<TreeNode<TreeData>>>,
-        indexes: Arc<Mutex<HashMap<String, Vec<usize>>>>,
-        engine: Arc<rhai::Engine>,
+        chunk: Vec<(String, usize)>,
         i: usize,
+        shared: Arc<Shared>,
     ) -> Result<()> {
+        let Shared {
+            opts,
+            ctx,
+            client,
+            progress,
+            root_progress,
+            depth,
+            previous_node,
+            indexes,
+            engine,
+            unconfirmed,
+            first_match,
+            rate,
+            tested_words,
+            bodies,
+            similar_children,
+            root_fingerprint,
+        } = &*shared;
         while indexes
             .lock()
             .get_mut(&previous_node.lock().data.url)
             .ok_or(eyre!("Couldn't find indexes for the previous node"))?[i]
             < chunk.len()
         {
+            if first_match.get().is_some() {
+                break;
+            }
             let index = indexes
                 .lock()
                 .get_mut(&previous_node.lock().data.url)
                 .ok_or(eyre!("Couldn't find indexes for the previous node"))?[i];
 
-            let word = chunk[index].clone();
+            let (word, wordlist) = chunk[index].clone();
             let data = previous_node.lock().data.clone();
 
+            if tested_words
+                .lock()
+                .get(&data.url)
+                .is_some_and(|words| words.contains(&word))
+            {
+                indexes
+                    .lock()
+                    .get_mut(&data.url)
+                    .ok_or(eyre!("Couldn't find indexes for the previous node"))?[i] += 1;
+                progress.inc(1);
+                continue;
+            }
             let mut url = data.url.clone();
             if !opts.distributed.is_empty() {
                 let current = index % (opts.distributed.len() + 1);
@@ -195,16 +389,33 @@ impl Recursive {
                     );
                 }
             }
-            match url.ends_with('/') {
-                true => url.push_str(&word),
-                false => url.push_str(&format!("/{}", word)),
+            // Children of the root are built relative to the base path, deeper nodes already include it
+            match &opts.base_path {
+                Some(base_path) if data.depth == 0 => {
+                    url = join_path(&join_path(&url, base_path), &word);
+                }
+                _ => match url.ends_with('/') {
+                    true => url.push_str(&word),
+                    false => url.push_str(&format!("/{}", word)),
+                },
             }
 
-            let request = super::client::build_request(&opts, &url, &client)?;
+            let request = super::client::build_request(opts, &url, client)?;
 
+            let _permit = match &ctx.slow_start {
+                Some(slow_start) => slow_start.acquire().await,
+                None => None,
+            };
+            let slot = match &ctx.autopilot {
+                Some(autopilot) => autopilot.acquire().await,
+                None => None,
+            };
             let t1 = Instant::now();
 
-            let response = client.execute(request).await;
+            // The body tells whether the matches are directories to recurse into
+            let response =
+                super::client::head_then_get(opts, &ctx.session, client, request, true).await;
+            drop(slot);
 
             if let Some(throttle) = opts.throttle {
                 if throttle > 0 {
@@ -218,51 +429,150 @@ impl Recursive {
             match response {
                 Ok(mut response) => {
                     let status_code = response.status().as_u16();
-                    let mut text = String::new();
+                    // The raw bytes are kept for `--filter-bytes`
+                    let mut body = Vec::new();
                     while let Ok(chunk) = response.chunk().await {
                         if let Some(chunk) = chunk {
-                            text.push_str(&String::from_utf8_lossy(&chunk));
+                            body.extend_from_slice(&chunk);
                         } else {
                             break;
                         }
                     }
-                    let is_dir = is_directory(&opts, &response, text.clone(), &progress);
+                    super::client::Timing::finish(&mut response);
+                    // Before `--confirm` sends another request to the same host
+                    super::host_limit::release(&mut response);
+                    let text = String::from_utf8_lossy(&body).to_string();
+                    let is_dir = (opts.recurse_redirects && is_slash_redirect(&url, &response))
+                        || is_directory(opts, &response, text.clone(), progress);
 
                     let filtered = super::filters::check(
-                        &opts,
-                        &progress,
+                        opts,
+                        &ctx.session,
+                        progress,
                         &text,
+                        &body,
                         t1.elapsed().as_millis(),
                         Some(*depth.lock()),
                         &response,
-                        &engine,
+                        engine,
                     );
+                    // Suppress soft-404s serving the root page again (`--filter-root-similar`)
+                    let filtered = filtered
+                        && !root_fingerprint.as_ref().is_some_and(|fingerprint| {
+                            fingerprint.matches(&text, response.headers())
+                        });
+                    // Request the URL once more to make sure this is not a transient response
+                    let filtered = if filtered && opts.confirm {
+                        // Not held across the request, the future must stay `Send`
+                        let depth = *depth.lock();
+                        let retry = super::client::build_request(opts, &url, client)?;
+                        let confirmed = super::filters::confirm(
+                            opts,
+                            &ctx.session,
+                            retry,
+                            client,
+                            progress,
+                            Some(depth),
+                            engine,
+                        )
+                        .await?;
+                        if !confirmed {
+                            unconfirmed.fetch_add(1, Ordering::Relaxed);
+                        }
+                        confirmed
+                    } else {
+                        filtered
+                    };
+                    // Only the first match is kept with `--stop-on-match`, the other tasks stop before their next request
+                    let filtered =
+                        filtered && (!opts.stop_on_match || first_match.set(url.clone()).is_ok());
 
                     if filtered {
-                        let additions =
-                            super::filters::parse_show(&opts, &text, &response, &progress, &engine);
+                        // Keep the body around for later analysis (`--dump-dir`)
+                        if let Err(err) = super::dump::write(opts, &url, &body).await {
+                            progress.println(format!(
+                                "{} Failed to dump the body of {}: {}",
+                                theme::warning(),
+                                &url,
+                                err
+                            ));
+                        }
+                        ctx.session.har.record(
+                            opts,
+                            &url,
+                            opts.data.as_deref(),
+                            &response,
+                            &text,
+                            t1.elapsed(),
+                        );
+                        ctx.notifier.on_match(opts, &url, status_code);
+                        let mut additions =
+                            super::filters::parse_show(opts, &text, &response, progress, engine);
+                        // Templated directories mirroring their parent are reported but not recursed into
+                        let similar_to_parent = opts
+                            .skip_similar_children
+                            .filter(|_| is_dir)
+                            .and_then(|threshold| {
+                                let bodies = bodies.lock();
+                                let similarity = similarity(opts, bodies.get(&data.url)?, &text);
+                                (similarity > threshold).then_some(similarity)
+                            });
+                        if let Some(similarity) = similar_to_parent {
+                            additions.push(Addition {
+                                key: "similar to parent".to_string(),
+                                value: format!("{}%", similarity),
+                            });
+                        }
 
-                        root_progress.println(format!(
-                            "{} {} {} {}{}",
-                            if response.status().is_success() {
-                                SUCCESS.to_string().green()
-                            } else if response.status().is_redirection() {
-                                WARNING.to_string().yellow()
-                            } else {
-                                ERROR.to_string().red()
-                            },
-                            response.status().as_str().bold(),
-                            url,
-                            format!("{}ms", t1.elapsed().as_millis().to_string().bold()).dimmed(),
-                            additions.iter().fold("".to_string(), |acc, addition| {
-                                format!(
-                                    "{} | {}: {}",
-                                    acc,
-                                    addition.key.dimmed().bold(),
-                                    addition.value.dimmed()
-                                )
-                            })
-                        ))?;
+                        if let Some(note) =
+                            check_previous(opts, ctx.previous_results.as_deref(), &url, status_code)
+                        {
+                            if ctx.session.printed.allow() {
+                                root_progress.println(format!(
+                                    "{} {} {} {}{}{}",
+                                    theme::status(response.status().as_u16()),
+                                    response.status().as_str().bold(),
+                                    url,
+                                    format!("{}ms", t1.elapsed().as_millis().to_string().bold())
+                                        .dimmed(),
+                                    additions.iter().fold("".to_string(), |acc, addition| {
+                                        format!(
+                                            "{} | {}: {}",
+                                            acc,
+                                            addition.key.dimmed().bold(),
+                                            addition.value.dimmed()
+                                        )
+                                    }),
+                                    note
+                                ))?;
+                            }
+                            if let Some(findings) = &ctx.findings {
+                                let _ = findings.send(Finding {
+                                    status_code,
+                                    size: text.len(),
+                                    time: t1.elapsed().as_millis(),
+                                    url: url.clone(),
+                                });
+                            }
+                        }
+                        // Keep track of the wordlist this word comes from
+                        if opts.wordlists.len() > 1 {
+                            additions.push(Addition {
+                                key: "wordlist".to_string(),
+                                value: wordlist.to_string(),
+                            });
+                        }
+                        // Remember the size and the time of the response for the signatures summary and --slowest
+                        if opts.signatures || opts.slowest.is_some() {
+                            additions.push(Addition {
+                                key: "size".to_string(),
+                                value: text.len().to_string(),
+                            });
+                            additions.push(Addition {
+                                key: "time".to_string(),
+                                value: t1.elapsed().as_millis().to_string(),
+                            });
+                        }
                         // Check if this path is already in the tree
                         if !previous_node
                             .lock()
@@ -279,10 +589,18 @@ impl Recursive {
                                         .unwrap_or_default()
                                         .to_string()
                                 });
+                            let extra = super::filters::extra(opts, &additions, &response);
+                            if opts.skip_similar_children.is_some() && is_dir {
+                                if similar_to_parent.is_some() {
+                                    similar_children.lock().insert(url.clone());
+                                } else {
+                                    bodies.lock().insert(url.clone(), text.clone());
+                                }
+                            }
                             let scripting_response =
                                 ScriptingResponse::from_response(response, Some(text)).await;
                             run_scripts(
-                                &opts,
+                                opts,
                                 &data,
                                 Some(scripting_response.clone()),
                                 progress.clone(),
@@ -291,32 +609,31 @@ impl Recursive {
                             .map_err(|err| {
                                 eyre!("Failed to run scripts on URL {}: {}", url, err)
                             })?;
-                            tree.lock().insert(
-                                TreeData {
-                                    url: url.clone(),
-                                    depth: data.depth + 1,
-                                    path: word.clone(),
-                                    status_code,
-                                    extra: json!(additions),
-                                    url_type: if is_dir {
-                                        UrlType::Directory
-                                    } else if let Some(content_type) = maybe_content_type {
-                                        UrlType::File(content_type)
-                                    } else {
-                                        UrlType::Unknown
-                                    },
-                                    response: if opts.capture {
-                                        Some(scripting_response)
-                                    } else {
-                                        None
-                                    },
+                            let found = TreeData {
+                                url: url.clone(),
+                                depth: data.depth + 1,
+                                path: word.clone(),
+                                status_code,
+                                extra,
+                                url_type: if is_dir {
+                                    UrlType::Directory
+                                } else if let Some(content_type) = maybe_content_type {
+                                    UrlType::File(content_type)
+                                } else {
+                                    UrlType::Unknown
                                 },
-                                Some(previous_node.clone()),
-                            );
+                                response: if opts.capture {
+                                    Some(scripting_response)
+                                } else {
+                                    None
+                                },
+                            };
+                            ctx.session.webhook.send(&found);
+                            ctx.tree.lock().insert(found, Some(previous_node.clone()));
                         } else {
                             progress.println(format!(
                                 "{} {} {}",
-                                WARNING.to_string().yellow(),
+                                theme::warning(),
                                 "Already in tree".bold(),
                                 url
                             ));
@@ -324,26 +641,35 @@ impl Recursive {
                     }
                 }
                 Err(err) => {
+                    ctx.errors.record(&url, &err);
                     if opts.hit_connection_errors && err.is_connect() {
-                        root_progress.println(format!(
-                            "{} {} {} {}",
-                            SUCCESS.to_string().green(),
-                            "Connection error".bold(),
-                            url,
-                            format!("{}ms", t1.elapsed().as_millis().to_string().bold()).dimmed()
-                        ))?;
+                        if let Some(note) =
+                            check_previous(opts, ctx.previous_results.as_deref(), &url, 0)
+                        {
+                            if ctx.session.printed.allow() {
+                                root_progress.println(format!(
+                                    "{} {} {} {}{}",
+                                    theme::success(),
+                                    "Connection error".bold(),
+                                    url,
+                                    format!("{}ms", t1.elapsed().as_millis().to_string().bold())
+                                        .dimmed(),
+                                    note
+                                ))?;
+                            }
+                        }
                         if !previous_node
                             .lock()
                             .children
                             .iter()
                             .any(|child| child.lock().data.path == *word)
                         {
-                            run_scripts(&opts, &data, None, progress.clone())
+                            run_scripts(opts, &data, None, progress.clone())
                                 .await
                                 .map_err(|err| {
                                     eyre!("Failed to run scripts on URL {}: {}", url, err)
                                 })?;
-                            tree.lock().insert(
+                            ctx.tree.lock().insert(
                                 TreeData {
                                     url: url.clone(),
                                     depth: data.depth + 1,
@@ -358,14 +684,14 @@ impl Recursive {
                         } else {
                             root_progress.println(format!(
                                 "{} {} {}",
-                                WARNING.to_string().yellow(),
+                                theme::warning(),
                                 "Already in tree".bold(),
                                 url
                             ))?;
                         }
                     } else {
                         super::filters::utils::print_error(
-                            &opts,
+                            opts,
                             |msg| {
                                 root_progress.println(msg)?;
                                 Ok(())
@@ -382,6 +708,14 @@ impl Recursive {
                 .get_mut(&previous_node.lock().data.url)
                 .ok_or(eyre!("Couldn't find indexes for the previous node"))?[i] += 1;
             progress.inc(1);
+            // Show the achieved rate next to the path to tell whether the throttle or the target is the bottleneck
+            if let Some(rate) = &rate {
+                progress.set_message(format!(
+                    "/{} {}",
+                    data.path.trim_start_matches('/'),
+                    rate.record()
+                ));
+            }
         }
 
         Ok(())
@@ -0,0 +1,119 @@
+use std::borrow::Cow;
+
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, HOST, PROXY_AUTHORIZATION,
+    USER_AGENT,
+};
+
+use crate::cli::opts::Opts;
+
+/// Log target of the requests, enabled at debug level whatever `RWALK_LOG` says
+pub const REQUESTS_TARGET: &str = "rwalk::requests";
+
+/// Number of bytes of the body shown, the rest is only counted
+const MAX_BODY: usize = 1024;
+
+/// Placeholder of the secrets, in the logs and in `--print-effective-config`
+pub const REDACTED: &str = "[redacted]";
+
+/// Headers replaced with `[redacted]` unless `--log-secrets` is set
+pub const SECRET_HEADERS: [HeaderName; 4] = [
+    AUTHORIZATION,
+    PROXY_AUTHORIZATION,
+    COOKIE,
+    HeaderName::from_static("x-api-key"),
+];
+
+/// Log a request the way it is sent, with the headers the client adds to every request (`--log-requests`)
+pub fn log(opts: &Opts, request: &reqwest::Request) {
+    let mut defaults = super::client::default_headers(opts).unwrap_or_default();
+    if let Ok(user_agent) = HeaderValue::from_str(&super::client::user_agent(opts)) {
+        defaults.entry(USER_AGENT).or_insert(user_agent);
+    }
+    log::debug!(
+        target: REQUESTS_TARGET,
+        "{}",
+        format_request(request, &defaults, opts.log_secrets)
+    );
+}
+
+fn format_request(request: &reqwest::Request, defaults: &HeaderMap, secrets: bool) -> String {
+    let url = request.url();
+    let mut out = format!(
+        "{} {}{} {:?}\n",
+        request.method(),
+        url.path(),
+        url.query()
+            .map(|query| format!("?{}", query))
+            .unwrap_or_default(),
+        request.version()
+    );
+    let mut headers = request.headers().clone();
+    // Like reqwest, the default headers only fill in the ones missing from the request
+    for name in defaults.keys() {
+        if !request.headers().contains_key(name) {
+            for value in defaults.get_all(name) {
+                headers.append(name, value.clone());
+            }
+        }
+    }
+    if !headers.contains_key(HOST) {
+        if let Some(host) = url.host_str() {
+            let host = match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            };
+            out.push_str(&format!("host: {}\n", host));
+        }
+    }
+    for (name, value) in &headers {
+        let value = if !secrets && SECRET_HEADERS.contains(name) {
+            Cow::Borrowed(REDACTED)
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        out.push_str(&format!("{}: {}\n", name, value));
+    }
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        if !body.is_empty() {
+            out.push('\n');
+            out.push_str(&String::from_utf8_lossy(&body[..body.len().min(MAX_BODY)]));
+            if body.len() > MAX_BODY {
+                out.push_str(&format!("... ({} bytes)", body.len()));
+            }
+        }
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_request() {
+        let mut request = reqwest::Request::new(
+            reqwest::Method::POST,
+            "http://example.com:8080/admin?id=1".parse().unwrap(),
+        );
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+        *request.body_mut() = Some("a".repeat(MAX_BODY + 1).into());
+        let mut defaults = HeaderMap::new();
+        defaults.insert(USER_AGENT, "rwalk".parse().unwrap());
+        defaults.insert(AUTHORIZATION, "Basic ignored".parse().unwrap());
+
+        let out = format_request(&request, &defaults, false);
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("POST /admin?id=1 HTTP/1.1"));
+        assert_eq!(lines.next(), Some("host: example.com:8080"));
+        assert!(out.contains("authorization: [redacted]\n"));
+        assert!(out.contains("user-agent: rwalk\n"));
+        assert!(out.ends_with(&format!("... ({} bytes)", MAX_BODY + 1)));
+
+        let out = format_request(&request, &defaults, true);
+        assert!(out.contains("authorization: Bearer secret\n"));
+        assert!(!out.contains("Basic"));
+    }
+}
@@ -0,0 +1,100 @@
+//! Words reordered by an external command (`--scorer-cmd`), to try the most promising ones first
+//!
+//! The command reads the words on stdin, one per line, and prints them back in the order to use.
+
+use std::{collections::HashMap, process::Stdio, time::Duration};
+
+use color_eyre::eyre::{bail, eyre, Result};
+use colored::Colorize;
+use tokio::io::AsyncWriteExt;
+
+use crate::utils::shell;
+
+use super::wordlists::ParsedWordlist;
+
+/// Time given to the command for each wordlist
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Reorder each wordlist with the command, the original order is kept if it fails, times out or changes the words
+pub async fn reorder(cmd: &str, wordlists: &mut HashMap<String, ParsedWordlist>) {
+    for (key, wordlist) in wordlists.iter_mut() {
+        match run(cmd, &wordlist.words).await {
+            Ok(words) => wordlist.words = words,
+            Err(err) => log::warn!("Keeping the order of the {} wordlist: {}", key.bold(), err),
+        }
+    }
+}
+
+async fn run(cmd: &str, words: &[String]) -> Result<Vec<String>> {
+    let mut child = shell(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or(eyre!("Failed to open the stdin of {}", cmd))?;
+    let input = words
+        .iter()
+        .map(|word| format!("{}\n", word))
+        .collect::<String>();
+    // Written while the output is read, the command may start printing before reading everything
+    let write = async move {
+        let res = stdin.write_all(input.as_bytes()).await;
+        drop(stdin);
+        res
+    };
+    let (written, output) = tokio::time::timeout(TIMEOUT, async {
+        tokio::join!(write, child.wait_with_output())
+    })
+    .await
+    .map_err(|_| eyre!("{} timed out after {}s", cmd, TIMEOUT.as_secs()))?;
+    let output = output?;
+    if !output.status.success() {
+        bail!("{} exited with {}", cmd, output.status);
+    }
+    written?;
+    let reordered = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim_end_matches('\r').to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    if !same_words(words, &reordered) {
+        bail!("{} didn't print back the same words", cmd);
+    }
+    Ok(reordered)
+}
+
+/// Check that the words are only reordered, none of them added or removed
+fn same_words(original: &[String], reordered: &[String]) -> bool {
+    let mut original = original
+        .iter()
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let mut reordered = reordered.iter().collect::<Vec<_>>();
+    original.sort_unstable();
+    reordered.sort_unstable();
+    original == reordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_same_words() {
+        let original = words(&["admin", "login", "api"]);
+        assert!(same_words(&original, &words(&["api", "admin", "login"])));
+        assert!(!same_words(&original, &words(&["api", "admin"])));
+        assert!(!same_words(
+            &original,
+            &words(&["api", "admin", "login", "x"])
+        ));
+        assert!(!same_words(&original, &words(&["api", "admin", "admin"])));
+    }
+}
@@ -0,0 +1,72 @@
+use color_eyre::eyre::Result;
+use parking_lot::Mutex;
+
+use crate::cli::opts::Opts;
+
+use super::{
+    breaker::CircuitBreaker,
+    dns::Resolver,
+    filters::{baseline::Baseline, collapse::Collapse},
+    har::Har,
+    host_limit::HostLimit,
+    pool::Pool,
+    printed::Printed,
+    proxies::Proxies,
+    rate_limit::RateLimit,
+    webhook::Webhook,
+};
+
+/// State of a scan shared by its requests and the filters of their responses, passed along with the client
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Shared by the clients, with the hosts resolved so far
+    pub resolver: Resolver,
+    pub proxies: Proxies,
+    pub host_limit: HostLimit,
+    pub rate_limit: RateLimit,
+    pub breaker: CircuitBreaker,
+    /// Requests and connections, reported with `--signatures`
+    pub pool: Pool,
+    pub printed: Printed,
+    /// Matched responses, for the `.har` outputs
+    pub har: Har,
+    /// Loaded with `--baseline-file`, once the baseline of `--save-baseline` is written
+    pub baseline: Option<Baseline>,
+    /// Sizes seen so far with `--collapse-size`
+    pub collapse: Option<Mutex<Collapse>>,
+    /// Findings posted to `--webhook`
+    pub webhook: Webhook,
+}
+
+impl Session {
+    pub fn new(opts: &Opts, resolver: Resolver, threads: usize) -> Result<Self> {
+        let pool = Pool::default();
+        Ok(Self {
+            proxies: Proxies::new(opts, &resolver, &pool)?,
+            host_limit: HostLimit::new(opts),
+            rate_limit: RateLimit::new(opts, threads),
+            breaker: CircuitBreaker::new(opts),
+            resolver,
+            pool,
+            ..Self::filters(opts)
+        })
+    }
+
+    /// Only what the filters need, to replay the captures of `--passive` without sending any request
+    pub fn filters(opts: &Opts) -> Self {
+        Self {
+            printed: Printed::new(opts),
+            collapse: opts
+                .collapse_size
+                .map(|threshold| Mutex::new(Collapse::new(threshold))),
+            ..Default::default()
+        }
+    }
+
+    /// Check if a response should be dropped by `--collapse-size`, the ones dropped are counted
+    pub fn collapsed(&self, size: usize) -> bool {
+        self.collapse
+            .as_ref()
+            .is_some_and(|collapse| collapse.lock().record(size))
+    }
+}
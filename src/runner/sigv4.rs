@@ -0,0 +1,361 @@
+//! AWS Signature Version 4 (`--sigv4`), as described in [the IAM guide](https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html)
+//!
+//! Only the credentials are parsed without the `sigv4` feature, to reject `--sigv4` early
+#![cfg_attr(not(feature = "sigv4"), allow(dead_code))]
+
+use std::time::SystemTime;
+
+use itertools::Itertools;
+use url::Url;
+
+use crate::utils::utc;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+/// Streamed bodies can't be hashed up front
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Credentials given as `ACCESS_KEY:SECRET_KEY:REGION:SERVICE[:SESSION_TOKEN]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub region: String,
+    pub service: String,
+    /// Token of temporary credentials, sent as `X-Amz-Security-Token`
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split(':');
+        let credentials = Self {
+            access_key: parts.next()?.to_string(),
+            secret_key: parts.next()?.to_string(),
+            region: parts.next()?.to_string(),
+            service: parts.next()?.to_string(),
+            session_token: parts.next().map(|token| token.to_string()),
+        };
+        // Visible ASCII only, the credentials end up in the headers
+        if parts.next().is_some()
+            || [
+                &credentials.access_key,
+                &credentials.secret_key,
+                &credentials.region,
+                &credentials.service,
+            ]
+            .into_iter()
+            .chain(&credentials.session_token)
+            .any(|part| part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_graphic()))
+        {
+            return None;
+        }
+        Some(credentials)
+    }
+
+    /// `DATE/REGION/SERVICE/aws4_request`
+    fn scope(&self, date: &str) -> String {
+        format!("{}/{}/{}/aws4_request", date, self.region, self.service)
+    }
+}
+
+/// Sign a request with the `Authorization`, `X-Amz-Date`, (for S3) `X-Amz-Content-Sha256` and (for temporary credentials) `X-Amz-Security-Token` headers
+///
+/// `host` is the `Host` header sent, which is not part of the request until the client sends it.
+/// An invalid header value fails like any other invalid request, it is never sent unsigned.
+#[cfg(feature = "sigv4")]
+pub fn sign(
+    credentials: &Credentials,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+    host: &str,
+    time: SystemTime,
+) -> reqwest::Result<reqwest::Request> {
+    use reqwest::header::AUTHORIZATION;
+
+    let payload_hash = match request.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => hex(&sha256(bytes)),
+            None => UNSIGNED_PAYLOAD.to_string(),
+        },
+        None => hex(&sha256(b"")),
+    };
+    let amz_date = amz_date(time);
+    let (canonical, signed_headers) = canonical_request(
+        request.method().as_str(),
+        request.url(),
+        host,
+        &amz_date,
+        &payload_hash,
+        credentials,
+    );
+    let date = &amz_date[..8];
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM,
+        amz_date,
+        credentials.scope(date),
+        hex(&sha256(canonical.as_bytes()))
+    );
+    let key = [
+        credentials.region.as_str(),
+        credentials.service.as_str(),
+        "aws4_request",
+    ]
+    .iter()
+    .fold(
+        hmac_sha256(
+            format!("AWS4{}", credentials.secret_key).as_bytes(),
+            date.as_bytes(),
+        ),
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    );
+    let authorization = format!(
+        "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+        ALGORITHM,
+        credentials.access_key,
+        credentials.scope(date),
+        signed_headers,
+        hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+    );
+
+    let mut request = request;
+    let headers = request.headers_mut();
+    for name in [
+        "x-amz-content-sha256",
+        "x-amz-security-token",
+        "x-amz-date",
+        AUTHORIZATION.as_str(),
+    ] {
+        headers.remove(name);
+    }
+    let mut builder = reqwest::RequestBuilder::from_parts(client.clone(), request);
+    if signed_headers.contains("x-amz-content-sha256") {
+        builder = builder.header("x-amz-content-sha256", payload_hash);
+    }
+    if let Some(token) = &credentials.session_token {
+        builder = builder.header("x-amz-security-token", token);
+    }
+    builder
+        .header("x-amz-date", amz_date)
+        .header(AUTHORIZATION, authorization)
+        .build()
+}
+
+#[cfg(feature = "sigv4")]
+fn sha256(data: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data).to_vec()
+}
+
+#[cfg(feature = "sigv4")]
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    use hmac::{Hmac, Mac};
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC accepts any key size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `YYYYMMDD'T'HHMMSS'Z'`
+fn amz_date(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc(time);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// The canonical request and the names of the signed headers
+///
+/// S3 requires the payload hash header and doesn't encode the path twice, unlike the other services.
+fn canonical_request(
+    method: &str,
+    url: &Url,
+    host: &str,
+    amz_date: &str,
+    payload_hash: &str,
+    credentials: &Credentials,
+) -> (String, String) {
+    let s3 = credentials.service == "s3";
+    let path = if s3 {
+        url.path().to_string()
+    } else {
+        url.path()
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+    let mut query = url
+        .query_pairs()
+        .map(|(key, value)| (uri_encode(&key), uri_encode(&value)))
+        .collect::<Vec<_>>();
+    query.sort();
+    let query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+    // Sorted by name
+    let mut headers = vec![("host", host)];
+    if s3 {
+        headers.push(("x-amz-content-sha256", payload_hash));
+    }
+    headers.push(("x-amz-date", amz_date));
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token));
+    }
+    let signed_headers = headers.iter().map(|(name, _)| *name).join(";");
+    let headers = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .join("");
+    (
+        format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, path, query, headers, signed_headers, payload_hash
+        ),
+        signed_headers,
+    )
+}
+
+/// Percent-encode everything but the unreserved characters
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    const EMPTY_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_parse_credentials() {
+        assert_eq!(
+            Credentials::parse("AKID:se/cr+et:us-east-1:execute-api"),
+            Some(Credentials {
+                access_key: "AKID".to_string(),
+                secret_key: "se/cr+et".to_string(),
+                region: "us-east-1".to_string(),
+                service: "execute-api".to_string(),
+                session_token: None,
+            })
+        );
+        assert_eq!(
+            Credentials::parse("AKID:secret:us-east-1:s3:to/ken=")
+                .unwrap()
+                .session_token,
+            Some("to/ken=".to_string())
+        );
+        assert_eq!(Credentials::parse("AKID:secret:us-east-1"), None);
+        assert_eq!(
+            Credentials::parse("AKID:secret:us-east-1:s3:token:extra"),
+            None
+        );
+        assert_eq!(Credentials::parse("AKID::us-east-1:s3"), None);
+        assert_eq!(Credentials::parse("AKID:secret:us-east-1:s3\n"), None);
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        let credentials = |s| Credentials::parse(s).unwrap();
+        let url = Url::parse("https://example.amazonaws.com/a b/?b=2&a=1&a=0").unwrap();
+        let (canonical, signed_headers) = canonical_request(
+            "GET",
+            &url,
+            "example.amazonaws.com",
+            "20150830T123600Z",
+            EMPTY_HASH,
+            &credentials("AKID:secret:us-east-1:service"),
+        );
+        assert_eq!(signed_headers, "host;x-amz-date");
+        assert_eq!(
+            canonical,
+            format!(
+                "GET\n/a%2520b/\na=0&a=1&b=2\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\nhost;x-amz-date\n{}",
+                EMPTY_HASH
+            )
+        );
+        let (canonical, signed_headers) = canonical_request(
+            "PUT",
+            &url,
+            "example.amazonaws.com",
+            "20150830T123600Z",
+            UNSIGNED_PAYLOAD,
+            &credentials("AKID:secret:us-east-1:s3"),
+        );
+        assert_eq!(signed_headers, "host;x-amz-content-sha256;x-amz-date");
+        assert!(canonical.starts_with("PUT\n/a%20b/\n"));
+        let (canonical, signed_headers) = canonical_request(
+            "GET",
+            &url,
+            "example.amazonaws.com",
+            "20150830T123600Z",
+            EMPTY_HASH,
+            &credentials("AKID:secret:us-east-1:service:token"),
+        );
+        assert_eq!(signed_headers, "host;x-amz-date;x-amz-security-token");
+        assert!(canonical.contains("x-amz-date:20150830T123600Z\nx-amz-security-token:token\n\n"));
+    }
+
+    #[test]
+    fn test_amz_date() {
+        assert_eq!(
+            amz_date(UNIX_EPOCH + Duration::from_secs(1_440_938_160)),
+            "20150830T123600Z"
+        );
+    }
+
+    #[cfg(feature = "sigv4")]
+    #[test]
+    fn test_sign() {
+        // `get-vanilla` from the AWS test suite
+        let credentials = Credentials::parse(
+            "AKIDEXAMPLE:wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY:us-east-1:service",
+        )
+        .unwrap();
+        let request = reqwest::Request::new(
+            reqwest::Method::GET,
+            Url::parse("https://example.amazonaws.com/").unwrap(),
+        );
+        let request = sign(
+            &credentials,
+            &reqwest::Client::new(),
+            request,
+            "example.amazonaws.com",
+            UNIX_EPOCH + Duration::from_secs(1_440_938_160),
+        )
+        .unwrap();
+        assert_eq!(
+            request.headers()["authorization"],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+        // Not sent unsigned
+        let credentials = Credentials {
+            session_token: Some("to\nken".to_string()),
+            ..credentials
+        };
+        assert!(sign(
+            &credentials,
+            &reqwest::Client::new(),
+            request,
+            "example.amazonaws.com",
+            UNIX_EPOCH + Duration::from_secs(1_440_938_160),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Gradually increases the number of concurrent requests up to the configured number of threads
+#[derive(Clone, Debug)]
+pub struct SlowStart {
+    semaphore: Arc<Semaphore>,
+}
+
+impl SlowStart {
+    /// Start ramping from 1 to `concurrency` permits over `duration`
+    ///
+    /// Returns `None` if there is nothing to ramp (no duration or a concurrency of 1)
+    pub fn start(duration: Option<u64>, concurrency: usize) -> Option<Self> {
+        let duration = duration.filter(|d| *d > 0)?;
+        if concurrency <= 1 {
+            return None;
+        }
+        let semaphore = Arc::new(Semaphore::new(1));
+        let interval = Duration::from_secs(duration) / (concurrency - 1) as u32;
+        let ramp_semaphore = semaphore.clone();
+        tokio::spawn(async move {
+            for _ in 1..concurrency {
+                tokio::time::sleep(interval).await;
+                ramp_semaphore.add_permits(1);
+            }
+            log::debug!("Slow start finished, running at full concurrency");
+            // Closing the semaphore lifts the limit for everyone, including waiting tasks
+            ramp_semaphore.close();
+        });
+        Some(Self { semaphore })
+    }
+
+    /// Wait for a slot to send a request
+    ///
+    /// Returns `None` once the ramp is over, meaning the request can be sent right away
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        self.semaphore.acquire().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slow_start_noop() {
+        assert!(SlowStart::start(None, 10).is_none());
+        assert!(SlowStart::start(Some(0), 10).is_none());
+        assert!(SlowStart::start(Some(5), 1).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_slow_start_ramp() {
+        let slow_start = SlowStart::start(Some(1), 3).unwrap();
+        assert_eq!(slow_start.semaphore.available_permits(), 1);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(slow_start.acquire().await.is_none());
+    }
+}
@@ -1,36 +1,44 @@
-use super::{filters::utils::is_directory, Runner};
+use super::{
+    context::ScanContext,
+    filters::{
+        fingerprint::Fingerprint,
+        utils::{check_previous, is_directory},
+        Addition,
+    },
+    Runner,
+};
 use crate::{
-    cli::opts::Opts,
+    cli::{opts::Opts, tui::Finding},
     utils::{
-        constants::{DEFAULT_DEPTH, ERROR, PROGRESS_CHARS, PROGRESS_TEMPLATE, SUCCESS, WARNING},
+        constants::{DEFAULT_DEPTH, PROGRESS_CHARS, PROGRESS_TEMPLATE},
         extract::{Document, LinkType},
         scripting::{run_scripts, ScriptingResponse},
-        tree::{Tree, TreeData, TreeNode, UrlType},
+        theme, title,
+        tree::{TreeData, TreeNode, UrlType},
     },
 };
 use color_eyre::eyre::eyre;
 use color_eyre::eyre::{Context, Ok, Result};
 use colored::Colorize;
-use indicatif::ProgressBar;
+use indicatif::{ProgressBar, ProgressDrawTarget};
 use itertools::Itertools;
 use parking_lot::Mutex;
-use serde_json::json;
 use std::sync::Arc;
 use url::Url;
 
 pub struct Spider {
     url: String,
     opts: Opts,
-    tree: Arc<Mutex<Tree<TreeData>>>,
+    ctx: Arc<ScanContext>,
     threads: usize,
 }
 
 impl Spider {
-    pub fn new(url: String, opts: Opts, tree: Arc<Mutex<Tree<TreeData>>>, threads: usize) -> Self {
+    pub fn new(url: String, opts: Opts, ctx: Arc<ScanContext>, threads: usize) -> Self {
         Self {
             url,
             opts,
-            tree,
+            ctx,
             threads,
         }
     }
@@ -43,12 +51,20 @@ impl Runner for Spider {
         let mut current_depth = 0;
         let mut current_nodes = vec![base.clone()];
         let mut visited: Vec<TreeData> = vec![];
+        let mut unconfirmed = 0;
+        let mut first_match = None;
+        let root_fingerprint = Fingerprint::from_root(&self.opts, &self.ctx.tree);
         let max_depth = self.opts.depth.unwrap_or(DEFAULT_DEPTH + 1);
         let pb = ProgressBar::new(0).with_style(
             indicatif::ProgressStyle::default_bar()
                 .template(PROGRESS_TEMPLATE)?
                 .progress_chars(PROGRESS_CHARS),
         );
+        // The live table replaces the progress bar and the scrolling output
+        if self.ctx.hides_progress() {
+            pb.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        title::track(&pb);
         let mut engine = rhai::Engine::new();
         engine.build_type::<ScriptingResponse>();
         let engine_opts = self.opts.clone();
@@ -59,7 +75,7 @@ impl Runner for Spider {
             }
         });
         let engine = Arc::new(engine);
-        while current_depth < max_depth {
+        while current_depth < max_depth && first_match.is_none() {
             let mut next_nodes = vec![];
             if current_nodes.is_empty() {
                 break;
@@ -67,7 +83,12 @@ impl Runner for Spider {
             pb.set_length(current_nodes.len() as u64);
             pb.set_position(0);
 
-            let client = super::client::build(&self.opts)?;
+            let client = super::client::build(
+                &self.opts,
+                &self.ctx.session.resolver,
+                &self.ctx.session.pool,
+            )?;
+            super::client::warmup(&self.opts, &self.ctx.session, &client, base.as_str()).await;
             let (tx, mut rx) = tokio::sync::mpsc::channel(current_nodes.len());
             let chunk_size = if current_nodes.len() < self.threads {
                 1
@@ -86,16 +107,28 @@ impl Runner for Spider {
                 let chunk_task = chunk.clone();
                 let tx = tx.clone();
                 let opts = self.opts.clone();
+                let ctx = self.ctx.clone();
                 tokio::spawn(async move {
                     let chunk = chunk_task;
 
                     for url in chunk {
                         let req = super::client::build_request(&opts, url.as_str(), &client)?;
+                        let _permit = match &ctx.slow_start {
+                            Some(slow_start) => slow_start.acquire().await,
+                            None => None,
+                        };
+                        let slot = match &ctx.autopilot {
+                            Some(autopilot) => autopilot.acquire().await,
+                            None => None,
+                        };
                         let t1 = std::time::Instant::now();
-                        let res = client
-                            .execute(req)
+                        let mut res = super::client::execute(&opts, &ctx.session, &client, req)
                             .await
+                            .inspect_err(|err| ctx.errors.record(url.as_str(), err))
                             .context(format!("Could not fetch {}", url))?;
+                        drop(slot);
+                        // The responses wait in the channel, they can't hold the slots of `--max-conns-per-host`
+                        super::host_limit::release(&mut res);
                         // log!(pb, "Visited <b>{}</>", url);
                         tx.send((url.clone(), res, t1.elapsed()))
                             .await
@@ -115,53 +148,124 @@ impl Runner for Spider {
             while let Some((url, mut response, elapsed)) = rx.recv().await {
                 pb.inc(1);
                 let status = response.status().as_u16();
-                let mut text = String::new();
+                let mut body = Vec::new();
 
-                // Read the response body into `text`
+                // Read the response body, the raw bytes are kept for `--filter-bytes`
                 while let std::result::Result::Ok(chunk) = response.chunk().await {
                     if let Some(chunk) = chunk {
-                        text.push_str(&String::from_utf8_lossy(&chunk));
+                        body.extend_from_slice(&chunk);
                     } else {
                         break;
                     }
                 }
+                let text = String::from_utf8_lossy(&body).to_string();
                 let is_dir = is_directory(&self.opts, &response, text.clone(), &pb);
 
                 let filtered = super::filters::check(
                     &self.opts,
+                    &self.ctx.session,
                     &pb,
                     &text,
+                    &body,
                     elapsed.as_millis(),
                     Some(current_depth),
                     &response,
                     &engine,
                 );
+                // Suppress soft-404s serving the root page again (`--filter-root-similar`)
+                // The root itself is kept, otherwise there would be no links to follow
+                let filtered = filtered
+                    && (current_depth == 0
+                        || !root_fingerprint.as_ref().is_some_and(|fingerprint| {
+                            fingerprint.matches(&text, response.headers())
+                        }));
+                // Request the URL once more to make sure this is not a transient response
+                let filtered = if filtered && self.opts.confirm {
+                    let retry = super::client::build_request(&self.opts, url.as_str(), &client)?;
+                    let confirmed = super::filters::confirm(
+                        &self.opts,
+                        &self.ctx.session,
+                        retry,
+                        &client,
+                        &pb,
+                        Some(current_depth),
+                        &engine,
+                    )
+                    .await?;
+                    if !confirmed {
+                        unconfirmed += 1;
+                    }
+                    confirmed
+                } else {
+                    filtered
+                };
 
                 if filtered {
-                    let additions =
+                    // Keep the body around for later analysis (`--dump-dir`)
+                    if let Err(err) = super::dump::write(&self.opts, url.as_str(), &body).await {
+                        pb.println(format!(
+                            "{} Failed to dump the body of {}: {}",
+                            theme::warning(),
+                            url.as_str(),
+                            err
+                        ));
+                    }
+                    self.ctx.session.har.record(
+                        &self.opts,
+                        url.as_str(),
+                        self.opts.data.as_deref(),
+                        &response,
+                        &text,
+                        elapsed,
+                    );
+                    self.ctx.notifier.on_match(&self.opts, url.as_str(), status);
+                    let mut additions =
                         super::filters::parse_show(&self.opts, &text, &response, &pb, &engine);
 
-                    pb.println(format!(
-                        "{} {} {} {}{}",
-                        if response.status().is_success() {
-                            SUCCESS.to_string().green()
-                        } else if response.status().is_redirection() {
-                            WARNING.to_string().yellow()
-                        } else {
-                            ERROR.to_string().red()
-                        },
-                        response.status().as_str().bold(),
-                        url,
-                        format!("{}ms", elapsed.as_millis().to_string().bold()).dimmed(),
-                        additions.iter().fold("".to_string(), |acc, addition| {
-                            format!(
-                                "{} | {}: {}",
-                                acc,
-                                addition.key.dimmed().bold(),
-                                addition.value.dimmed()
-                            )
-                        })
-                    ));
+                    if let Some(note) = check_previous(
+                        &self.opts,
+                        self.ctx.previous_results.as_deref(),
+                        url.as_str(),
+                        status,
+                    ) {
+                        if self.ctx.session.printed.allow() {
+                            pb.println(format!(
+                                "{} {} {} {}{}{}",
+                                theme::status(response.status().as_u16()),
+                                response.status().as_str().bold(),
+                                url,
+                                format!("{}ms", elapsed.as_millis().to_string().bold()).dimmed(),
+                                additions.iter().fold("".to_string(), |acc, addition| {
+                                    format!(
+                                        "{} | {}: {}",
+                                        acc,
+                                        addition.key.dimmed().bold(),
+                                        addition.value.dimmed()
+                                    )
+                                }),
+                                note
+                            ));
+                        }
+                        if let Some(findings) = &self.ctx.findings {
+                            let _ = findings.send(Finding {
+                                status_code: status,
+                                size: text.len(),
+                                time: elapsed.as_millis(),
+                                url: url.to_string(),
+                            });
+                        }
+                    }
+                    // Remember the size and the time of the response for the signatures summary and --slowest
+                    if self.opts.signatures || self.opts.slowest.is_some() {
+                        additions.push(Addition {
+                            key: "size".to_string(),
+                            value: text.len().to_string(),
+                        });
+                        additions.push(Addition {
+                            key: "time".to_string(),
+                            value: elapsed.as_millis().to_string(),
+                        });
+                    }
                     let maybe_content_type = response.headers().get("content-type").map(|x| {
                         x.to_str()
                             .unwrap_or_default()
@@ -170,6 +274,7 @@ impl Runner for Spider {
                             .unwrap_or_default()
                             .to_string()
                     });
+                    let extra = super::filters::extra(&self.opts, &additions, &response);
                     let scripting_response =
                         ScriptingResponse::from_response(response, Some(text.clone())).await;
                     let data = TreeData {
@@ -184,7 +289,7 @@ impl Runner for Spider {
                             UrlType::Unknown
                         },
                         status_code: status,
-                        extra: json!(additions),
+                        extra,
                         response: if self.opts.capture {
                             Some(scripting_response.clone())
                         } else {
@@ -194,7 +299,15 @@ impl Runner for Spider {
                     run_scripts(&self.opts, &data, Some(scripting_response), pb.clone())
                         .await
                         .map_err(|err| eyre!("Failed to run scripts on URL {}: {}", url, err))?;
+                    if current_depth > 0 {
+                        self.ctx.session.webhook.send(&data);
+                    }
                     visited.push(data);
+                    // The root is only there to follow its links, it doesn't count as a match
+                    if self.opts.stop_on_match && current_depth > 0 {
+                        first_match = Some(url.to_string());
+                        break;
+                    }
                     let document = Document::parse(&url, &text);
 
                     let links = document
@@ -225,7 +338,17 @@ impl Runner for Spider {
 
         pb.finish_and_clear();
 
-        let mut tree = self.tree.lock();
+        if self.opts.confirm && !self.opts.quiet {
+            log::info!(
+                "{} candidates failed confirmation",
+                unconfirmed.to_string().bold()
+            );
+        }
+        if let Some(url) = first_match.filter(|_| !self.opts.quiet) {
+            log::info!("Stopped at the first match: {}", url.bold());
+        }
+
+        let mut tree = self.ctx.tree.lock();
         let root = tree.root.clone().unwrap();
 
         if self.opts.subdomains || self.opts.external {
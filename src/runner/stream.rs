@@ -0,0 +1,137 @@
+//! Wordlist read from disk or stdin in batches (`--low-memory`), only the words of the current batch are kept in memory
+
+use std::{collections::HashMap, path::Path};
+
+use color_eyre::eyre::{bail, Context, Result};
+use colored::Colorize;
+use tokio::{
+    fs::File,
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
+};
+
+use crate::{
+    cli::opts::Wordlist,
+    utils::constants::{DEFAULT_FUZZ_KEY, LOW_MEMORY_BATCH},
+};
+
+use super::wordlists::{expand_tilde, ParsedWordlist};
+
+pub struct Batches {
+    reader: Box<dyn AsyncBufRead + Unpin + Send + Sync>,
+    path: String,
+    key: String,
+    size: usize,
+    /// Unknown for stdin, the estimate is then the number of lines read so far
+    file_size: Option<u64>,
+    /// Bytes and lines read so far, to estimate the number of words
+    bytes: u64,
+    lines: usize,
+    done: bool,
+}
+
+impl Batches {
+    /// Open the only wordlist of the scan, the words are read with [`Batches::next`]
+    pub async fn open(wordlists: &[Wordlist]) -> Result<Self> {
+        let [Wordlist(path, keys)] = wordlists else {
+            bail!("{} reads a single wordlist", "--low-memory".bold());
+        };
+        if keys.len() > 1 {
+            bail!(
+                "{} can't use several keys for a wordlist",
+                "--low-memory".bold()
+            );
+        }
+        let (reader, file_size): (Box<dyn AsyncBufRead + Unpin + Send + Sync>, _) = if path == "-" {
+            (Box::new(BufReader::new(tokio::io::stdin())), None)
+        } else {
+            let file = File::open(expand_tilde(Path::new(path))?)
+                .await
+                .with_context(|| format!("Failed to open wordlist file: {}", path.bold().red()))?;
+            let file_size = file.metadata().await?.len();
+            (Box::new(BufReader::new(file)), Some(file_size))
+        };
+        Ok(Self {
+            reader,
+            path: path.clone(),
+            key: keys
+                .first()
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_FUZZ_KEY.to_string()),
+            size: LOW_MEMORY_BATCH,
+            file_size,
+            bytes: 0,
+            lines: 0,
+            done: false,
+        })
+    }
+
+    /// Read the next batch of words, `None` once the whole file was read
+    pub async fn next(&mut self) -> Result<Option<HashMap<String, ParsedWordlist>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut words = Vec::new();
+        let mut line = Vec::new();
+        while words.len() < self.size {
+            line.clear();
+            let read = self.reader.read_until(b'\n', &mut line).await?;
+            if read == 0 {
+                self.done = true;
+                break;
+            }
+            self.bytes += read as u64;
+            self.lines += 1;
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if !line.is_empty() {
+                words.push(String::from_utf8_lossy(&line).to_string());
+            }
+        }
+        if words.is_empty() && self.done {
+            return Ok(None);
+        }
+        Ok(Some(HashMap::from([(
+            self.key.clone(),
+            ParsedWordlist::new(self.path.clone(), words),
+        )])))
+    }
+
+    /// Number of lines in the file, estimated from its size and the average length of the lines read so far
+    pub fn estimate(&self) -> usize {
+        match self.file_size {
+            Some(file_size) if !self.done && self.bytes > 0 => {
+                (file_size as f64 / self.bytes as f64 * self.lines as f64).round() as usize
+            }
+            _ => self.lines,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_batches() {
+        let path = std::env::temp_dir().join(format!("rwalk-stream-{}.txt", std::process::id()));
+        std::fs::write(&path, "admin\nlogin\n\napi\nbackup\n").unwrap();
+        let mut batches = Batches::open(&[Wordlist(
+            path.to_string_lossy().to_string(),
+            vec!["W".to_string()],
+        )])
+        .await
+        .unwrap();
+        batches.size = 2;
+
+        let words =
+            |batch: Option<HashMap<String, ParsedWordlist>>| batch.unwrap()["W"].words.clone();
+        assert_eq!(words(batches.next().await.unwrap()), vec!["admin", "login"]);
+        assert_eq!(batches.estimate(), 4);
+        // Empty lines are skipped
+        assert_eq!(words(batches.next().await.unwrap()), vec!["api", "backup"]);
+        assert!(batches.next().await.unwrap().is_none());
+        assert_eq!(batches.estimate(), 5);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! Tokens expanded in the URL and the `--header` values of every request: `{{timestamp}}`, `{{uuid}}` and `{{counter}}`
+
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{bail, Result};
+use colored::Colorize;
+use reqwest::header::{HeaderName, HeaderValue};
+use url::Url;
+
+use crate::cli::opts::Opts;
+
+const TOKENS: [&str; 3] = ["timestamp", "uuid", "counter"];
+
+/// Number of requests sent, the first one gets 1
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn token(name: &str) -> String {
+    format!("{{{{{}}}}}", name)
+}
+
+/// Braces are percent-encoded in the path of the URLs
+fn encoded_token(name: &str) -> String {
+    format!("%7B%7B{}%7D%7D", name)
+}
+
+fn has_tokens(s: &str) -> bool {
+    TOKENS
+        .iter()
+        .any(|name| s.contains(&token(name)) || s.contains(&encoded_token(name)))
+}
+
+fn render(s: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(s.to_string(), |s, (name, value)| {
+        s.replace(&token(name), value)
+            .replace(&encoded_token(name), value)
+    })
+}
+
+/// Values of the tokens for a request, shared by its URL and its headers
+fn values() -> [(&'static str, String); 3] {
+    [
+        (
+            "timestamp",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                .to_string(),
+        ),
+        ("uuid", uuid::Uuid::new_v4().to_string()),
+        (
+            "counter",
+            (COUNTER.fetch_add(1, Ordering::Relaxed) + 1).to_string(),
+        ),
+    ]
+}
+
+/// Expand the tokens of a request, right before sending it
+pub fn expand(opts: &Opts, request: &mut reqwest::Request) {
+    let headers = opts
+        .headers
+        .iter()
+        .filter_map(|header| header.split_once(':'))
+        .filter(|(_, value)| has_tokens(value))
+        .collect::<Vec<_>>();
+    let url_has_tokens = has_tokens(request.url().as_str());
+    if headers.is_empty() && !url_has_tokens {
+        return;
+    }
+    let values = values();
+    if url_has_tokens {
+        if let Ok(url) = Url::parse(&render(request.url().as_str(), &values)) {
+            *request.url_mut() = url;
+        }
+    }
+    // Override the raw values sent with the default headers
+    for (key, value) in headers {
+        if let (Ok(key), Ok(value)) = (
+            HeaderName::from_str(key.trim()),
+            HeaderValue::from_str(&render(value.trim(), &values)),
+        ) {
+            request.headers_mut().insert(key, value);
+        }
+    }
+}
+
+/// Make sure no fuzz key would replace a part of a token used in the URL
+pub fn check_keys<'a>(url: &str, keys: impl IntoIterator<Item = &'a String>) -> Result<()> {
+    for key in keys {
+        if let Some(name) = TOKENS
+            .iter()
+            .find(|name| url.contains(&token(name)) && token(name).contains(key.as_str()))
+        {
+            bail!(
+                "The fuzz key {} is part of the {} token, use another key",
+                key.bold(),
+                token(name).bold()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let values = [("uuid", "id".to_string()), ("counter", "7".to_string())];
+        assert_eq!(
+            render("/a/%7B%7Buuid%7D%7D?n={{counter}}&m={{other}}", &values),
+            "/a/id?n=7&m={{other}}"
+        );
+        assert!(has_tokens("x-{{timestamp}}"));
+        assert!(!has_tokens("{timestamp}"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let opts = Opts {
+            headers: vec![
+                "X-Request-Id: {{uuid}}".to_string(),
+                "X-Static: 1".to_string(),
+            ],
+            ..Default::default()
+        };
+        let mut request = reqwest::Request::new(
+            reqwest::Method::GET,
+            Url::parse("http://localhost/{{counter}}/?t={{timestamp}}").unwrap(),
+        );
+        expand(&opts, &mut request);
+        assert!(!has_tokens(request.url().as_str()));
+        assert!(request
+            .url()
+            .path()
+            .trim_matches('/')
+            .parse::<u64>()
+            .is_ok());
+        assert_eq!(request.headers()["x-request-id"].len(), 36);
+        assert!(request.headers().get("x-static").is_none());
+    }
+
+    #[test]
+    fn test_check_keys() {
+        let keys = ["$".to_string(), "counter".to_string()];
+        assert!(check_keys("http://localhost/$?n={{timestamp}}", &keys).is_ok());
+        assert!(check_keys("http://localhost/$?n={{counter}}", &keys).is_err());
+        assert!(check_keys("http://localhost/$", &["{{".to_string()]).is_ok());
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::{
+    cli::opts::Opts,
+    utils::{constants::DEFAULT_WEBHOOK_BATCH, tree::TreeData},
+};
+
+/// Attempts to post a batch before giving up on it
+const ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled on each attempt
+const BACKOFF: Duration = Duration::from_millis(500);
+/// Incomplete batches are posted after this delay without new findings
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Findings waiting to be posted by the background task of a scan
+#[derive(Debug, Default)]
+pub struct Webhook(Mutex<Option<UnboundedSender<Value>>>);
+
+impl Webhook {
+    /// Start posting the findings to `--webhook` in the background
+    pub fn start(&self, opts: &Opts) -> color_eyre::Result<Option<JoinHandle<()>>> {
+        let Some(url) = opts.webhook.clone() else {
+            *self.0.lock() = None;
+            return Ok(None);
+        };
+        // A client of its own, so the posts don't count against the scan's connections and rate
+        let client = super::client::build_plain(opts)?;
+        let batch = opts.webhook_batch.unwrap_or(DEFAULT_WEBHOOK_BATCH).max(1);
+        let (tx, rx) = unbounded_channel();
+        *self.0.lock() = Some(tx);
+        Ok(Some(tokio::spawn(post_all(client, url, batch, rx))))
+    }
+
+    /// Queue a finding, never blocks the scan
+    pub fn send(&self, data: &TreeData) {
+        if let Some(sender) = &*self.0.lock() {
+            let _ = sender.send(json!({
+                "url": data.url,
+                "status": data.status_code,
+                "depth": data.depth,
+                "extra": data.extra,
+            }));
+        }
+    }
+
+    /// Post the remaining findings and stop the background task
+    pub async fn finish(&self, handle: Option<JoinHandle<()>>) {
+        self.0.lock().take();
+        if let Some(handle) = handle {
+            let _ = handle.await;
+        }
+    }
+}
+
+async fn post_all(
+    client: reqwest::Client,
+    url: String,
+    batch: usize,
+    mut rx: UnboundedReceiver<Value>,
+) {
+    let mut findings = Vec::with_capacity(batch);
+    loop {
+        // Flush on a full batch, after a quiet period and once the scan is over
+        let (flush, closed) = match tokio::time::timeout(FLUSH_INTERVAL, rx.recv()).await {
+            Ok(Some(finding)) => {
+                findings.push(finding);
+                (findings.len() >= batch, false)
+            }
+            Ok(None) => (true, true),
+            Err(_) => (true, false),
+        };
+        if flush && !findings.is_empty() {
+            // A single finding is posted as is, batches as an array
+            let body = if batch == 1 {
+                findings.remove(0)
+            } else {
+                Value::Array(std::mem::take(&mut findings))
+            };
+            post(&client, &url, &body).await;
+        }
+        if closed {
+            break;
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: &Value) {
+    for attempt in 0..ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(BACKOFF * 2u32.pow(attempt - 1)).await;
+        }
+        let request = client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => log::debug!("Webhook answered {}", response.status()),
+            Err(err) => log::debug!("Webhook failed: {}", err),
+        }
+    }
+    log::warn!("Could not post to the webhook after {} attempts", ATTEMPTS);
+}
@@ -1,27 +1,101 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Path, PathBuf},
 };
 
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::{bail, Context, Result};
 use colored::Colorize;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 
 use crate::{
-    cli::opts::{Opts, Wordlist},
-    utils::{check_range, constants::DEFAULT_FUZZ_KEY, parse_range_input},
+    cli::{
+        helpers::{is_remote, parse_wordlist},
+        opts::{Opts, Wordlist},
+    },
+    utils::{
+        check_range,
+        constants::{DEFAULT_FUZZ_KEY, WORDLIST_CACHE_TTL},
+        parse_range_input,
+    },
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ParsedWordlist {
     pub path: String,
     pub words: Vec<String>,
+    /// Index of the wordlist each word comes from (in the order they were passed)
+    pub origins: BTreeMap<String, usize>,
 }
 
 impl ParsedWordlist {
     pub fn new(path: String, words: Vec<String>) -> Self {
-        Self { path, words }
+        Self {
+            path,
+            words,
+            origins: BTreeMap::new(),
+        }
+    }
+
+    /// Get the index of the wordlist a word comes from
+    pub fn origin(&self, word: &str) -> usize {
+        self.origins.get(word).copied().unwrap_or_default()
+    }
+}
+
+/// Download the wordlists given as URLs to temporary files, so they can be parsed like local ones
+///
+/// Files are cached by URL for a day, running again with the same wordlist won't download it twice (unless `--refresh-wordlists`)
+pub async fn download(opts: &Opts) -> Result<Vec<Wordlist>> {
+    if !opts
+        .wordlists
+        .iter()
+        .any(|Wordlist(path, _)| is_remote(path))
+    {
+        return Ok(opts.wordlists.clone());
+    }
+    let client = crate::runner::client::build_plain(opts)?;
+    let mut out = Vec::with_capacity(opts.wordlists.len());
+    for Wordlist(path, keys) in &opts.wordlists {
+        if !is_remote(path) {
+            out.push(Wordlist(path.clone(), keys.clone()));
+            continue;
+        }
+        let cached = std::env::temp_dir().join(format!("rwalk-{:x}.txt", md5::compute(path)));
+        if opts.refresh_wordlists || !is_fresh(&cached) {
+            let response = client.get(path).send().await.with_context(|| {
+                format!(
+                    "Failed to fetch wordlist: {}",
+                    path.to_string().bold().red()
+                )
+            })?;
+            if response.status() != reqwest::StatusCode::OK {
+                bail!(
+                    "Failed to fetch wordlist: {} (status {})",
+                    path.to_string().bold().red(),
+                    response.status().as_str().bold()
+                );
+            }
+            let bytes = response.bytes().await?;
+            tokio::fs::write(&cached, &bytes).await.with_context(|| {
+                format!(
+                    "Failed to cache wordlist to {}",
+                    cached.display().to_string().bold().red()
+                )
+            })?;
+            log::debug!("Downloaded {} to {}", path, cached.display());
+        }
+        out.push(Wordlist(cached.to_string_lossy().to_string(), keys.clone()));
     }
+    Ok(out)
+}
+
+/// Check if a cached wordlist exists and is younger than [`WORDLIST_CACHE_TTL`]
+fn is_fresh(cached: &Path) -> bool {
+    std::fs::metadata(cached)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < WORDLIST_CACHE_TTL)
 }
 
 /// Parse wordlists
@@ -34,16 +108,26 @@ impl ParsedWordlist {
 ///
 /// A hashmap of parsed wordlists (key = path, value = ParsedWordlist)
 /// Where ParsedWordlist contains the path to the wordlist and the words in the wordlist
-pub async fn parse(wordlists: &Vec<Wordlist>) -> Result<HashMap<String, ParsedWordlist>> {
+pub async fn parse(wordlists: &[Wordlist]) -> Result<HashMap<String, ParsedWordlist>> {
+    if wordlists
+        .iter()
+        .filter(|Wordlist(path, _)| path == "-")
+        .count()
+        > 1
+    {
+        bail!("Only one wordlist can be read from stdin");
+    }
     let mut out: HashMap<String, ParsedWordlist> = HashMap::new();
-    for Wordlist(path, keys) in wordlists {
-        let words: String = match path.as_str() {
+    for (index, Wordlist(path, keys)) in wordlists.iter().enumerate() {
+        let words: Vec<String> = match path.as_str() {
+            // Read line by line until the pipe is closed, `--low-memory` scans it in batches instead
             "-" => {
-                let mut stdin = tokio::io::stdin();
-
-                let mut buf = String::new();
-                stdin.read_to_string(&mut buf).await?;
-                buf
+                let mut lines = BufReader::new(tokio::io::stdin()).lines();
+                let mut words = Vec::new();
+                while let Some(line) = lines.next_line().await? {
+                    words.push(line);
+                }
+                words
             }
             _ => {
                 let mut file = tokio::fs::File::open(
@@ -65,6 +149,9 @@ pub async fn parse(wordlists: &Vec<Wordlist>) -> Result<HashMap<String, ParsedWo
                 file.read_to_end(&mut bytes).await?;
 
                 unsafe { String::from_utf8_unchecked(bytes) }
+                    .split('\n')
+                    .map(str::to_string)
+                    .collect()
             }
         };
         for key in {
@@ -74,22 +161,36 @@ pub async fn parse(wordlists: &Vec<Wordlist>) -> Result<HashMap<String, ParsedWo
                 keys.clone()
             }
         } {
-            let entry = out.entry(key.clone()).or_insert(ParsedWordlist {
-                path: path.clone(),
-                words: Vec::new(),
-            });
-            entry.words.extend(
-                words
-                    .split('\n')
-                    .map(|x| x.to_string())
-                    .filter(|x| !x.is_empty()),
-            );
+            let entry = out
+                .entry(key.clone())
+                .or_insert(ParsedWordlist::new(path.clone(), Vec::new()));
+            for word in words.iter().filter(|x| !x.is_empty()) {
+                entry.origins.entry(word.to_string()).or_insert(index);
+                entry.words.push(word.to_string());
+            }
         }
     }
 
     Ok(out)
 }
 
+/// Check if no word is left to scan, which is an error unless `--allow-empty` is set
+pub fn check_empty(opts: &Opts, wordlists: &HashMap<String, ParsedWordlist>) -> Result<bool> {
+    if wordlists
+        .values()
+        .any(|wordlist| !wordlist.words.is_empty())
+    {
+        return Ok(false);
+    }
+    if !opts.allow_empty {
+        bail!(
+            "No words found in wordlists, use {} to only request the base URL",
+            "--allow-empty".bold()
+        );
+    }
+    Ok(true)
+}
+
 pub fn deduplicate(wordlists: &mut HashMap<String, ParsedWordlist>) {
     for ParsedWordlist { words, .. } in (*wordlists).values_mut() {
         words.sort_unstable();
@@ -97,6 +198,135 @@ pub fn deduplicate(wordlists: &mut HashMap<String, ParsedWordlist>) {
     }
 }
 
+/// Remove the duplicates but keep the first occurrence of each word in place, to preserve a weighted order
+pub fn deduplicate_in_order(wordlists: &mut HashMap<String, ParsedWordlist>) {
+    for ParsedWordlist { words, .. } in wordlists.values_mut() {
+        let mut seen = HashSet::new();
+        words.retain(|word| seen.insert(word.clone()));
+    }
+}
+
+/// Sort the words by descending weight and strip it (`--wordlist-weighted`)
+///
+/// Lines are formatted as `word<TAB>weight`, lines without a weight default to 0.
+/// Words with the same weight keep the order of the wordlists.
+pub fn weigh(wordlists: &mut HashMap<String, ParsedWordlist>) {
+    for wordlist in wordlists.values_mut() {
+        let mut weighted = wordlist
+            .words
+            .iter()
+            .map(|line| {
+                let (word, weight) = parse_weight(line);
+                (word.to_string(), weight, wordlist.origin(line))
+            })
+            .collect::<Vec<_>>();
+        weighted.sort_by(|(_, a, _), (_, b, _)| b.total_cmp(a));
+        wordlist.origins = BTreeMap::new();
+        wordlist.words = weighted
+            .into_iter()
+            .map(|(word, _, origin)| {
+                wordlist.origins.entry(word.clone()).or_insert(origin);
+                word
+            })
+            .collect();
+    }
+}
+
+fn parse_weight(line: &str) -> (&str, f64) {
+    line.rsplit_once('\t')
+        .and_then(|(word, weight)| Some((word, weight.trim().parse().ok()?)))
+        .unwrap_or((line, 0.0))
+}
+
+/// Whether the words are dealt one by one to the threads (`--chunk-strategy`), the default for weighted wordlists
+pub fn round_robin(opts: &Opts) -> bool {
+    match opts.chunk_strategy.as_deref() {
+        Some(strategy) => strategy == "round-robin",
+        None => opts.wordlist_weighted || opts.scorer_cmd.is_some(),
+    }
+}
+
+/// Split the words (tagged with the wordlist they come from) into a chunk for each thread
+///
+/// With `--chunk-strategy round-robin`, they are dealt one by one instead so every thread starts with the first ones
+pub fn chunks(
+    opts: &Opts,
+    wordlists: &HashMap<String, ParsedWordlist>,
+    threads: usize,
+) -> Vec<Vec<(String, usize)>> {
+    let tagged = wordlists
+        .values()
+        .flat_map(|wordlist| {
+            wordlist
+                .words
+                .iter()
+                .map(|word| (word.clone(), wordlist.origin(word)))
+        })
+        .collect::<Vec<_>>();
+    if round_robin(opts) {
+        interleave(&tagged, threads)
+    } else {
+        tagged
+            .chunks((tagged.len() / threads.max(1)).max(1))
+            .map(|chunk| chunk.to_vec())
+            .collect()
+    }
+}
+
+/// Wordlists given for a single depth with `--depth-wordlist`
+pub fn depth_wordlists(opts: &Opts) -> BTreeMap<usize, Vec<Wordlist>> {
+    let mut depths: BTreeMap<usize, Vec<Wordlist>> = BTreeMap::new();
+    for (depth, file) in opts.depth_wordlist.iter().filter_map(|s| s.split_once(':')) {
+        if let (Ok(depth), Ok(wordlist)) = (depth.parse(), parse_wordlist(file)) {
+            depths.entry(depth).or_default().push(wordlist);
+        }
+    }
+    depths
+}
+
+/// Load the wordlists of each depth, filtered and transformed like the main ones
+pub async fn load_depths(opts: &Opts) -> Result<BTreeMap<usize, HashMap<String, ParsedWordlist>>> {
+    let mut depths = BTreeMap::new();
+    for (depth, wordlists) in depth_wordlists(opts) {
+        let opts = Opts {
+            wordlists,
+            ..opts.clone()
+        };
+        let mut words = parse(&download(&opts).await?).await?;
+        if opts.wordlist_weighted {
+            weigh(&mut words);
+        }
+        prepare(&opts, &mut words)?;
+        depths.insert(depth, words);
+    }
+    Ok(depths)
+}
+
+/// Match, filter, transform and deduplicate the words, like the main wordlists
+pub fn prepare(opts: &Opts, wordlists: &mut HashMap<String, ParsedWordlist>) -> Result<()> {
+    if opts.filter_word.is_some() || opts.match_word.is_some() {
+        match_words(opts, wordlists)?;
+    }
+    filters(opts, wordlists)?;
+    transformations(opts, wordlists);
+    if opts.wordlist_weighted {
+        deduplicate_in_order(wordlists);
+    } else {
+        deduplicate(wordlists);
+    }
+    Ok(())
+}
+
+/// Split items into at most `count` chunks, dealing them one by one so each chunk starts with the first items
+pub fn interleave<T: Clone>(items: &[T], count: usize) -> Vec<Vec<T>> {
+    let count = count.min(items.len()).max(1);
+    let mut chunks = vec![Vec::with_capacity(items.len().div_ceil(count)); count];
+    for (i, item) in items.iter().enumerate() {
+        chunks[i % count].push(item.clone());
+    }
+    chunks
+}
+
 pub fn filters(opts: &Opts, wordlists: &mut HashMap<String, ParsedWordlist>) -> Result<()> {
     for filter in opts.wordlist_filter.iter().cloned() {
         let mut filter = filter;
@@ -235,7 +465,34 @@ pub fn filters(opts: &Opts, wordlists: &mut HashMap<String, ParsedWordlist>) ->
     Ok(())
 }
 
+/// Apply `--filter-word` and `--match-word` to all the wordlists
+pub fn match_words(opts: &Opts, wordlists: &mut HashMap<String, ParsedWordlist>) -> Result<()> {
+    let exclude = opts
+        .filter_word
+        .as_deref()
+        .map(regex::Regex::new)
+        .transpose()
+        .context("Invalid --filter-word regex")?;
+    let include = opts
+        .match_word
+        .as_deref()
+        .map(regex::Regex::new)
+        .transpose()
+        .context("Invalid --match-word regex")?;
+    for ParsedWordlist { words, .. } in wordlists.values_mut() {
+        words.retain(|word| {
+            !exclude.as_ref().is_some_and(|re| re.is_match(word))
+                && include.as_ref().is_none_or(|re| re.is_match(word))
+        });
+    }
+    Ok(())
+}
+
 pub fn transformations(opts: &Opts, wordlists: &mut HashMap<String, ParsedWordlist>) {
+    let before = wordlists
+        .iter()
+        .map(|(key, ParsedWordlist { words, .. })| (key.clone(), words.clone()))
+        .collect::<HashMap<_, _>>();
     for transformation in opts.transform.clone() {
         let mut transformation = transformation;
 
@@ -415,6 +672,17 @@ pub fn transformations(opts: &Opts, wordlists: &mut HashMap<String, ParsedWordli
             _ => {}
         }
     }
+    // Transformations are applied in place, so we can carry the origins over to the new words
+    for (key, wordlist) in wordlists.iter_mut() {
+        if let Some(before) = before.get(key) {
+            let origins = before
+                .iter()
+                .zip(&wordlist.words)
+                .map(|(old, new)| (new.clone(), wordlist.origin(old)))
+                .collect();
+            wordlist.origins = origins;
+        }
+    }
 }
 
 pub fn compute_checksum(wordlists: &HashMap<String, ParsedWordlist>) -> String {
@@ -426,7 +694,7 @@ pub fn compute_checksum(wordlists: &HashMap<String, ParsedWordlist>) -> String {
     format!("{:x}", md5::compute(to_compute))
 }
 
-fn expand_tilde<P: AsRef<Path>>(path_user_input: P) -> Result<PathBuf> {
+pub fn expand_tilde<P: AsRef<Path>>(path_user_input: P) -> Result<PathBuf> {
     let p = path_user_input.as_ref();
     if !p.starts_with("~") {
         return Ok(p.to_path_buf());
@@ -476,6 +744,33 @@ mod tests {
         assert_eq!(parsed.len(), 2);
         assert_eq!(parsed.get("W1").unwrap().words.len(), 7);
         assert_eq!(parsed.get("W2").unwrap().words.len(), 2);
+        let w1 = parsed.get("W1").unwrap();
+        assert_eq!(w1.origin(&w1.words[0]), 0);
+        assert_eq!(w1.origin(w1.words.last().unwrap()), 1);
+        let w2 = parsed.get("W2").unwrap();
+        assert_eq!(w2.origin(&w2.words[0]), 2);
+    }
+
+    #[tokio::test]
+    async fn test_parse_stdin_once() {
+        let wordlists = vec![
+            Wordlist("-".to_string(), vec!["W1".to_string()]),
+            Wordlist("-".to_string(), vec!["W2".to_string()]),
+        ];
+        assert!(parse(&wordlists).await.is_err());
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let path = std::env::temp_dir().join(format!("rwalk-fresh-{}.txt", std::process::id()));
+        assert!(!is_fresh(&path));
+        let file = std::fs::File::create(&path).unwrap();
+        assert!(is_fresh(&path));
+        let old =
+            std::time::SystemTime::now() - std::time::Duration::from_secs(WORDLIST_CACHE_TTL + 60);
+        file.set_modified(old).unwrap();
+        assert!(!is_fresh(&path));
+        std::fs::remove_file(path).unwrap();
     }
 
     #[test]
@@ -495,6 +790,121 @@ mod tests {
         assert_eq!(wordlists.get("FUZZ").unwrap().words.len(), 1);
     }
 
+    #[test]
+    fn test_weigh() {
+        let mut wordlists = HashMap::new();
+        let mut wordlist = ParsedWordlist::new(
+            "".to_string(),
+            vec!["a", "b\t10", "c\t-1", "d\t10", "e\tx", "b\t2"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        );
+        wordlist.origins.insert("b\t10".to_string(), 1);
+        wordlists.insert("FUZZ".to_string(), wordlist);
+        weigh(&mut wordlists);
+        deduplicate_in_order(&mut wordlists);
+        let wordlist = wordlists.get("FUZZ").unwrap();
+        assert_eq!(wordlist.words, vec!["b", "d", "a", "e\tx", "c"]);
+        assert_eq!(wordlist.origin("b"), 1);
+    }
+
+    #[test]
+    fn test_interleave() {
+        assert_eq!(
+            interleave(&[1, 2, 3, 4, 5], 2),
+            vec![vec![1, 3, 5], vec![2, 4]]
+        );
+        assert_eq!(interleave(&[1, 2], 4), vec![vec![1], vec![2]]);
+        assert_eq!(interleave::<u8>(&[], 4), vec![Vec::<u8>::new()]);
+    }
+
+    #[test]
+    fn test_depth_wordlists() {
+        let depths = depth_wordlists(&Opts {
+            depth_wordlist: vec![
+                "1:dirs.txt".to_string(),
+                "2:files.txt".to_string(),
+                "1:more.txt".to_string(),
+            ],
+            ..Default::default()
+        });
+        assert_eq!(
+            depths[&1],
+            vec![
+                Wordlist::new("dirs.txt".to_string(), vec![]),
+                Wordlist::new("more.txt".to_string(), vec![])
+            ]
+        );
+        assert_eq!(
+            depths[&2],
+            vec![Wordlist::new("files.txt".to_string(), vec![])]
+        );
+        assert!(!depths.contains_key(&3));
+    }
+
+    #[test]
+    fn test_chunks() {
+        let mut wordlists = HashMap::new();
+        wordlists.insert(
+            "$".to_string(),
+            ParsedWordlist::new(
+                "".to_string(),
+                vec!["a", "b", "c"].into_iter().map(String::from).collect(),
+            ),
+        );
+        assert_eq!(
+            chunks(&Opts::default(), &wordlists, 2),
+            vec![
+                vec![("a".to_string(), 0)],
+                vec![("b".to_string(), 0)],
+                vec![("c".to_string(), 0)]
+            ]
+        );
+        // More threads than words
+        assert_eq!(chunks(&Opts::default(), &wordlists, 8).len(), 3);
+        assert_eq!(
+            chunks(
+                &Opts {
+                    chunk_strategy: Some("round-robin".to_string()),
+                    ..Default::default()
+                },
+                &wordlists,
+                2
+            ),
+            vec![
+                vec![("a".to_string(), 0), ("c".to_string(), 0)],
+                vec![("b".to_string(), 0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_empty() {
+        let mut wordlists = HashMap::new();
+        wordlists.insert(
+            "FUZZ".to_string(),
+            ParsedWordlist::new("".to_string(), vec![]),
+        );
+        assert!(check_empty(&Opts::default(), &wordlists).is_err());
+        assert!(check_empty(
+            &Opts {
+                allow_empty: true,
+                ..Default::default()
+            },
+            &wordlists
+        )
+        .unwrap());
+        assert!(check_empty(&Opts::default(), &HashMap::new()).is_err());
+
+        wordlists
+            .get_mut("FUZZ")
+            .unwrap()
+            .words
+            .push("admin".to_string());
+        assert!(!check_empty(&Opts::default(), &wordlists).unwrap());
+    }
+
     #[test]
     fn test_filters() {
         let mut wordlists = HashMap::new();
@@ -526,6 +936,42 @@ mod tests {
         assert_eq!(wordlists.get("FUZZ").unwrap().words.len(), 1);
     }
 
+    #[test]
+    fn test_match_words() {
+        let mut wordlists = HashMap::new();
+        wordlists.insert(
+            "FUZZ".to_string(),
+            ParsedWordlist::new(
+                "".to_string(),
+                vec![
+                    "# comment".to_string(),
+                    "admin".to_string(),
+                    "api".to_string(),
+                    "login".to_string(),
+                ],
+            ),
+        );
+        match_words(
+            &Opts {
+                filter_word: Some("^#".to_string()),
+                match_word: Some("^a".to_string()),
+                ..Default::default()
+            },
+            &mut wordlists,
+        )
+        .unwrap();
+        assert_eq!(wordlists.get("FUZZ").unwrap().words, vec!["admin", "api"]);
+
+        assert!(match_words(
+            &Opts {
+                filter_word: Some("(".to_string()),
+                ..Default::default()
+            },
+            &mut wordlists,
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_transformations() {
         let mut wordlists = HashMap::new();
@@ -576,6 +1022,25 @@ mod tests {
         assert_eq!(wordlists.get("FUZZ").unwrap().words[0], "Cad");
     }
 
+    #[test]
+    fn test_transformations_keep_origins() {
+        let mut wordlist =
+            ParsedWordlist::new("".to_string(), vec!["a".to_string(), "b".to_string()]);
+        wordlist.origins.insert("a".to_string(), 0);
+        wordlist.origins.insert("b".to_string(), 1);
+        let mut wordlists = HashMap::new();
+        wordlists.insert("FUZZ".to_string(), wordlist);
+        transformations(
+            &Opts {
+                transform: vec![KeyOrKeyVal("prefix".to_string(), Some("c".to_string()))],
+                ..Default::default()
+            },
+            &mut wordlists,
+        );
+        assert_eq!(wordlists.get("FUZZ").unwrap().origin("ca"), 0);
+        assert_eq!(wordlists.get("FUZZ").unwrap().origin("cb"), 1);
+    }
+
     #[test]
     fn test_compute_checksum() {
         let mut wordlists = HashMap::new();
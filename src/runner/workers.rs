@@ -0,0 +1,65 @@
+//! Worker tasks of a scan, a failed one stops it unless `--continue-on-error`
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use color_eyre::eyre::{eyre, Result};
+use tokio::task::JoinHandle;
+
+use crate::cli::opts::Opts;
+
+/// Workers that failed with `--continue-on-error`
+#[derive(Debug, Default)]
+pub struct Workers {
+    failed: AtomicUsize,
+}
+
+impl Workers {
+    /// Wait for all the workers, the first error is returned unless `--continue-on-error`, in which case it is logged and counted
+    pub async fn join(&self, opts: &Opts, handles: Vec<JoinHandle<Result<()>>>) -> Result<()> {
+        for handle in handles {
+            let res = handle
+                .await
+                .map_err(|err| eyre!("Failed to receive result from worker thread: {}", err))
+                .and_then(|res| res);
+            match res {
+                Ok(()) => {}
+                Err(err) if opts.continue_on_error => {
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    log::error!("A worker failed, the scan goes on without it: {}", err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::bail;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_join() {
+        let handles = || {
+            vec![
+                tokio::spawn(async { Ok(()) }),
+                tokio::spawn(async { bail!("chunk failed") }),
+                tokio::spawn(async { panic!("chunk panicked") }),
+            ]
+        };
+        let workers = Workers::default();
+        assert!(workers.join(&Opts::default(), handles()).await.is_err());
+        let opts = Opts {
+            continue_on_error: true,
+            ..Default::default()
+        };
+        assert!(workers.join(&opts, handles()).await.is_ok());
+        assert_eq!(workers.failed(), 2);
+    }
+}
@@ -15,4 +15,17 @@ pub const DEFAULT_METHOD: &str = "GET";
 pub const DEFAULT_MODE: &str = "recursive";
 pub const DEFAULT_DEPTH: usize = 1;
 pub const DEFAULT_FILE_TYPE: &str = "txt";
+pub const DEFAULT_SORT_OUTPUT: &str = "by-url";
 pub const DEFAULT_CONFIG_PATH: &str = ".config/rwalk/config.toml";
+pub const DEFAULT_RECIPES_PATH: &str = ".config/rwalk/recipes";
+pub const DEFAULT_RETRIES: usize = 3;
+pub const DEFAULT_CIRCUIT_WINDOW: usize = 100;
+pub const DEFAULT_HISTOGRAM_BUCKETS: usize = 10;
+/// Exit code when the scan is aborted by `--circuit-breaker`
+pub const CIRCUIT_BREAKER_EXIT_CODE: i32 = 3;
+/// Age after which a cached remote wordlist is downloaded again, in seconds
+pub const WORDLIST_CACHE_TTL: u64 = 24 * 60 * 60;
+/// Number of words read at once from the wordlist with `--low-memory`
+pub const LOW_MEMORY_BATCH: usize = 100_000;
+/// Number of findings posted at once to the webhook
+pub const DEFAULT_WEBHOOK_BATCH: usize = 1;
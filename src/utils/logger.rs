@@ -2,12 +2,16 @@ use env_logger::{fmt::Color, Builder, Env};
 
 use std::io::Write;
 
+use crate::runner::request_log::REQUESTS_TARGET;
+
 pub fn init_logger() {
     let env = Env::default().filter_or("RWALK_LOG", "info");
 
     Builder::from_env(env)
         .filter_module("hyper_util::client::legacy::pool", log::LevelFilter::Warn)
         .filter_module("reqwest::connect", log::LevelFilter::Warn)
+        // Only logged with `--log-requests`, so always shown
+        .filter_module(REQUESTS_TARGET, log::LevelFilter::Debug)
         .filter_module(
             "hyper_util::client::legacy::connect::http",
             log::LevelFilter::Warn,
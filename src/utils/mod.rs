@@ -1,13 +1,17 @@
 use color_eyre::eyre::{bail, Result};
 use colored::{Colorize, CustomColor};
+use flate2::{write::GzEncoder, Compression};
 use parking_lot::Mutex;
 use std::path::PathBuf;
-use std::{io::Write, sync::Arc};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs::OpenOptions, io::Write, sync::Arc};
+use url::Url;
 
 use crate::cli::opts::Opts;
+use crate::runner::har::Har;
 use crate::utils::tree::{Tree, TreeData, TreeNode};
 
-use self::constants::DEFAULT_FILE_TYPE;
+use self::constants::{DEFAULT_FILE_TYPE, DEFAULT_SORT_OUTPUT};
 
 pub mod constants;
 pub mod display;
@@ -16,6 +20,8 @@ pub mod logger;
 pub mod scripting;
 pub mod structs;
 pub mod table;
+pub mod theme;
+pub mod title;
 pub mod tree;
 
 pub static GIT_COMMIT_HASH: &str = env!("_GIT_INFO");
@@ -131,6 +137,48 @@ pub fn is_range(s: &str) -> bool {
     false
 }
 
+/// Join two URL parts with a single slash between them
+pub fn join_path(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Path of `url` after the path of `root`, without leading slash
+///
+/// Roots with or without a trailing slash (or no path at all) give the same result.
+pub fn relative_path(url: &Url, root: &Url) -> String {
+    let path = url.path();
+    let root_path = root.path();
+    path.strip_prefix(root_path)
+        .or_else(|| path.strip_prefix(root_path.trim_end_matches('/')))
+        .unwrap_or(path)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// UTC year, month, day, hour, minute and second of a point in time
+pub fn utc(time: SystemTime) -> (i64, i64, i64, u64, u64, u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+    // Days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, rest / 3600, rest % 3600 / 60, rest % 60)
+}
+
 pub fn init_panic() -> Result<()> {
     let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default()
         .panic_section(format!(
@@ -184,63 +232,306 @@ pub fn open_file(file: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-// Write the tree to a file (json, csv, md)
+/// Command running `cmd` with the system shell
+pub fn shell(cmd: &str) -> tokio::process::Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = tokio::process::Command::new(shell);
+    command.args([flag, cmd]);
+    command
+}
+
+/// Expand the placeholders of an output path (`{host}`, `{date}`, `{time}` and `{timestamp}`), the date and time are in UTC
+pub fn expand_output(output: &str, url: &str, now: SystemTime) -> String {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.replace(':', "_")))
+        .unwrap_or_default();
+    let (year, month, day, hours, minutes, seconds) = utc(now);
+    output
+        .replace("{host}", &host)
+        .replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day))
+        .replace(
+            "{time}",
+            &format!("{:02}{:02}{:02}", hours, minutes, seconds),
+        )
+        .replace(
+            "{timestamp}",
+            &now.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+                .to_string(),
+        )
+}
+
+// Write the tree to each output file (json, ndjson, csv, md, har, dot)
+//
+// Returns the paths written to, with their placeholders expanded
 pub fn save_to_file(
     opts: &Opts,
+    har: &Har,
     root: Arc<Mutex<TreeNode<TreeData>>>,
     depth: Arc<Mutex<usize>>,
     tree: Arc<Mutex<Tree<TreeData>>>,
-) -> Result<()> {
-    let output = opts.output.clone().unwrap();
-    let file_type = output.split('.').last().unwrap_or(DEFAULT_FILE_TYPE);
-    let mut file = std::fs::File::create(opts.output.clone().unwrap())?;
+) -> Result<Vec<String>> {
+    // All the outputs of a scan share the same date and time
+    let now = SystemTime::now();
+    let root_url = root.lock().data.url.clone();
+    let outputs = opts
+        .output
+        .iter()
+        .map(|output| expand_output(output, &root_url, now))
+        .collect::<Vec<_>>();
+    // Walk the tree once for all the outputs
+    let mut nodes = Vec::new();
+    for depth in 0..*depth.lock() {
+        nodes.append(&mut tree.lock().get_nodes_at_depth(depth));
+    }
+    // The workers insert the results as they come, sort them for the output to be the same from one scan to another
+    let order = opts.sort_output.as_deref().unwrap_or(DEFAULT_SORT_OUTPUT);
+    let mut nodes = nodes
+        .iter()
+        .map(|node| node.lock().data.clone())
+        .collect::<Vec<_>>();
+    nodes.sort_by(|a, b| compare(a, b, order));
+    let json = if opts
+        .output
+        .iter()
+        .any(|output| output_format(opts, output) == "json")
+    {
+        let root = sorted(&root.lock(), order);
+        Some(if opts.pretty {
+            serde_json::to_string_pretty(&root)?
+        } else {
+            serde_json::to_string(&root)?
+        })
+    } else {
+        None
+    };
+    let har = if opts
+        .output
+        .iter()
+        .any(|output| output_format(opts, output) == "har")
+    {
+        Some(har.export(opts.pretty)?)
+    } else {
+        None
+    };
+    let dot = if opts
+        .output
+        .iter()
+        .any(|output| matches!(output_format(opts, output), "dot" | "gv"))
+    {
+        Some(tree::to_dot(&sorted(&root.lock(), order)))
+    } else {
+        None
+    };
+    // Markdown lists are nested, the children follow their parent instead of the nodes at the same depth
+    let md = if opts
+        .output
+        .iter()
+        .any(|output| output_format(opts, output) == "md")
+    {
+        let mut md = Vec::new();
+        flatten(&sorted(&root.lock(), order), *depth.lock(), &mut md);
+        Some(md)
+    } else {
+        None
+    };
 
+    for output in &outputs {
+        let file_type = output_format(opts, output);
+        let nodes = match (file_type, &md) {
+            ("md", Some(md)) => md,
+            _ => &nodes,
+        };
+        let compress = output.ends_with(".gz") || opts.compress_output;
+        if let Some(parent) = std::path::Path::new(output)
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Appended CSV files already start with a header
+        let headers = !opts.append || std::fs::metadata(output).map(|m| m.len()).unwrap_or(0) == 0;
+        let file = if opts.append {
+            OpenOptions::new().create(true).append(true).open(output)?
+        } else {
+            std::fs::File::create(output)?
+        };
+
+        if compress {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            write_output(
+                &mut encoder,
+                file_type,
+                nodes,
+                json.as_deref(),
+                dot.as_deref(),
+                har.as_deref(),
+                headers,
+            )?;
+            encoder.finish()?;
+        } else {
+            write_output(
+                file,
+                file_type,
+                nodes,
+                json.as_deref(),
+                dot.as_deref(),
+                har.as_deref(),
+                headers,
+            )?;
+        }
+    }
+    Ok(outputs)
+}
+
+/// Format of an output file given by `--format` or its extension, `.gz` outputs are compressed and use the extension before it
+fn output_format<'a>(opts: &'a Opts, output: &'a str) -> &'a str {
+    opts.format.as_deref().unwrap_or_else(|| {
+        output
+            .trim_end_matches(".gz")
+            .rsplit('.')
+            .next()
+            .unwrap_or(DEFAULT_FILE_TYPE)
+    })
+}
+
+/// Formats holding a single document, new results can't be appended to them
+const SINGLE_DOCUMENT_FORMATS: [&str; 6] = ["json", "har", "dot", "gv", "html", "htm"];
+
+/// Make sure `--format` is not given for several outputs, their formats come from their extensions
+pub fn check_format(opts: &Opts) -> Result<()> {
+    if opts.format.is_some() && opts.output.len() > 1 {
+        bail!(
+            "{} can't be used with several outputs, their formats are given by their extensions",
+            "--format".bold()
+        );
+    }
+    Ok(())
+}
+
+/// Make sure the outputs can be appended to with `--append`, before scanning
+pub fn check_append(opts: &Opts) -> Result<()> {
+    if !opts.append {
+        return Ok(());
+    }
+    if let Some(output) = opts
+        .output
+        .iter()
+        .find(|output| SINGLE_DOCUMENT_FORMATS.contains(&output_format(opts, output)))
+    {
+        bail!(
+            "Can't append to {}, use a line-based format like .ndjson or .csv",
+            output.bold()
+        );
+    }
+    Ok(())
+}
+
+/// Compare two results for `--sort-output`, "discovery" keeps the order they were found in
+fn compare(a: &TreeData, b: &TreeData, order: &str) -> std::cmp::Ordering {
+    match order {
+        "by-url" => a.url.cmp(&b.url),
+        "by-status" => (a.status_code, &a.url).cmp(&(b.status_code, &b.url)),
+        _ => std::cmp::Ordering::Equal,
+    }
+}
+
+/// Copy of a node with its children sorted, recursively
+fn sorted(node: &TreeNode<TreeData>, order: &str) -> TreeNode<TreeData> {
+    let mut children = node
+        .children
+        .iter()
+        .map(|child| sorted(&child.lock(), order))
+        .collect::<Vec<_>>();
+    children.sort_by(|a, b| compare(&a.data, &b.data, order));
+    TreeNode {
+        data: node.data.clone(),
+        children: children
+            .into_iter()
+            .map(|child| Arc::new(Mutex::new(child)))
+            .collect(),
+    }
+}
+
+/// Nodes of a tree above `depth`, each followed by its children
+fn flatten(node: &TreeNode<TreeData>, depth: usize, nodes: &mut Vec<TreeData>) {
+    if depth == 0 {
+        return;
+    }
+    nodes.push(node.data.clone());
+    for child in &node.children {
+        flatten(&child.lock(), depth - 1, nodes);
+    }
+}
+
+fn write_output(
+    mut file: impl Write,
+    file_type: &str,
+    nodes: &[TreeData],
+    json: Option<&str>,
+    dot: Option<&str>,
+    har: Option<&str>,
+    headers: bool,
+) -> Result<()> {
     match file_type {
+        "dot" | "gv" => {
+            file.write_all(dot.unwrap_or_default().as_bytes())?;
+            file.flush()?;
+            Ok(())
+        }
         "json" => {
-            let value = if opts.pretty {
-                serde_json::to_string_pretty(&*root.lock())?
-            } else {
-                serde_json::to_string(&*root.lock())?
-            };
-            file.write_all(value.as_bytes())?;
+            file.write_all(json.unwrap_or_default().as_bytes())?;
             file.flush()?;
             Ok(())
         }
-        "csv" => {
-            let mut writer = csv::Writer::from_writer(file);
-            let mut nodes = Vec::new();
-            for depth in 0..*depth.lock() {
-                nodes.append(&mut tree.lock().get_nodes_at_depth(depth));
+        "har" => {
+            file.write_all(har.unwrap_or_default().as_bytes())?;
+            file.flush()?;
+            Ok(())
+        }
+        "ndjson" | "jsonl" => {
+            for data in nodes {
+                serde_json::to_writer(&mut file, data)?;
+                file.write_all(b"\n")?;
             }
-            for node in nodes {
-                writer.serialize(node.lock().data.clone())?;
+            file.flush()?;
+            Ok(())
+        }
+        "csv" => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(headers)
+                .from_writer(file);
+            for data in nodes {
+                writer.serialize(data)?;
             }
             writer.flush()?;
             Ok(())
         }
+        "html" | "htm" => {
+            file.write_all(html(nodes).as_bytes())?;
+            file.flush()?;
+            Ok(())
+        }
         "md" => {
-            let mut nodes = Vec::new();
-            for depth in 0..*depth.lock() {
-                nodes.append(&mut tree.lock().get_nodes_at_depth(depth));
-            }
-            for node in nodes {
-                let data = node.lock().data.clone();
+            for data in nodes {
                 let emoji = get_emoji_for_status_code(data.status_code);
-                let url = data.url;
-                let path = data.path;
-                let depth = data.depth;
-                let status_code = data.status_code;
                 let line = format!(
                     "{}- [{} /{} {}]({})",
-                    "  ".repeat(depth),
+                    "  ".repeat(data.depth),
                     emoji,
-                    path.trim_start_matches('/'),
-                    if status_code == 0 {
+                    data.path.trim_start_matches('/'),
+                    if data.status_code == 0 {
                         "".to_string()
                     } else {
-                        format!("({})", status_code)
+                        format!("({})", data.status_code)
                     },
-                    url,
+                    data.url,
                 );
                 file.write_all(line.as_bytes())?;
                 file.write_all(b"\n")?;
@@ -249,12 +540,7 @@ pub fn save_to_file(
             Ok(())
         }
         _ => {
-            let mut nodes = Vec::new();
-            for depth in 0..*depth.lock() {
-                nodes.append(&mut tree.lock().get_nodes_at_depth(depth));
-            }
-            for node in nodes {
-                let data = node.lock().data.clone();
+            for data in nodes {
                 file.write_all(data.url.as_bytes())?;
                 file.write_all(b"\n")?;
             }
@@ -264,6 +550,32 @@ pub fn save_to_file(
     }
 }
 
+/// Standalone HTML page with a table of the results
+fn html(nodes: &[TreeData]) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let rows = nodes
+        .iter()
+        .map(|data| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td><a href=\"{}\">{}</a></td></tr>\n",
+                data.status_code,
+                data.depth,
+                escape(&data.url),
+                escape(&data.url)
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>rwalk results</title>\n</head>\n<body>\n<table>\n<tr><th>Status</th><th>Depth</th><th>URL</th></tr>\n{}</table>\n</body>\n</html>\n",
+        rows
+    )
+}
+
 pub fn version() -> String {
     let author = clap::crate_authors!();
 
@@ -315,6 +627,221 @@ mod tests {
         assert!(parse_range_input("1-2,>3,4-").is_err());
     }
 
+    #[test]
+    fn test_join_path() {
+        assert_eq!(join_path("http://x", "app"), "http://x/app");
+        assert_eq!(join_path("http://x/", "/app/"), "http://x/app/");
+        assert_eq!(join_path("/app/", "/admin"), "/app/admin");
+        assert_eq!(join_path("/app", "admin"), "/app/admin");
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        for root in ["http://x", "http://x/"] {
+            assert_eq!(relative_path(&url("http://x/a/b"), &url(root)), "a/b");
+            assert_eq!(relative_path(&url("http://x/"), &url(root)), "");
+        }
+        for root in ["http://x/app", "http://x/app/"] {
+            assert_eq!(relative_path(&url("http://x/app/a/b"), &url(root)), "a/b");
+            assert_eq!(relative_path(&url("http://x/app"), &url(root)), "");
+        }
+        // Classic roots stop right before the fuzz key
+        assert_eq!(
+            relative_path(&url("http://x/file_backup.php"), &url("http://x/file_")),
+            "backup.php"
+        );
+        // URLs outside of the root keep their full path
+        assert_eq!(
+            relative_path(&url("http://x/other/a"), &url("http://x/app")),
+            "other/a"
+        );
+    }
+
+    #[test]
+    fn test_save_to_file() {
+        let output =
+            std::env::temp_dir().join(format!("rwalk-test-{}.json.gz", std::process::id()));
+        let mut tree = Tree::new();
+        tree.insert(
+            TreeData {
+                url: "http://localhost/".to_string(),
+                depth: 0,
+                path: "".to_string(),
+                status_code: 200,
+                extra: serde_json::Value::Null,
+                url_type: tree::UrlType::Directory,
+                response: None,
+            },
+            None,
+        );
+        let root = tree.root.clone().unwrap();
+        let opts = Opts {
+            output: vec![
+                output.display().to_string(),
+                output.with_extension("csv").display().to_string(),
+                output.with_extension("html").display().to_string(),
+            ],
+            ..Default::default()
+        };
+        save_to_file(
+            &opts,
+            &Har::default(),
+            root,
+            Arc::new(Mutex::new(1)),
+            Arc::new(Mutex::new(tree)),
+        )
+        .unwrap();
+
+        let file = std::fs::File::open(&output).unwrap();
+        let mut json = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(file), &mut json).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        // Every output is written from the same tree
+        let csv = std::fs::read_to_string(output.with_extension("csv")).unwrap();
+        std::fs::remove_file(output.with_extension("csv")).unwrap();
+        assert!(csv.contains("http://localhost/"));
+        let html = std::fs::read_to_string(output.with_extension("html")).unwrap();
+        std::fs::remove_file(output.with_extension("html")).unwrap();
+        assert!(html.contains("<a href=\"http://localhost/\">http://localhost/</a>"));
+        let saved: TreeNode<TreeData> = serde_json::from_str(&json).unwrap();
+        assert_eq!(saved.data.url, "http://localhost/");
+    }
+
+    #[test]
+    fn test_check_format() {
+        let opts = Opts {
+            output: vec!["results.txt".to_string()],
+            format: Some("json".to_string()),
+            ..Default::default()
+        };
+        assert!(check_format(&opts).is_ok());
+        assert_eq!(output_format(&opts, "results.txt"), "json");
+        assert!(check_format(&Opts {
+            output: vec!["results.txt".to_string(), "results.csv".to_string()],
+            ..opts
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_expand_output() {
+        // 2024-03-05 07:08:09 UTC
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(1709622489);
+        assert_eq!(
+            expand_output(
+                "scans/{host}-{date}-{time}.json",
+                "https://example.com:8443/a",
+                now
+            ),
+            "scans/example.com-2024-03-05-070809.json"
+        );
+        assert_eq!(
+            expand_output("{timestamp}-{other}.csv", "http://[::1]/", now),
+            "1709622489-{other}.csv"
+        );
+        assert_eq!(
+            expand_output("{host}.csv", "http://[::1]/", now),
+            "[__1].csv"
+        );
+    }
+
+    #[test]
+    fn test_save_to_file_nested() {
+        let dir = std::env::temp_dir().join(format!("rwalk-test-nested-{}", std::process::id()));
+        let mut tree = Tree::new();
+        tree.insert(
+            TreeData {
+                url: "http://localhost/".to_string(),
+                depth: 0,
+                path: "".to_string(),
+                status_code: 200,
+                extra: serde_json::Value::Null,
+                url_type: tree::UrlType::Directory,
+                response: None,
+            },
+            None,
+        );
+        let root = tree.root.clone().unwrap();
+        let opts = Opts {
+            output: vec![dir.join("{host}/results.csv").display().to_string()],
+            ..Default::default()
+        };
+        let outputs = save_to_file(
+            &opts,
+            &Har::default(),
+            root,
+            Arc::new(Mutex::new(1)),
+            Arc::new(Mutex::new(tree)),
+        )
+        .unwrap();
+
+        let output = dir.join("localhost/results.csv");
+        assert_eq!(outputs, vec![output.display().to_string()]);
+        assert!(std::fs::read_to_string(&output)
+            .unwrap()
+            .contains("http://localhost/"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_to_file_append() {
+        let output =
+            std::env::temp_dir().join(format!("rwalk-test-append-{}.csv", std::process::id()));
+        let mut tree = Tree::new();
+        tree.insert(
+            TreeData {
+                url: "http://localhost/".to_string(),
+                depth: 0,
+                path: "".to_string(),
+                status_code: 200,
+                extra: serde_json::Value::Null,
+                url_type: tree::UrlType::Directory,
+                response: None,
+            },
+            None,
+        );
+        let root = tree.root.clone().unwrap();
+        let tree = Arc::new(Mutex::new(tree));
+        let opts = Opts {
+            output: vec![
+                output.display().to_string(),
+                output.with_extension("ndjson").display().to_string(),
+            ],
+            append: true,
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            save_to_file(
+                &opts,
+                &Har::default(),
+                root.clone(),
+                Arc::new(Mutex::new(1)),
+                tree.clone(),
+            )
+            .unwrap();
+        }
+
+        let csv = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        let ndjson = std::fs::read_to_string(output.with_extension("ndjson")).unwrap();
+        std::fs::remove_file(output.with_extension("ndjson")).unwrap();
+        // A single header, then the two results
+        assert_eq!(csv.lines().count(), 3);
+        assert_eq!(
+            csv.lines().filter(|line| line.starts_with("url")).count(),
+            1
+        );
+        assert_eq!(ndjson.lines().count(), 2);
+
+        let opts = Opts {
+            output: vec!["results.json".to_string()],
+            append: true,
+            ..Default::default()
+        };
+        assert!(check_append(&opts).is_err());
+    }
+
     #[test]
     fn test_get_emoji_for_status_code() {
         assert_eq!(get_emoji_for_status_code(200), "✓");
@@ -323,4 +850,38 @@ mod tests {
         assert_eq!(get_emoji_for_status_code(500), "⚠");
         assert_eq!(get_emoji_for_status_code(0), "⚠");
     }
+
+    #[test]
+    fn test_sorted() {
+        let data = |url: &str, status_code: u16| TreeData {
+            url: url.to_string(),
+            status_code,
+            ..Default::default()
+        };
+        let mut tree = Tree::new();
+        let root = tree.insert(data("http://x/", 200), None);
+        for (url, status_code) in [
+            ("http://x/c", 200),
+            ("http://x/a", 403),
+            ("http://x/b", 200),
+        ] {
+            tree.insert(data(url, status_code), Some(root.clone()));
+        }
+        let urls = |order: &str| {
+            sorted(&root.lock(), order)
+                .children
+                .iter()
+                .map(|child| child.lock().data.url.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(urls("by-url"), ["http://x/a", "http://x/b", "http://x/c"]);
+        assert_eq!(
+            urls("by-status"),
+            ["http://x/b", "http://x/c", "http://x/a"]
+        );
+        assert_eq!(
+            urls("discovery"),
+            ["http://x/c", "http://x/a", "http://x/b"]
+        );
+    }
 }
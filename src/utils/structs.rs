@@ -48,6 +48,12 @@ pub struct Save {
     pub wordlist_checksum: String,
     pub indexes: HashMap<String, Vec<usize>>,
     pub opts: Opts,
+    /// Words of the chunks the indexes point into, to resume with a superset of the wordlists
+    #[serde(default)]
+    pub chunks: Vec<Vec<String>>,
+    /// URLs that failed with a connection error or a timeout, for `--rerun-errors`
+    #[serde(default)]
+    pub errors: Vec<String>,
 }
 
 #[cfg(test)]
@@ -69,4 +75,28 @@ mod tests {
         assert_eq!(Mode::from("c"), Mode::Classic);
         assert_eq!(Mode::from("invalid"), Mode::Recursive);
     }
+
+    #[test]
+    fn test_save_without_errors() {
+        let save = Save {
+            tree: Arc::new(Mutex::new(Tree::new())),
+            depth: Arc::new(Mutex::new(0)),
+            wordlist_checksum: String::new(),
+            indexes: HashMap::new(),
+            opts: Opts::default(),
+            chunks: Vec::new(),
+            errors: vec!["http://localhost/admin".to_string()],
+        };
+        let mut json = serde_json::to_value(&save).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Save>(json.clone()).unwrap().errors,
+            save.errors
+        );
+        // Saved by a version without `--rerun-errors`
+        json.as_object_mut().unwrap().remove("errors");
+        assert!(serde_json::from_value::<Save>(json)
+            .unwrap()
+            .errors
+            .is_empty());
+    }
 }
@@ -7,8 +7,10 @@ use crate::{
     },
     runner::wordlists::ParsedWordlist,
     utils::{
+        color_for_status_code,
         display::{display_range, display_range_status},
         is_range,
+        tree::TreeData,
     },
 };
 use colored::{Colorize, CustomColor};
@@ -272,10 +274,10 @@ pub fn build_opts_table(
             if num_cpus::get() > 1 { "s" } else { "" }
         ),
     ]);
-    if opts.output.is_some() {
+    if !opts.output.is_empty() {
         builder.push_record(vec![
             "Output",
-            &opts.output.as_ref().unwrap().bold().blue().to_string(),
+            &opts.output.join(", ").bold().blue().to_string(),
         ]);
     }
 
@@ -285,3 +287,248 @@ pub fn build_opts_table(
         .with(Alignment::center_vertical())
         .to_string()
 }
+
+/// Builds the table of `(status, size)` signatures found during the scan (`--signatures`)
+///
+/// Sizes are read from the `size` addition stored in each node's extra data
+pub fn build_signatures_table(datas: &[TreeData]) -> String {
+    let mut builder = Builder::default();
+    builder.push_record(vec!["Status", "Size", "Count"]);
+
+    let signatures = datas
+        .iter()
+        .filter_map(|data| Some((data.status_code, numeric_addition(data, "size")?)))
+        .counts()
+        .into_iter()
+        .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for ((status_code, size), count) in signatures {
+        builder.push_record(vec![
+            color_for_status_code(status_code.to_string(), status_code),
+            size.to_string(),
+            count.to_string().bold().to_string(),
+        ]);
+    }
+
+    builder.build().with(Style::modern_rounded()).to_string()
+}
+
+/// Width of the longest bar of the histogram
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Builds a histogram of the response times of all the requests sent (`--signatures`)
+///
+/// Returns `None` if no response time was recorded
+pub fn build_time_histogram(times: &[usize], buckets: usize) -> Option<String> {
+    let times = times.iter().copied().sorted_unstable().collect::<Vec<_>>();
+    let (min, max) = (*times.first()?, *times.last()?);
+    let mean = times.iter().sum::<usize>() / times.len();
+    // Nearest-rank percentile
+    let p95 = times[(times.len() * 95).div_ceil(100) - 1];
+
+    let buckets = buckets.clamp(1, max - min + 1);
+    let width = (max - min) / buckets + 1;
+    let mut counts = vec![0; buckets];
+    for time in &times {
+        counts[(time - min) / width] += 1;
+    }
+    let peak = counts.iter().max().copied().unwrap_or(1).max(1);
+
+    let mut out = format!(
+        "Response times: min {}ms | mean {}ms | p95 {}ms | max {}ms\n",
+        min.to_string().bold(),
+        mean.to_string().bold(),
+        p95.to_string().bold(),
+        max.to_string().bold()
+    );
+    for (i, count) in counts.iter().enumerate() {
+        let low = min + i * width;
+        out.push_str(&format!(
+            "{:>7} - {:<8} {} {}\n",
+            format!("{}ms", low),
+            format!("{}ms", low + width - 1),
+            "█".repeat(count * HISTOGRAM_WIDTH / peak),
+            count.to_string().dimmed()
+        ));
+    }
+    Some(out)
+}
+
+/// Builds a table of the `count` slowest results (`--slowest`)
+pub fn build_slowest_table(datas: &[TreeData], count: usize) -> Option<String> {
+    let slowest = datas
+        .iter()
+        .filter_map(|data| Some((numeric_addition(data, "time")?, data)))
+        .sorted_by(|a, b| b.0.cmp(&a.0).then(a.1.url.cmp(&b.1.url)))
+        .take(count)
+        .collect::<Vec<_>>();
+    if slowest.is_empty() {
+        return None;
+    }
+    let mut builder = Builder::default();
+    builder.push_record(vec!["Time", "Status", "URL"]);
+    for (time, data) in slowest {
+        builder.push_record(vec![
+            format!("{}ms", time).bold().to_string(),
+            color_for_status_code(data.status_code.to_string(), data.status_code),
+            data.url.clone(),
+        ]);
+    }
+    Some(builder.build().with(Style::modern_rounded()).to_string())
+}
+
+/// Builds a table of the percentiles of each timing saved with `--timing-detail`
+///
+/// Returns `None` if no timing was recorded
+pub fn build_timing_table(datas: &[TreeData]) -> Option<String> {
+    let timings = datas
+        .iter()
+        .filter_map(|data| {
+            data.extra
+                .as_array()?
+                .iter()
+                .find(|addition| addition["key"] == "timing")
+                .map(|addition| &addition["value"])
+        })
+        .collect::<Vec<_>>();
+    if timings.is_empty() {
+        return None;
+    }
+    let mut builder = Builder::default();
+    builder.push_record(vec!["Timing", "p50", "p90", "p99", "Max"]);
+    for (name, key) in [
+        ("Time to first byte", "ttfb"),
+        ("Download", "download"),
+        ("Total", "total"),
+    ] {
+        let values = timings
+            .iter()
+            .filter_map(|timing| timing[key].as_u64())
+            .sorted_unstable()
+            .collect::<Vec<_>>();
+        let Some(max) = values.last() else {
+            continue;
+        };
+        // Nearest-rank percentile
+        let percentile = |p: usize| values[(values.len() * p).div_ceil(100) - 1];
+        let mut record = vec![name.to_string()];
+        record.extend(
+            [percentile(50), percentile(90), percentile(99), *max]
+                .iter()
+                .map(|ms| format!("{}ms", ms)),
+        );
+        builder.push_record(record);
+    }
+    Some(builder.build().with(Style::modern_rounded()).to_string())
+}
+
+/// Builds a table of the URLs whose methods got statuses of different classes (`--method-report`), e.g. `GET 404` and `POST 200`
+///
+/// Returns `None` if there is no such URL
+pub fn build_method_table(datas: &[TreeData]) -> Option<String> {
+    // A URL is in the tree once per method that matched, all with the same statuses
+    let urls = datas
+        .iter()
+        .filter_map(|data| Some((data.url.clone(), method_statuses(data)?)))
+        .filter(|(_, statuses)| methods_differ(statuses))
+        .collect::<std::collections::BTreeMap<_, _>>();
+    if urls.is_empty() {
+        return None;
+    }
+    let mut builder = Builder::default();
+    builder.push_record(vec!["URL", "Statuses"]);
+    for (url, statuses) in urls {
+        builder.push_record(vec![
+            url,
+            statuses
+                .iter()
+                .map(|(method, status_code)| {
+                    format!(
+                        "{} {}",
+                        method.bold(),
+                        color_for_status_code(status_code.to_string(), *status_code)
+                    )
+                })
+                .join(", "),
+        ]);
+    }
+    Some(builder.build().with(Style::modern_rounded()).to_string())
+}
+
+/// Status code of each method saved with `--methods`, sorted by method
+fn method_statuses(data: &TreeData) -> Option<Vec<(String, u16)>> {
+    let statuses = data
+        .extra
+        .as_array()?
+        .iter()
+        .find(|addition| addition["key"] == "methods")?["value"]
+        .as_object()?;
+    Some(
+        statuses
+            .iter()
+            .filter_map(|(method, status_code)| {
+                Some((method.clone(), status_code.as_u64()? as u16))
+            })
+            .sorted()
+            .collect(),
+    )
+}
+
+/// Whether the methods got statuses of different classes, `405` and `501` only tell that a method isn't supported
+fn methods_differ(statuses: &[(String, u16)]) -> bool {
+    statuses
+        .iter()
+        .filter(|(_, status_code)| !matches!(status_code, 405 | 501))
+        .map(|(_, status_code)| status_code / 100)
+        .unique()
+        .count()
+        > 1
+}
+
+/// Numeric value of an addition saved in the extra data of a result
+fn numeric_addition(data: &TreeData, key: &str) -> Option<usize> {
+    data.extra
+        .as_array()?
+        .iter()
+        .find(|addition| addition["key"] == key)?["value"]
+        .as_str()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses(statuses: &[(&str, u16)]) -> Vec<(String, u16)> {
+        statuses
+            .iter()
+            .map(|(method, status_code)| (method.to_string(), *status_code))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_time_histogram() {
+        assert_eq!(build_time_histogram(&[], 10), None);
+        let histogram = strip_ansi_escapes::strip_str(
+            build_time_histogram(&[10, 12, 11, 100, 101], 2).unwrap(),
+        );
+        let lines = histogram.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "Response times: min 10ms | mean 46ms | p95 101ms | max 101ms"
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with(" 3"));
+        assert!(lines[2].ends_with(" 2"));
+    }
+
+    #[test]
+    fn test_methods_differ() {
+        assert!(methods_differ(&statuses(&[("GET", 404), ("POST", 200)])));
+        assert!(methods_differ(&statuses(&[("GET", 200), ("PUT", 403)])));
+        assert!(!methods_differ(&statuses(&[("GET", 200), ("POST", 201)])));
+        // Unsupported methods are expected to differ
+        assert!(!methods_differ(&statuses(&[("GET", 200), ("DELETE", 405)])));
+        assert!(!methods_differ(&statuses(&[("GET", 404)])));
+    }
+}
@@ -0,0 +1,236 @@
+use std::{collections::HashMap, str::FromStr};
+
+use color_eyre::eyre::{eyre, Result};
+use colored::{Color, ColoredString, Colorize};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+
+use crate::cli::opts::Opts;
+
+use super::constants::{ERROR, SUCCESS, WARNING};
+
+lazy_static! {
+    static ref THEME: RwLock<Theme> = RwLock::new(Theme::default());
+}
+
+/// Symbols and colors used to mark the results
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub success: (String, Color),
+    pub warning: (String, Color),
+    pub error: (String, Color),
+    /// Colors of specific status codes, over the ones above (`--color-map`)
+    pub status_colors: HashMap<u16, Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            success: (SUCCESS.to_string(), Color::Green),
+            warning: (WARNING.to_string(), Color::Yellow),
+            error: (ERROR.to_string(), Color::Red),
+            status_colors: HashMap::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// Get a preset by name (`--theme`)
+    pub fn preset(name: &str) -> Result<Self> {
+        match name {
+            "default" => Ok(Self::default()),
+            // Okabe-Ito palette, distinguishable with the most common color vision deficiencies
+            "colorblind" => Ok(Self {
+                success: (
+                    SUCCESS.to_string(),
+                    Color::TrueColor {
+                        r: 0,
+                        g: 114,
+                        b: 178,
+                    },
+                ),
+                warning: (
+                    WARNING.to_string(),
+                    Color::TrueColor {
+                        r: 230,
+                        g: 159,
+                        b: 0,
+                    },
+                ),
+                error: (
+                    ERROR.to_string(),
+                    Color::TrueColor {
+                        r: 213,
+                        g: 94,
+                        b: 0,
+                    },
+                ),
+                status_colors: HashMap::new(),
+            }),
+            // For terminals without unicode support
+            "ascii" => Ok(Self {
+                success: ("+".to_string(), Color::Green),
+                warning: ("!".to_string(), Color::Yellow),
+                error: ("x".to_string(), Color::Red),
+                status_colors: HashMap::new(),
+            }),
+            _ => Err(eyre!("Unknown theme: {}", name)),
+        }
+    }
+
+    /// Build the theme from the preset and the overrides given in the options
+    pub fn from_opts(opts: &Opts) -> Result<Self> {
+        let mut theme = Self::preset(opts.theme.as_deref().unwrap_or("default"))?;
+        for ((symbol, color), (symbol_opt, color_opt)) in [
+            (
+                &mut theme.success,
+                (&opts.symbol_success, &opts.color_success),
+            ),
+            (
+                &mut theme.warning,
+                (&opts.symbol_warning, &opts.color_warning),
+            ),
+            (&mut theme.error, (&opts.symbol_error, &opts.color_error)),
+        ] {
+            if let Some(symbol_opt) = symbol_opt {
+                symbol.clone_from(symbol_opt);
+            }
+            if let Some(color_opt) = color_opt {
+                *color = parse_color(color_opt)?;
+            }
+        }
+        // Everything is printed without colors anyway
+        if !opts.no_color {
+            for entry in &opts.color_map {
+                let (code, color) = entry.split_once('=').ok_or_else(|| {
+                    eyre!("Invalid color mapping: {}, expected CODE=COLOR", entry)
+                })?;
+                let code = code
+                    .trim()
+                    .parse::<u16>()
+                    .map_err(|_| eyre!("Invalid status code: {}", code))?;
+                theme.status_colors.insert(code, parse_color(color.trim())?);
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// Parse a color name (`red`, `bright blue`...) or a `#rrggbb` hex code
+fn parse_color(s: &str) -> Result<Color> {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+                return Ok(Color::TrueColor {
+                    r: (rgb >> 16) as u8,
+                    g: (rgb >> 8) as u8,
+                    b: rgb as u8,
+                });
+            }
+        }
+    }
+    Color::from_str(s).map_err(|_| eyre!("Invalid color: {}", s))
+}
+
+/// Set the theme used for the rest of the run
+pub fn init(opts: &Opts) -> Result<()> {
+    *THEME.write() = Theme::from_opts(opts)?;
+    Ok(())
+}
+
+pub fn success() -> ColoredString {
+    let (symbol, color) = &THEME.read().success;
+    symbol.color(*color)
+}
+
+pub fn warning() -> ColoredString {
+    let (symbol, color) = &THEME.read().warning;
+    symbol.color(*color)
+}
+
+pub fn error() -> ColoredString {
+    let (symbol, color) = &THEME.read().error;
+    symbol.color(*color)
+}
+
+/// Symbol of a response, colored by its status code when it is in `--color-map`
+pub fn status(status_code: u16) -> ColoredString {
+    let theme = THEME.read();
+    let (symbol, color) = match status_code {
+        200..=299 => &theme.success,
+        300..=399 => &theme.warning,
+        _ => &theme.error,
+    };
+    symbol.color(*theme.status_colors.get(&status_code).unwrap_or(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_from_opts() {
+        assert_eq!(
+            Theme::from_opts(&Opts::default()).unwrap(),
+            Theme::default()
+        );
+
+        let theme = Theme::from_opts(&Opts {
+            theme: Some("ascii".to_string()),
+            symbol_error: Some("E".to_string()),
+            color_success: Some("blue".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(theme.success, ("+".to_string(), Color::Blue));
+        assert_eq!(theme.warning, ("!".to_string(), Color::Yellow));
+        assert_eq!(theme.error, ("E".to_string(), Color::Red));
+
+        assert_eq!(
+            parse_color("#0072b2").unwrap(),
+            Color::TrueColor {
+                r: 0,
+                g: 114,
+                b: 178
+            }
+        );
+        assert!(parse_color("#0072").is_err());
+
+        assert!(Theme::from_opts(&Opts {
+            color_warning: Some("not-a-color".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_color_map() {
+        let theme = Theme::from_opts(&Opts {
+            color_map: vec!["401=magenta".to_string(), "500 = #ff0000".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(theme.status_colors[&401], Color::Magenta);
+        assert_eq!(
+            theme.status_colors[&500],
+            Color::TrueColor { r: 255, g: 0, b: 0 }
+        );
+
+        for color_map in ["401", "abc=red", "401=not-a-color"] {
+            assert!(Theme::from_opts(&Opts {
+                color_map: vec![color_map.to_string()],
+                ..Default::default()
+            })
+            .is_err());
+        }
+
+        // Ignored without colors
+        let theme = Theme::from_opts(&Opts {
+            color_map: vec!["401=magenta".to_string()],
+            no_color: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(theme.status_colors.is_empty());
+    }
+}
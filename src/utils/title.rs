@@ -0,0 +1,99 @@
+use std::{
+    io::{IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanDuration, ProgressBar};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::cli::opts::Opts;
+
+/// How often the title is refreshed
+const INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    /// Every progress bar of the scan, their sum is shown in the title
+    static ref PROGRESSES: Mutex<Vec<ProgressBar>> = Mutex::new(Vec::new());
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Show the progress and the ETA in the terminal title (`--update-title`)
+///
+/// Disabled when stderr is not a terminal or colors are turned off.
+pub fn start(opts: &Opts) -> Option<JoinHandle<()>> {
+    if !opts.update_title
+        || opts.no_color
+        || std::env::var_os("NO_COLOR").is_some()
+        || !std::io::stderr().is_terminal()
+    {
+        return None;
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+    let start = Instant::now();
+    Some(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(INTERVAL).await;
+            let (done, total) = PROGRESSES.lock().iter().fold((0, 0), |(done, total), pb| {
+                (done + pb.position(), total + pb.length().unwrap_or(0))
+            });
+            set(&format_title(done, total, start.elapsed()));
+        }
+    }))
+}
+
+/// Include a progress bar in the title
+pub fn track(progress: &ProgressBar) {
+    if ENABLED.load(Ordering::Relaxed) {
+        PROGRESSES.lock().push(progress.clone());
+    }
+}
+
+/// Stop updating the title and give it back to the terminal
+pub fn stop(handle: JoinHandle<()>) {
+    handle.abort();
+    set("");
+}
+
+fn set(title: &str) {
+    let mut stderr = std::io::stderr();
+    let _ = write!(stderr, "\x1b]0;{}\x07", title);
+    let _ = stderr.flush();
+}
+
+fn format_title(done: u64, total: u64, elapsed: Duration) -> String {
+    if total == 0 || done == 0 {
+        return "rwalk - starting".to_string();
+    }
+    let done = done.min(total);
+    let eta = elapsed.mul_f64((total - done) as f64 / done as f64);
+    format!(
+        "rwalk - {}% - ETA {}",
+        done * 100 / total,
+        HumanDuration(eta)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_title() {
+        assert_eq!(
+            format_title(0, 100, Duration::from_secs(5)),
+            "rwalk - starting"
+        );
+        assert_eq!(
+            format_title(25, 100, Duration::from_secs(60)),
+            "rwalk - 25% - ETA 3 minutes"
+        );
+        assert_eq!(
+            format_title(100, 100, Duration::from_secs(60)),
+            "rwalk - 100% - ETA 0 seconds"
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, Result};
 use colored::Colorize;
+use itertools::Itertools;
 use log::{info, warn};
 use parking_lot::Mutex;
 use ptree::{print_tree, TreeItem};
@@ -7,12 +8,16 @@ use rhai::{CustomType, TypeBuilder};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fmt::Debug;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use url::Url;
 
 use crate::{
     cli::opts::Opts,
     runner::wordlists::{compute_checksum, ParsedWordlist},
-    utils::{get_emoji_for_status_code_colored, scripting::ScriptingResponse},
+    utils::{get_emoji_for_status_code_colored, relative_path, scripting::ScriptingResponse},
     Save,
 };
 
@@ -179,6 +184,114 @@ impl<T> Tree<T> {
     }
 }
 
+impl<T: Clone> TreeNode<T> {
+    /// Collect the data of this node and all of its descendants
+    ///
+    /// # Returns
+    ///
+    /// A vector of all the data in the subtree, in depth-first order
+    ///
+    pub fn flatten(&self) -> Vec<T> {
+        let mut datas = vec![self.data.clone()];
+        for child in &self.children {
+            datas.extend(child.lock().flatten());
+        }
+        datas
+    }
+}
+
+/// Graphviz DOT graph of a tree, with the nodes colored by status class (`.dot` outputs)
+pub fn to_dot(root: &TreeNode<TreeData>) -> String {
+    let mut out = "digraph rwalk {\n    node [shape=box, style=filled];\n".to_string();
+    write_dot_node(root, true, &mut out, &mut 0);
+    out.push_str("}\n");
+    out
+}
+
+/// Write a node and its subtree, returns the id of the node
+fn write_dot_node(
+    node: &TreeNode<TreeData>,
+    root: bool,
+    out: &mut String,
+    next_id: &mut usize,
+) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    // Only the path segment, the full URL is in the tooltip
+    let label = if root {
+        node.data.url.as_str()
+    } else {
+        node.data.path.trim_start_matches('/')
+    };
+    let color = match node.data.status_code {
+        100..=199 => "lightblue",
+        200..=299 => "palegreen",
+        300..=399 => "khaki",
+        400..=499 => "orange",
+        500..=599 => "salmon",
+        _ => "lightgray",
+    };
+    out.push_str(&format!(
+        "    n{} [label=\"{}\", tooltip=\"{} {}\", fillcolor={}];\n",
+        id,
+        escape_dot(label),
+        node.data.status_code,
+        escape_dot(&node.data.url),
+        color
+    ));
+    for child in &node.children {
+        let child_id = write_dot_node(&child.lock(), false, out, next_id);
+        out.push_str(&format!("    n{} -> n{};\n", id, child_id));
+    }
+    id
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Results of several scans in a single tree, their roots under a root named after `name` (e.g. the `--urls-file`)
+///
+/// A single tree is returned as is.
+pub fn merge(mut trees: Vec<Tree<TreeData>>, name: &str) -> Tree<TreeData> {
+    if trees.len() == 1 {
+        return trees.remove(0);
+    }
+    let mut merged = Tree::new();
+    let root = merged.insert(
+        TreeData {
+            url: name.to_string(),
+            url_type: UrlType::None,
+            ..Default::default()
+        },
+        None,
+    );
+    root.lock().children = trees.into_iter().filter_map(|tree| tree.root).collect();
+    merged
+}
+
+/// Sorted entries of a wordlist for a follow-up scan (`--emit-wordlist`), the paths of the results relative to the root or their URLs
+pub fn to_wordlist(root: &TreeNode<TreeData>, urls: bool) -> Vec<String> {
+    let root_url = Url::parse(&root.data.url).ok();
+    root.flatten()
+        .into_iter()
+        .skip(1)
+        .filter_map(|data| {
+            if urls {
+                return Some(data.url);
+            }
+            let url = Url::parse(&data.url).ok()?;
+            let path = match &root_url {
+                Some(root_url) => relative_path(&url, root_url),
+                None => url.path().trim_start_matches('/').to_string(),
+            };
+            (!path.is_empty()).then_some(path)
+        })
+        .sorted()
+        .dedup()
+        .collect()
+}
+
 impl TreeItem for TreeNode<String> {
     type Child = TreeNode<String>;
     fn children(&self) -> std::borrow::Cow<[Self::Child]> {
@@ -262,6 +375,7 @@ pub fn from_save(
     save: &Save,
     depth: Arc<Mutex<usize>>,
     current_indexes: Arc<Mutex<HashMap<String, Vec<usize>>>>,
+    tested_words: Arc<Mutex<HashMap<String, HashSet<String>>>>,
     words: HashMap<String, ParsedWordlist>,
 ) -> Result<Arc<Mutex<Tree<TreeData>>>> {
     if let Some(root) = &save.tree.clone().lock().root {
@@ -278,6 +392,19 @@ pub fn from_save(
             *depth.lock() = *save.depth.lock();
             if save.wordlist_checksum == { compute_checksum(&words) } {
                 current_indexes.lock().clone_from(&save.indexes);
+            } else if !save.chunks.is_empty() {
+                // The words are matched by value, the new ones can be anywhere in the wordlists
+                let tested = tested_words_from_save(save, &words)?;
+                info!(
+                    "Wordlists have grown, skipping the {} words already tested",
+                    tested
+                        .values()
+                        .map(HashSet::len)
+                        .sum::<usize>()
+                        .to_string()
+                        .bold()
+                );
+                *tested_words.lock() = tested;
             } else {
                 warn!(
                     "Wordlists have changed, starting from scratch at depth {}",
@@ -291,6 +418,55 @@ pub fn from_save(
     }
 }
 
+/// Words tested for each URL before the save, as long as the new wordlists still contain all the saved words
+fn tested_words_from_save(
+    save: &Save,
+    words: &HashMap<String, ParsedWordlist>,
+) -> Result<HashMap<String, HashSet<String>>> {
+    let current = words
+        .values()
+        .flat_map(|wordlist| &wordlist.words)
+        .collect::<HashSet<_>>();
+    let missing = save
+        .chunks
+        .iter()
+        .flatten()
+        .filter(|word| !current.contains(word))
+        .collect::<Vec<_>>();
+    if let Some(word) = missing.first() {
+        bail!(
+            "Cannot resume: {} words of the saved state are missing from the wordlists (e.g. {}), only words can be added",
+            missing.len(),
+            word.bold()
+        );
+    }
+    Ok(save
+        .indexes
+        .iter()
+        .map(|(url, indexes)| {
+            let tested = indexes
+                .iter()
+                .zip(&save.chunks)
+                .flat_map(|(index, chunk)| chunk[..(*index).min(chunk.len())].iter().cloned())
+                .collect();
+            (url.clone(), tested)
+        })
+        .collect())
+}
+
+/// Load the URLs and status codes found in a previous JSON output
+pub fn load_previous(path: &str) -> Result<HashMap<String, u16>> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        color_eyre::eyre::eyre!("Failed to read previous output {}: {}", path.bold(), e)
+    })?;
+    let root: TreeNode<TreeData> = serde_json::from_str(&content)?;
+    Ok(root
+        .flatten()
+        .into_iter()
+        .map(|data| (data.url, data.status_code))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -309,6 +485,34 @@ mod tests {
         assert_eq!(tree_root.children[0].lock().data, "node2".to_string());
     }
 
+    #[test]
+    fn test_merge() {
+        let tree = |url: &str| {
+            let mut tree = Tree::new();
+            tree.insert(
+                TreeData {
+                    url: url.to_string(),
+                    ..Default::default()
+                },
+                None,
+            );
+            tree
+        };
+        let merged = merge(vec![tree("http://a/")], "targets.txt");
+        assert_eq!(merged.root.unwrap().lock().data.url, "http://a/");
+        let merged = merge(vec![tree("http://a/"), tree("http://b/")], "targets.txt");
+        let root = merged.root.unwrap();
+        let root = root.lock();
+        assert_eq!(root.data.url, "targets.txt");
+        assert_eq!(
+            root.children
+                .iter()
+                .map(|child| child.lock().data.url.clone())
+                .collect::<Vec<_>>(),
+            vec!["http://a/", "http://b/"]
+        );
+    }
+
     #[test]
     fn test_tree_get_nodes_at_depth() {
         let mut tree = Tree::new();
@@ -343,6 +547,26 @@ mod tests {
         assert_eq!(tree_root.children[0].lock().data, "node2".to_string());
     }
 
+    #[test]
+    fn test_tree_node_flatten() {
+        let mut tree = Tree::new();
+        let node1 = tree.insert("node1".to_string(), None);
+        let node2 = tree.insert("node2".to_string(), Some(node1.clone()));
+        let _node3 = tree.insert("node3".to_string(), Some(node1.clone()));
+        let _node4 = tree.insert("node4".to_string(), Some(node2.clone()));
+
+        let datas = node1.lock().flatten();
+        assert_eq!(
+            datas,
+            vec![
+                "node1".to_string(),
+                "node2".to_string(),
+                "node4".to_string(),
+                "node3".to_string()
+            ]
+        );
+    }
+
     #[test]
     fn test_tree_item_write_self() {
         let node = TreeNode {
@@ -377,4 +601,103 @@ mod tests {
             "✓ 200 /test (dir)".to_string()
         );
     }
+
+    #[test]
+    fn test_to_dot() {
+        let data = |url: &str, path: &str, status_code| TreeData {
+            url: url.to_string(),
+            depth: 0,
+            path: path.to_string(),
+            status_code,
+            extra: Value::Null,
+            url_type: UrlType::Directory,
+            response: None,
+        };
+        let mut tree = Tree::new();
+        let root = tree.insert(data("http://example.com/", "", 0), None);
+        let admin = tree.insert(
+            data("http://example.com/admin", "/admin", 403),
+            Some(root.clone()),
+        );
+        tree.insert(
+            data("http://example.com/admin/\"x\"", "/\"x\"", 200),
+            Some(admin),
+        );
+        assert_eq!(
+            to_dot(&root.lock()),
+            "digraph rwalk {
+    node [shape=box, style=filled];
+    n0 [label=\"http://example.com/\", tooltip=\"0 http://example.com/\", fillcolor=lightgray];
+    n1 [label=\"admin\", tooltip=\"403 http://example.com/admin\", fillcolor=orange];
+    n2 [label=\"\\\"x\\\"\", tooltip=\"200 http://example.com/admin/\\\"x\\\"\", fillcolor=palegreen];
+    n1 -> n2;
+    n0 -> n1;
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_to_wordlist() {
+        let data = |url: &str| TreeData {
+            url: url.to_string(),
+            depth: 0,
+            path: String::new(),
+            status_code: 200,
+            extra: Value::Null,
+            url_type: UrlType::Unknown,
+            response: None,
+        };
+        let mut tree = Tree::new();
+        let root = tree.insert(data("http://example.com/app/"), None);
+        let admin = tree.insert(data("http://example.com/app/admin"), Some(root.clone()));
+        tree.insert(data("http://example.com/app/admin/login"), Some(admin));
+        tree.insert(data("http://example.com/app/admin"), Some(root.clone()));
+        assert_eq!(
+            to_wordlist(&root.lock(), false),
+            vec!["admin", "admin/login"]
+        );
+        assert_eq!(
+            to_wordlist(&root.lock(), true),
+            vec![
+                "http://example.com/app/admin",
+                "http://example.com/app/admin/login"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tested_words_from_save() {
+        let save = Save {
+            tree: Arc::new(Mutex::new(Tree::new())),
+            depth: Arc::new(Mutex::new(0)),
+            wordlist_checksum: String::new(),
+            indexes: HashMap::from([("http://localhost/".to_string(), vec![2, 0])]),
+            opts: Opts::default(),
+            chunks: vec![
+                vec!["admin".to_string(), "login".to_string(), "api".to_string()],
+                vec!["backup".to_string()],
+            ],
+            errors: Vec::new(),
+        };
+        let wordlist = |words: &[&str]| {
+            HashMap::from([(
+                "$".to_string(),
+                ParsedWordlist::new(
+                    String::new(),
+                    words.iter().map(|word| word.to_string()).collect(),
+                ),
+            )])
+        };
+        let tested = tested_words_from_save(
+            &save,
+            &wordlist(&["new", "admin", "login", "api", "backup", "other"]),
+        )
+        .unwrap();
+        assert_eq!(
+            tested["http://localhost/"],
+            HashSet::from(["admin".to_string(), "login".to_string()])
+        );
+        assert!(tested_words_from_save(&save, &wordlist(&["admin", "login", "api"])).is_err());
+    }
 }